@@ -0,0 +1,131 @@
+use crate::util::{parse_profile, Message};
+#[cfg(not(target_os = "windows"))]
+use anyhow::anyhow;
+use anyhow::Result;
+#[cfg(target_os = "windows")]
+use tokio::net::windows::named_pipe::{ClientOptions, ServerOptions};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader},
+    sync::mpsc::UnboundedSender,
+};
+#[cfg(not(target_os = "windows"))]
+use {
+    std::path::PathBuf,
+    tokio::net::{UnixListener, UnixStream},
+    win_alpha_core::config::get_config_dir,
+};
+
+#[cfg(target_os = "windows")]
+const PIPE_NAME: &str = r"\\.\pipe\WinAlpha";
+
+/*
+  Sends the profile to the WinAlpha that is already running, one line with its name (`base` for None).
+  Returns false when there is none to send it to.
+*/
+#[cfg(not(target_os = "windows"))]
+pub async fn forward_profile(profile: &Option<String>) -> bool {
+    let Ok(mut stream) = UnixStream::connect(get_socket_path()).await else {
+        return false;
+    };
+
+    send_profile(&mut stream, profile).await
+}
+
+#[cfg(target_os = "windows")]
+pub async fn forward_profile(profile: &Option<String>) -> bool {
+    let Ok(mut pipe) = ClientOptions::new().open(PIPE_NAME) else {
+        return false;
+    };
+
+    send_profile(&mut pipe, profile).await
+}
+
+/*
+  Listens for later launches with `--profile`, the profiles they send switch this one like the tray does.
+*/
+#[cfg(not(target_os = "windows"))]
+pub async fn listen_for_profiles(tx: UnboundedSender<Message>) -> Result<()> {
+    let socket_path = get_socket_path();
+
+    if UnixStream::connect(&socket_path).await.is_ok() {
+        return Err(anyhow!("Another WinAlpha is already listening"));
+    }
+
+    // Nothing answered on it, so it was left behind by a run that did not exit cleanly
+    if socket_path.exists() {
+        std::fs::remove_file(&socket_path)?;
+    }
+
+    let listener = UnixListener::bind(&socket_path)?;
+
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(receive_profile(stream, tx.clone()));
+                }
+                Err(e) => eprintln!("Failed to accept a profile switch: {}", e),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+pub async fn listen_for_profiles(tx: UnboundedSender<Message>) -> Result<()> {
+    let mut server = ServerOptions::new()
+        .first_pipe_instance(true)
+        .create(PIPE_NAME)?;
+
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = server.connect().await {
+                eprintln!("Failed to accept a profile switch: {}", e);
+                continue;
+            }
+
+            // The next launch connects to a new instance of the pipe while this one is read
+            let connected = match ServerOptions::new().create(PIPE_NAME) {
+                Ok(next) => std::mem::replace(&mut server, next),
+                Err(e) => {
+                    eprintln!("Stopped listening for profile switches: {}", e);
+                    return;
+                }
+            };
+
+            tokio::spawn(receive_profile(connected, tx.clone()));
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn get_socket_path() -> PathBuf {
+    get_config_dir().join("win_alpha.sock")
+}
+
+async fn send_profile(stream: &mut (impl AsyncWriteExt + Unpin), profile: &Option<String>) -> bool {
+    let line = format!("{}\n", profile.as_deref().unwrap_or("base"));
+
+    match stream.write_all(line.as_bytes()).await {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("Failed to send the profile to the running WinAlpha: {}", e);
+            false
+        }
+    }
+}
+
+async fn receive_profile(stream: impl AsyncRead + Unpin, tx: UnboundedSender<Message>) {
+    let mut name = String::new();
+
+    match BufReader::new(stream).read_line(&mut name).await {
+        Ok(_) if !name.trim().is_empty() => {
+            _ = tx.send(Message::Profile(parse_profile(name.trim())));
+        }
+        Ok(_) => {}
+        Err(e) => eprintln!("Failed to read a profile switch: {}", e),
+    }
+}
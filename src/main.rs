@@ -1,33 +1,81 @@
 #![windows_subsystem = "windows"]
 #![feature(let_chains)]
 use anyhow::Result;
-use app_state::AppState;
-use monitor::monitor_windows;
+use instance::{forward_profile, listen_for_profiles};
+use signals::listen_for_signals;
 use std::sync::Arc;
 use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
-use tray::{setup_tray, STARTUP_ID};
-use util::{load_config, Message};
-use win_utils::{change_startup, get_startup_state};
-mod app_state;
-mod monitor;
+use transparency::create_rules_window;
+use tray::{rebuild_tray, setup_tray, update_startup_item};
+use util::{load_config, profile_from_args, save_config, Message};
+use win_alpha_core::{
+    app_state::AppState,
+    backend,
+    config::{get_config_dir, get_config_path},
+    crash::install_panic_hook,
+    journal::{get_journal_path, replay_journal},
+    monitor::monitor_windows,
+};
+use win_utils::{add_window_rule, change_startup, get_startup_state};
+mod instance;
+mod signals;
 mod transparency;
 mod tray;
 mod util;
 mod win_utils;
-mod window_config;
 
 slint::include_modules!();
 
-#[cfg(target_os = "windows")]
 #[tokio::main]
 async fn main() -> Result<()> {
-    let (config, config_path) = load_config();
+    // Only puts back the windows a crashed run left changed
+    if std::env::args().any(|arg| arg == "--restore") {
+        let backend = backend::default_backend()?;
+        let restored = replay_journal(backend.as_ref(), &get_journal_path(&get_config_path()))?;
+        eprintln!("Restored {} windows", restored);
+        return Ok(());
+    }
+
+    let (mut config, config_path) = load_config();
+
+    // `--profile <name>` switches profile on launch, `--profile base` goes back to the base rules.
+    // With WinAlpha already running it switches that one instead.
+    if let Some(profile) = profile_from_args() {
+        if forward_profile(&profile).await {
+            return Ok(());
+        }
+
+        if let Err(e) = config.set_active_profile(profile) {
+            eprintln!("Failed to switch profile: {}", e);
+        } else if let Err(e) = save_config(&config, &config_path) {
+            eprintln!("Failed to save the active profile: {}", e);
+        }
+    }
     let (tx, mut rx): (UnboundedSender<Message>, UnboundedReceiver<Message>) =
         mpsc::unbounded_channel();
 
-    let mut tray = setup_tray(tx.clone())?;
+    let mut tray = setup_tray(
+        tx.clone(),
+        &config.get_profile_names(),
+        config.get_active_profile(),
+    )?;
 
-    let app_state = Arc::new(AppState::new(config, config_path));
+    if let Err(e) = listen_for_signals(tx.clone()) {
+        eprintln!("Failed to listen for shutdown signals: {}", e);
+    }
+
+    if let Err(e) = listen_for_profiles(tx.clone()).await {
+        eprintln!("Failed to listen for profile switches: {}", e);
+    }
+
+    let backend = backend::default_backend()?;
+    install_panic_hook(
+        backend.clone(),
+        get_journal_path(&config_path),
+        get_config_dir(),
+    );
+
+    let app_state = Arc::new(AppState::new(config, config_path, backend));
     let clone_state = app_state.clone();
 
     tokio::spawn(async move {
@@ -37,17 +85,18 @@ async fn main() -> Result<()> {
     loop {
         if let Some(event) = rx.recv().await {
             match event {
+                // From the tray or a shutdown signal, returns once the windows are restored
                 Message::Quit => {
                     app_state.quit().await;
                     return Ok(());
                 }
                 Message::Rules => {
-                    if let Err(e) = app_state.show_rules_window().await {
+                    if let Err(e) = create_rules_window(app_state.clone(), tx.clone()).await {
                         eprintln!("Error in rules window: {}", e);
                     }
                 }
                 Message::Add => {
-                    if let Err(e) = app_state.add_window_rule().await {
+                    if let Err(e) = add_window_rule(app_state.clone()).await {
                         eprintln!("Error in selection window: {}", e);
                     }
                 }
@@ -57,11 +106,24 @@ async fn main() -> Result<()> {
                 Message::Disable => {
                     app_state.disable().await;
                 }
+                Message::Profile(profile) => {
+                    if let Err(e) = app_state.set_active_profile(profile).await {
+                        eprintln!("Failed to switch profile: {}", e);
+                    }
+                    _ = tx.send(Message::ProfilesChanged);
+                }
+                Message::ProfilesChanged => {
+                    let config = app_state.get_config().await;
+                    rebuild_tray(
+                        &mut tray,
+                        tx.clone(),
+                        &config.get_profile_names(),
+                        config.get_active_profile(),
+                    )?;
+                }
                 Message::Startup => {
                     _ = change_startup(!get_startup_state());
-                    let state_string = format!("Startup - {}", get_startup_state());
-                    tray.inner_mut()
-                        .set_menu_item_label(&state_string, STARTUP_ID)?;
+                    update_startup_item(&mut tray)?;
                 }
             }
         }
@@ -0,0 +1,153 @@
+use crate::util::Message;
+use anyhow::Result;
+#[cfg(target_os = "windows")]
+use std::{cell::RefCell, thread};
+use tokio::sync::mpsc::UnboundedSender;
+#[cfg(target_os = "windows")]
+use windows::{
+    core::w,
+    Win32::{
+        Foundation::{HWND, LPARAM, LRESULT, WPARAM},
+        UI::WindowsAndMessaging::{
+            CreateWindowExW, DefWindowProcW, DispatchMessageW, GetMessageW, RegisterClassW, MSG,
+            WINDOW_EX_STYLE, WINDOW_STYLE, WM_ENDSESSION, WM_QUERYENDSESSION, WNDCLASSW,
+        },
+    },
+};
+
+#[cfg(target_os = "windows")]
+thread_local! {
+    // Window procedures carry no user data, the session window thread keeps its sender here.
+    static SESSION_SENDER: RefCell<Option<UnboundedSender<Message>>> = const { RefCell::new(None) };
+}
+
+/*
+  Turns the ways the OS asks WinAlpha to exit into a Quit, so they get the same shutdown as the tray item.
+*/
+#[cfg(not(target_os = "windows"))]
+pub fn listen_for_signals(tx: UnboundedSender<Message>) -> Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut hangup = signal(SignalKind::hangup())?;
+
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = interrupt.recv() => {}
+            _ = terminate.recv() => {}
+            _ = hangup.recv() => {}
+        }
+
+        _ = tx.send(Message::Quit);
+    });
+
+    Ok(())
+}
+
+/*
+  Turns the ways the OS asks WinAlpha to exit into a Quit, so they get the same shutdown as the tray item.
+  Console close, logoff and shutdown keep the process alive until main returns.
+  GUI processes do not get the logoff and shutdown console events, those come from the session window instead.
+*/
+#[cfg(target_os = "windows")]
+pub fn listen_for_signals(tx: UnboundedSender<Message>) -> Result<()> {
+    use tokio::signal::windows::{ctrl_break, ctrl_c, ctrl_close, ctrl_logoff, ctrl_shutdown};
+
+    let mut interrupt = ctrl_c()?;
+    let mut ctrl_break = ctrl_break()?;
+    let mut close = ctrl_close()?;
+    let mut logoff = ctrl_logoff()?;
+    let mut shutdown = ctrl_shutdown()?;
+
+    spawn_session_window(tx.clone());
+
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = interrupt.recv() => {}
+            _ = ctrl_break.recv() => {}
+            _ = close.recv() => {}
+            _ = logoff.recv() => {}
+            _ = shutdown.recv() => {}
+        }
+
+        _ = tx.send(Message::Quit);
+    });
+
+    Ok(())
+}
+
+/*
+  A hidden top level window, only there to be told the session is ending.
+  Message only windows do not get WM_QUERYENDSESSION, so this one is a real (never shown) window.
+*/
+#[cfg(target_os = "windows")]
+fn spawn_session_window(tx: UnboundedSender<Message>) {
+    thread::spawn(move || unsafe {
+        SESSION_SENDER.set(Some(tx));
+
+        let class = WNDCLASSW {
+            lpfnWndProc: Some(session_window_proc),
+            lpszClassName: w!("WinAlphaSession"),
+            ..Default::default()
+        };
+
+        if RegisterClassW(&class) == 0 {
+            eprintln!("Failed to register the session window, logoff will not restore windows");
+            return;
+        }
+
+        if let Err(e) = CreateWindowExW(
+            WINDOW_EX_STYLE::default(),
+            w!("WinAlphaSession"),
+            w!("WinAlpha"),
+            WINDOW_STYLE::default(),
+            0,
+            0,
+            0,
+            0,
+            None,
+            None,
+            None,
+            None,
+        ) {
+            eprintln!("Failed to create the session window: {}", e);
+            return;
+        }
+
+        let mut message = MSG::default();
+
+        // -1 is an error, 0 is WM_QUIT
+        while GetMessageW(&mut message, None, 0, 0).0 > 0 {
+            DispatchMessageW(&message);
+        }
+    });
+}
+
+#[cfg(target_os = "windows")]
+unsafe extern "system" fn session_window_proc(
+    hwnd: HWND,
+    message: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    match message {
+        // Never block the session from ending
+        WM_QUERYENDSESSION => LRESULT(1),
+        // wparam is zero when the session end was cancelled
+        WM_ENDSESSION if wparam.0 != 0 => {
+            SESSION_SENDER.with_borrow(|sender| {
+                if let Some(tx) = sender {
+                    _ = tx.send(Message::Quit);
+                }
+            });
+
+            // The process can be ended as soon as this returns, main exits it once the windows are restored
+            loop {
+                thread::park();
+            }
+        }
+        WM_ENDSESSION => LRESULT(0),
+        _ => DefWindowProcW(hwnd, message, wparam, lparam),
+    }
+}
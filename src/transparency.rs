@@ -1,18 +1,85 @@
-use crate::{app_state::AppState, RulesStorage, RulesWindow, TransparencyRule};
-use slint::{ComponentHandle, Model, VecModel};
+use crate::{util::Message, RulesStorage, RulesWindow, TransparencyRule};
+use slint::{ComponentHandle, Model, SharedString, VecModel, Weak};
 use std::{rc::Rc, sync::Arc};
+use tokio::sync::mpsc::UnboundedSender;
+use win_alpha_core::{
+    app_state::AppState,
+    config::Config,
+    hover::HoverSettings,
+    monitor_match::MonitorMatch,
+    window_config::{convert_to_full, convert_to_human, WindowConfig},
+};
+
+pub async fn get_window_rules(app_state: &AppState) -> Vec<TransparencyRule> {
+    let config = app_state.get_config().await;
+    config
+        .get_windows_in_order()
+        .into_iter()
+        .map(|window_config| {
+            // A profile can move base rules without copying them
+            let mut rule = TransparencyRule::from(window_config);
+            rule.priority = config.get_priority(window_config);
+            rule
+        })
+        .collect()
+}
+
+/*
+  Rules that can not be moved past the others, the same as WindowConfig::is_active_exclusion.
+*/
+fn is_pinned(rule: &TransparencyRule) -> bool {
+    rule.exclude && rule.enabled
+}
+
+/*
+  "Base" and then the profile names, with the position of the active one.
+*/
+fn get_profile_choices(config: &Config) -> (Vec<SharedString>, i32) {
+    let profiles = config.get_profile_names();
+    let active_profile = config
+        .get_active_profile()
+        .and_then(|active| profiles.iter().position(|name| name == active))
+        .map_or(0, |position| position + 1);
+
+    let names = std::iter::once("Base".into())
+        .chain(profiles.iter().map(SharedString::from))
+        .collect();
+
+    (names, active_profile as i32)
+}
+
+/*
+  Shows the profiles and the rules of the active one again, after switching, adding or deleting a profile.
+*/
+async fn refresh_rules_window(app_state: &AppState, window_handle: Weak<RulesWindow>) {
+    let (profiles, active_profile) = get_profile_choices(&app_state.get_config().await);
+    let rules = get_window_rules(app_state).await;
+
+    _ = window_handle.upgrade_in_event_loop(move |window| {
+        let storage = window.global::<RulesStorage>();
+        storage.set_profiles(Rc::new(VecModel::from(profiles)).into());
+        storage.set_active_profile(active_profile);
+
+        let items = storage.get_items();
+        if let Some(model) = items.as_any().downcast_ref::<VecModel<TransparencyRule>>() {
+            model.set_vec(rules);
+        }
+    });
+}
 
 /*
   Creates the rules window, this is so the user can see what rules are currently active.
   There is hardcoded minimum of 30%
 */
-pub async fn create_rules_window(app_state: Arc<AppState>) -> Result<(), core::fmt::Error> {
+pub async fn create_rules_window(
+    app_state: Arc<AppState>,
+    tx: UnboundedSender<Message>,
+) -> Result<(), core::fmt::Error> {
     let window = RulesWindow::new().unwrap();
     let window_handle = window.as_weak();
 
-    // Create the model once
-    let mut window_info = app_state.get_window_rules().await;
-    window_info.sort_by_key(|rule| rule.process_name.clone());
+    // Create the model once, in the order the rules win
+    let window_info = get_window_rules(&app_state).await;
     let items_model = Rc::new(VecModel::from(window_info));
 
     // Set initial items
@@ -20,41 +87,160 @@ pub async fn create_rules_window(app_state: Arc<AppState>) -> Result<(), core::f
         .global::<RulesStorage>()
         .set_items(items_model.clone().into());
 
-    // Handle submit events
+    // Base rules first, then the profiles
+    {
+        let (profiles, active_profile) = get_profile_choices(&app_state.get_config().await);
+        let storage = window.global::<RulesStorage>();
+
+        storage.set_profiles(Rc::new(VecModel::from(profiles)).into());
+        storage.set_active_profile(active_profile);
+    }
+
+    // Switching profile shows the rules of the new one
+    let app_clone = app_state.clone();
+    let profile_handle = window.as_weak();
+
+    let tray_tx = tx.clone();
+
+    window.on_select_profile(move |index| {
+        let Some(window) = profile_handle.upgrade() else {
+            return;
+        };
+        let profile = window
+            .global::<RulesStorage>()
+            .get_profiles()
+            .row_data(index as usize)
+            .filter(|_| index > 0)
+            .map(String::from);
+        let app_state = app_clone.clone();
+        let profile_handle = profile_handle.clone();
+        let tray_tx = tray_tx.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = app_state.set_active_profile(profile).await {
+                eprintln!("Failed to switch profile: {}", e);
+            }
+
+            _ = tray_tx.send(Message::ProfilesChanged);
+            refresh_rules_window(&app_state, profile_handle).await;
+        });
+    });
+
+    // A new profile starts empty and becomes the active one, the tray menu is rebuilt to list it
     let app_clone = app_state.clone();
+    let profile_handle = window.as_weak();
+    let tray_tx = tx.clone();
+
+    window.on_add_profile(move |name| {
+        let app_state = app_clone.clone();
+        let profile_handle = profile_handle.clone();
+        let tray_tx = tray_tx.clone();
+        let name = name.trim().to_owned();
 
-    window.on_submit(move |value: TransparencyRule| {
-        app_clone.spawn_update_config(value.clone().into());
+        tokio::spawn(async move {
+            if let Err(e) = app_state.add_profile(name).await {
+                eprintln!("Failed to add profile: {}", e);
+                return;
+            }
+
+            _ = tray_tx.send(Message::ProfilesChanged);
+            refresh_rules_window(&app_state, profile_handle).await;
+        });
     });
 
-    let items_model_weak = window.as_weak();
-    // Handle force events
-    window.on_force(move |value: TransparencyRule| {
-        app_state.spawn_force_config(value.clone().into());
+    let app_clone = app_state.clone();
+    let profile_handle = window.as_weak();
+
+    window.on_delete_profile(move |index| {
+        let Some(name) = profile_handle.upgrade().and_then(|window| {
+            window
+                .global::<RulesStorage>()
+                .get_profiles()
+                .row_data(index as usize)
+                .filter(|_| index > 0)
+        }) else {
+            return;
+        };
+        let app_state = app_clone.clone();
+        let profile_handle = profile_handle.clone();
+        let tray_tx = tx.clone();
 
-        let handle = items_model_weak.clone();
-        let app_state_clone = app_state.clone();
         tokio::spawn(async move {
-            let current_items = app_state_clone.get_window_rules().await;
-
-            handle.upgrade_in_event_loop(move |window| {
-                if let Some(items_vec) = window
-                    .global::<RulesStorage>()
-                    .get_items()
-                    .as_any()
-                    .downcast_ref::<VecModel<TransparencyRule>>()
-                {
-                    if let Some(idx) = (0..items_vec.row_count()).find(|&i| {
-                        items_vec.row_data(i).unwrap().process_name == value.process_name
-                    }) {
-                        items_vec.set_row_data(idx, current_items.get(idx).unwrap().clone());
-                    }
-                }
-            })?;
-            Ok::<(), anyhow::Error>(())
+            if let Err(e) = app_state.remove_profile(name.into()).await {
+                eprintln!("Failed to delete profile: {}", e);
+                return;
+            }
+
+            _ = tray_tx.send(Message::ProfilesChanged);
+            refresh_rules_window(&app_state, profile_handle).await;
         });
     });
 
+    // Handle submit events, an edit that changes the key replaces the rule saved under the old one
+    let app_clone = app_state.clone();
+    let submit_model = items_model.clone();
+
+    window.on_submit(move |old_key, value: TransparencyRule| {
+        let old_key = old_key.to_string();
+        let window_config = WindowConfig::from(value);
+        let key = window_config.get_key();
+
+        if key == old_key {
+            app_clone.spawn_update_config(window_config);
+            return;
+        }
+
+        // Later edits of the row are saved under the new key
+        if let Some(row) = (0..submit_model.row_count()).find(|&row| {
+            submit_model
+                .row_data(row)
+                .is_some_and(|rule| rule.key == old_key)
+        }) && let Some(mut rule) = submit_model.row_data(row)
+        {
+            rule.key = key.into();
+            submit_model.set_row_data(row, rule);
+        }
+
+        app_clone.spawn_replace_config(old_key, window_config);
+    });
+
+    // Handle reordering, every rule gets a new priority from its position
+    let app_clone = app_state.clone();
+    let reorder_model = items_model.clone();
+
+    window.on_move_rule(move |index, offset| {
+        let (Ok(index), Ok(target)) = (usize::try_from(index), usize::try_from(index + offset))
+        else {
+            return;
+        };
+        let (Some(rule), Some(other)) = (
+            reorder_model.row_data(index),
+            reorder_model.row_data(target),
+        ) else {
+            return;
+        };
+        // Exclusions always come first when rules are applied, so they stay above the other rules here too
+        if is_pinned(&rule) != is_pinned(&other) {
+            return;
+        }
+
+        reorder_model.set_row_data(index, other);
+        reorder_model.set_row_data(target, rule);
+
+        let count = reorder_model.row_count();
+        let mut keys = Vec::with_capacity(count);
+
+        for row in 0..count {
+            if let Some(mut rule) = reorder_model.row_data(row) {
+                rule.priority = (count - row) as i32;
+                keys.push(rule.key.to_string());
+                reorder_model.set_row_data(row, rule);
+            }
+        }
+
+        app_clone.spawn_reorder_rules(keys);
+    });
+
     // Handle cancel events
     window.on_cancel(move || {
         if let Some(window) = window_handle.upgrade() {
@@ -65,3 +251,90 @@ pub async fn create_rules_window(app_state: Arc<AppState>) -> Result<(), core::f
     window.run().unwrap();
     Ok(())
 }
+
+impl From<&WindowConfig> for TransparencyRule {
+    fn from(config: &WindowConfig) -> Self {
+        TransparencyRule {
+            key: config.get_key().into(),
+            process_name: config.get_name().into(),
+            window_class: config.get_window_class().into(),
+            process_match: config.get_process_match().to_string().into(),
+            class_match: config.get_class_match().to_string().into(),
+            window_title: config
+                .get_window_title()
+                .to_owned()
+                .unwrap_or_default()
+                .into(),
+            title_match: config.get_title_match().to_string().into(),
+            priority: config.get_priority(),
+            transparency: convert_to_human(config.get_transparency()).into(),
+            inactive_transparency: config
+                .get_inactive_transparency()
+                .map_or(0, |inactive| convert_to_human(inactive).into()),
+            hover: config.get_hover().is_some(),
+            hover_enter_delay: config
+                .get_hover()
+                .map_or(0, |hover| hover.enter_delay.min(i32::MAX as u64) as i32),
+            hover_leave_delay: config
+                .get_hover()
+                .map_or(0, |hover| hover.leave_delay.min(i32::MAX as u64) as i32),
+            enabled: config.is_enabled(),
+            apply_to: config.get_apply_to() as i32,
+            chain_depth: (config.get_class_chain().len() - 1) as i32,
+            exclude: config.is_exclude(),
+            lifetime: config.get_lifetime().to_string().into(),
+            lifetime_label: config.get_lifetime().describe().into(),
+            roles: config.get_roles_string().into(),
+            monitor: config
+                .get_monitor()
+                .map(MonitorMatch::to_string)
+                .unwrap_or_default()
+                .into(),
+            monitor_label: config
+                .get_monitor()
+                .map(MonitorMatch::describe)
+                .unwrap_or_default()
+                .into(),
+        }
+    }
+}
+
+impl From<TransparencyRule> for WindowConfig {
+    fn from(config: TransparencyRule) -> Self {
+        let mut window_config = WindowConfig::default();
+        window_config.set_name(config.process_name.into());
+        window_config.set_window_class(&config.window_class);
+        window_config.set_process_match(config.process_match.parse().unwrap_or_default());
+        window_config.set_class_match(config.class_match.parse().unwrap_or_default());
+        window_config.set_window_title(if config.window_title.is_empty() {
+            None
+        } else {
+            Some(config.window_title.into())
+        });
+        window_config.set_title_match(config.title_match.parse().unwrap_or_default());
+        window_config.set_priority(config.priority);
+        window_config.set_transparency(convert_to_full(config.transparency));
+        window_config.set_inactive_transparency(
+            (config.inactive_transparency > 0)
+                .then(|| convert_to_full(config.inactive_transparency)),
+        );
+        window_config.set_hover(config.hover.then(|| HoverSettings {
+            enter_delay: config.hover_enter_delay.max(0) as u64,
+            leave_delay: config.hover_leave_delay.max(0) as u64,
+        }));
+        window_config.set_enabled(config.enabled);
+        window_config.set_apply_to(config.apply_to.max(0) as usize);
+        window_config.set_exclude(config.exclude);
+        window_config.set_lifetime(config.lifetime.parse().unwrap_or_default());
+        window_config.set_roles(
+            config
+                .roles
+                .split(',')
+                .filter_map(|role| role.parse().ok())
+                .collect(),
+        );
+        window_config.set_monitor(config.monitor.parse().ok());
+
+        window_config
+    }
+}
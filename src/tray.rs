@@ -1,12 +1,49 @@
 use crate::{get_startup_state, util::Message};
+use anyhow::Result;
 use tokio::sync::mpsc::UnboundedSender;
-use tray_item::{IconSource, TIError, TrayItem};
+#[cfg(target_os = "windows")]
+use tray_item::{IconSource, TrayItem};
 
 // ID for startup menu item
-pub const STARTUP_ID: u32 = 5;
+#[cfg(target_os = "windows")]
+const STARTUP_ID: u32 = 5;
 
-pub fn setup_tray(tx: UnboundedSender<Message>) -> Result<TrayItem, TIError> {
-    let mut tray = TrayItem::new("WinAlpha", IconSource::Resource("tray-default"))?;
+// Embedded resource on Windows, an icon theme name everywhere else
+#[cfg(target_os = "windows")]
+const TRAY_ICON: &str = "tray-default";
+#[cfg(not(target_os = "windows"))]
+const TRAY_ICON: &str = "preferences-desktop-display";
+
+/*
+  The tray icon and its menu.
+  On Windows this is tray_item, elsewhere the menu is served with ksni directly so it can change while WinAlpha runs.
+*/
+#[cfg(target_os = "windows")]
+pub struct Tray(TrayItem);
+
+#[cfg(not(target_os = "windows"))]
+pub struct Tray(ksni::Handle<TrayMenu>);
+
+fn startup_label() -> String {
+    format!("Startup: {}", get_startup_state())
+}
+
+// The profile in use is marked, so the menu shows which one the rules come from
+fn profile_label(name: &str, active: bool) -> String {
+    if active {
+        format!("● Profile: {}", name)
+    } else {
+        format!("Profile: {}", name)
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub fn setup_tray(
+    tx: UnboundedSender<Message>,
+    profiles: &[String],
+    active_profile: Option<&str>,
+) -> Result<Tray> {
+    let mut tray = TrayItem::new("WinAlpha", IconSource::Resource(TRAY_ICON))?;
 
     add_tray_menu_item(&mut tray, "Add", &tx, Message::Add)?;
     add_tray_menu_item(&mut tray, "Rules", &tx, Message::Rules)?;
@@ -15,10 +52,8 @@ pub fn setup_tray(tx: UnboundedSender<Message>) -> Result<TrayItem, TIError> {
 
     tray.inner_mut().add_separator()?;
 
-    let startup_label = format!("Startup: {}", get_startup_state());
-
     let startup_tx = tx.clone();
-    tray.add_menu_item(&startup_label, move || {
+    tray.add_menu_item(&startup_label(), move || {
         if let Err(e) = startup_tx.send(Message::Startup) {
             eprintln!("Failed to send Startup message: {}", e);
         }
@@ -26,24 +61,213 @@ pub fn setup_tray(tx: UnboundedSender<Message>) -> Result<TrayItem, TIError> {
 
     tray.inner_mut().add_separator()?;
 
+    // Added after the startup item so its id stays the same
+    if !profiles.is_empty() {
+        add_profile_menu_item(
+            &mut tray,
+            &profile_label("Base", active_profile.is_none()),
+            &tx,
+            None,
+        )?;
+
+        for profile in profiles {
+            add_profile_menu_item(
+                &mut tray,
+                &profile_label(profile, active_profile == Some(profile.as_str())),
+                &tx,
+                Some(profile.clone()),
+            )?;
+        }
+
+        tray.inner_mut().add_separator()?;
+    }
+
     add_tray_menu_item(&mut tray, "Quit", &tx, Message::Quit)?;
 
-    Ok(tray)
+    Ok(Tray(tray))
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn setup_tray(
+    tx: UnboundedSender<Message>,
+    profiles: &[String],
+    active_profile: Option<&str>,
+) -> Result<Tray> {
+    let service = ksni::TrayService::new(TrayMenu {
+        tx,
+        startup: startup_label(),
+        profiles: profiles.to_vec(),
+        active_profile: active_profile.map(str::to_owned),
+    });
+    let handle = service.handle();
+    service.spawn();
+
+    Ok(Tray(handle))
+}
+
+/*
+  Shows the profiles as they are now, after one was added, deleted or switched to.
+  tray_item has no way to remove menu items, so on Windows the tray is replaced and dropping the old one takes its icon down.
+*/
+#[cfg(target_os = "windows")]
+pub fn rebuild_tray(
+    tray: &mut Tray,
+    tx: UnboundedSender<Message>,
+    profiles: &[String],
+    active_profile: Option<&str>,
+) -> Result<()> {
+    *tray = setup_tray(tx, profiles, active_profile)?;
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn rebuild_tray(
+    tray: &mut Tray,
+    _tx: UnboundedSender<Message>,
+    profiles: &[String],
+    active_profile: Option<&str>,
+) -> Result<()> {
+    tray.0.update(|menu| {
+        menu.profiles = profiles.to_vec();
+        menu.active_profile = active_profile.map(str::to_owned);
+    });
+
+    Ok(())
+}
+
+/*
+  Shows whether WinAlpha starts with the system, after it was changed.
+*/
+#[cfg(target_os = "windows")]
+pub fn update_startup_item(tray: &mut Tray) -> Result<()> {
+    tray.0
+        .inner_mut()
+        .set_menu_item_label(&startup_label(), STARTUP_ID)?;
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn update_startup_item(tray: &mut Tray) -> Result<()> {
+    tray.0.update(|menu| menu.startup = startup_label());
+
+    Ok(())
 }
 
 /*
  Just adds another tray item. Keeps the above method clean
 */
+#[cfg(target_os = "windows")]
 fn add_tray_menu_item(
     tray: &mut TrayItem,
     label: &'static str,
     tx: &UnboundedSender<Message>,
     message: Message,
-) -> Result<(), TIError> {
+) -> Result<()> {
     let tx_clone = tx.clone();
     tray.add_menu_item(label, move || {
         if let Err(e) = tx_clone.send(message.clone()) {
             eprintln!("Failed to send {} message: {}", label, e);
         }
-    })
+    })?;
+
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+fn add_profile_menu_item(
+    tray: &mut TrayItem,
+    label: &str,
+    tx: &UnboundedSender<Message>,
+    profile: Option<String>,
+) -> Result<()> {
+    let tx_clone = tx.clone();
+    tray.add_menu_item(label, move || {
+        if let Err(e) = tx_clone.send(Message::Profile(profile.clone())) {
+            eprintln!("Failed to send Profile message: {}", e);
+        }
+    })?;
+
+    Ok(())
+}
+
+/*
+  What the ksni menu is built from, it is built again whenever this changes.
+*/
+#[cfg(not(target_os = "windows"))]
+pub struct TrayMenu {
+    tx: UnboundedSender<Message>,
+    startup: String,
+    profiles: Vec<String>,
+    active_profile: Option<String>,
+}
+
+#[cfg(not(target_os = "windows"))]
+impl TrayMenu {
+    fn send(&self, message: Message) {
+        if let Err(e) = self.tx.send(message) {
+            eprintln!("Failed to send tray message: {}", e);
+        }
+    }
+
+    fn item(label: &str, message: Message) -> ksni::MenuItem<Self> {
+        ksni::menu::StandardItem {
+            label: label.to_owned(),
+            activate: Box::new(move |menu: &mut Self| menu.send(message.clone())),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+impl ksni::Tray for TrayMenu {
+    fn id(&self) -> String {
+        "WinAlpha".to_owned()
+    }
+
+    fn title(&self) -> String {
+        "WinAlpha".to_owned()
+    }
+
+    fn icon_name(&self) -> String {
+        TRAY_ICON.to_owned()
+    }
+
+    fn menu(&self) -> Vec<ksni::MenuItem<Self>> {
+        let mut menu = vec![
+            Self::item("Add", Message::Add),
+            Self::item("Rules", Message::Rules),
+            Self::item("Enable", Message::Enable),
+            Self::item("Disable", Message::Disable),
+            ksni::MenuItem::Separator,
+            Self::item(&self.startup, Message::Startup),
+            ksni::MenuItem::Separator,
+        ];
+
+        if !self.profiles.is_empty() {
+            let labels = std::iter::once(profile_label("Base", self.active_profile.is_none()))
+                .chain(self.profiles.iter().map(|profile| {
+                    profile_label(profile, self.active_profile.as_ref() == Some(profile))
+                }));
+
+            // Underscores mark the access key in a ksni label, doubled they are shown as they are
+            menu.extend(labels.enumerate().map(|(index, label)| {
+                Self::item(
+                    &label.replace('_', "__"),
+                    Message::Profile(
+                        index
+                            .checked_sub(1)
+                            .map(|index| self.profiles[index].clone()),
+                    ),
+                )
+            }));
+            menu.push(ksni::MenuItem::Separator);
+        }
+
+        menu.push(Self::item("Quit", Message::Quit));
+
+        menu
+    }
 }
@@ -1,15 +1,14 @@
-use crate::{window_config::WindowConfig, ConfigWindow};
+use crate::ConfigWindow;
 use anyhow::{anyhow, Error};
-use directories::ProjectDirs;
-use serde::{Deserialize, Serialize};
 use serde_json::from_str;
 use slint::ComponentHandle;
 use std::{
-    collections::HashMap,
-    fs::{self, create_dir_all},
+    fs,
     path::PathBuf,
     sync::{Arc, Mutex},
 };
+use win_alpha_core::config::{get_config_path, Config};
+#[cfg(target_os = "windows")]
 use windows::{
     core::{w, PCWSTR},
     Win32::UI::Shell::ShellExecuteW,
@@ -23,27 +22,10 @@ pub enum Message {
     Enable,
     Disable,
     Startup,
-}
-
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct Config {
-    windows: HashMap<String, WindowConfig>,
-}
-
-impl Config {
-    pub fn new() -> Self {
-        Self {
-            windows: HashMap::new(),
-        }
-    }
-
-    pub fn get_windows(&mut self) -> &mut HashMap<String, WindowConfig> {
-        &mut self.windows
-    }
-
-    pub fn get_windows_non_mut(&self) -> &HashMap<String, WindowConfig> {
-        &self.windows
-    }
+    // None is the base rules
+    Profile(Option<String>),
+    // A profile was added, deleted or switched to, the tray menu lists them and marks the active one
+    ProfilesChanged,
 }
 
 pub fn create_config_error_window(config_path: PathBuf) -> Result<(), Error> {
@@ -62,31 +44,16 @@ pub fn create_config_error_window(config_path: PathBuf) -> Result<(), Error> {
     let config_clone_cancel = config_clone.clone();
 
     window.on_submit(move |value| match value {
-        crate::Action::Edit => unsafe {
+        crate::Action::Edit => {
             // shhh its okay sometimes
             *action_taken.lock().unwrap() = true;
             match config_clone_submit.lock() {
-                Ok(path) => {
-                    ShellExecuteW(
-                        None,
-                        w!("open"),
-                        PCWSTR::from_raw(
-                            path.as_str()
-                                .encode_utf16()
-                                .chain(Some(0))
-                                .collect::<Vec<u16>>()
-                                .as_ptr(),
-                        ),
-                        None,
-                        None,
-                        windows::Win32::UI::WindowsAndMessaging::SHOW_WINDOW_CMD(1),
-                    );
-                }
+                Ok(path) => open_file(path.as_str()),
                 Err(_) => {
                     _ = anyhow!("AHHHHH");
                 }
             };
-        },
+        }
         crate::Action::Reset => {
             match action_taken.lock() {
                 Ok(mut action_state) => {
@@ -124,20 +91,46 @@ pub fn create_config_error_window(config_path: PathBuf) -> Result<(), Error> {
     Ok(())
 }
 
-pub fn load_config() -> (Config, PathBuf) {
-    let project_dirs = ProjectDirs::from("com", "windowtransparency", "winalpha")
-        .expect("Failed to get project config directories.");
-
-    let config_dir = project_dirs.config_dir();
+/*
+  Opens the file with whatever program the user has set for it.
+*/
+#[cfg(target_os = "windows")]
+fn open_file(path: &str) {
+    unsafe {
+        ShellExecuteW(
+            None,
+            w!("open"),
+            PCWSTR::from_raw(
+                path.encode_utf16()
+                    .chain(Some(0))
+                    .collect::<Vec<u16>>()
+                    .as_ptr(),
+            ),
+            None,
+            None,
+            windows::Win32::UI::WindowsAndMessaging::SHOW_WINDOW_CMD(1),
+        );
+    }
+}
 
-    create_dir_all(config_dir).ok();
+#[cfg(not(target_os = "windows"))]
+fn open_file(path: &str) {
+    _ = std::process::Command::new("xdg-open").arg(path).spawn();
+}
 
-    let config_path = config_dir.join("config.json");
+pub fn load_config() -> (Config, PathBuf) {
+    let config_path = get_config_path();
 
     if config_path.exists()
         && let Ok(config_data) = fs::read_to_string(&config_path)
     {
-        if let Ok(existing) = from_str::<Config>(&config_data) {
+        if let Ok(mut existing) = from_str::<Config>(&config_data) {
+            if existing.migrate()
+                && let Err(e) = save_config(&existing, &config_path)
+            {
+                eprintln!("Failed to save the migrated config: {}", e);
+            }
+
             (existing, config_path)
         } else {
             _ = create_config_error_window(config_path);
@@ -148,3 +141,26 @@ pub fn load_config() -> (Config, PathBuf) {
         (Config::new(), config_path)
     }
 }
+
+pub fn save_config(config: &Config, config_path: &PathBuf) -> Result<(), Error> {
+    fs::write(config_path, serde_json::to_string_pretty(config)?)?;
+    Ok(())
+}
+
+/*
+  Returns the profile asked for with `--profile <name>`, Some(None) for `base`.
+*/
+pub fn profile_from_args() -> Option<Option<String>> {
+    let mut args = std::env::args()
+        .skip_while(|arg| arg != "--profile")
+        .skip(1);
+
+    args.next().map(|name| parse_profile(&name))
+}
+
+/*
+  The profile a name picks, None for `base`.
+*/
+pub fn parse_profile(name: &str) -> Option<String> {
+    (!name.eq_ignore_ascii_case("base")).then(|| name.to_owned())
+}
@@ -1,106 +1,105 @@
-use crate::{
-    app_state::AppState, window_config::WindowConfig, MouseInfo, PercentageInput, PercentageWindow,
-};
+use crate::{MouseInfo, PercentageInput, PercentageWindow};
 use anyhow::{anyhow, Result};
 use core::time::Duration;
 use crossbeam_channel::{bounded, Receiver, Sender};
 use slint::{ComponentHandle, PhysicalPosition, SharedString};
+#[cfg(not(target_os = "windows"))]
+use std::fs;
 use std::{
     env::current_exe,
-    os::raw::c_void,
     sync::Arc,
     thread::{self, sleep},
     time::Instant,
 };
+use win_alpha_core::{
+    app_state::AppState,
+    backend::{WindowBackend, WindowRole},
+    config::Config,
+    hover::HoverSettings,
+    lifetime::RuleLifetime,
+    monitor_match::MonitorMatch,
+    window_config::{convert_to_full, WindowConfig, WindowInfo},
+};
+#[cfg(target_os = "windows")]
 use windows::{
-    core::{PCSTR, PWSTR},
+    core::PCSTR,
     Win32::{
-        Foundation::{COLORREF, ERROR_SUCCESS, HANDLE, HWND, MAX_PATH, POINT},
-        Security::{GetTokenInformation, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY},
-        System::{
-            Registry::{
-                RegCloseKey, RegCreateKeyExA, RegDeleteValueA, RegOpenKeyExA, RegQueryValueExA,
-                RegSetValueExA, HKEY, HKEY_CURRENT_USER, KEY_ALL_ACCESS, KEY_READ,
-                REG_OPTION_NON_VOLATILE, REG_SZ,
-            },
-            Threading::{
-                GetCurrentProcess, OpenProcess, OpenProcessToken, QueryFullProcessImageNameW,
-                PROCESS_NAME_FORMAT, PROCESS_QUERY_LIMITED_INFORMATION,
-            },
-        },
-        UI::{
-            Input::KeyboardAndMouse::{GetAsyncKeyState, VK_LBUTTON},
-            WindowsAndMessaging::{
-                GetClassNameW, GetCursorPos, GetWindowLongW, GetWindowThreadProcessId,
-                SetLayeredWindowAttributes, SetWindowLongW, WindowFromPoint, GWL_EXSTYLE,
-                LAYERED_WINDOW_ATTRIBUTES_FLAGS, WS_EX_LAYERED,
-            },
+        Foundation::ERROR_SUCCESS,
+        System::Registry::{
+            RegCloseKey, RegCreateKeyExA, RegDeleteValueA, RegOpenKeyExA, RegQueryValueExA,
+            RegSetValueExA, HKEY, HKEY_CURRENT_USER, KEY_ALL_ACCESS, KEY_READ,
+            REG_OPTION_NON_VOLATILE, REG_SZ,
         },
     },
 };
 
-// This is "left click"
-const KEY_PRESSED: i16 = 0x8000u16 as i16;
-
 // Aligns the mouse cursor (window scaling will break this)
 const MOUSE_OFFSET: i32 = 15;
 
-const MINIMUM_TRANSPARENCY: i32 = 30;
-
-#[derive(Debug, Clone, Default, PartialEq)]
-pub struct WindowInfo {
-    pub class_name: String,
-    pub process_name: String,
-}
-
 /*
   This function is called to allow the user to click on a window, the info about the window is returned.
 
   Note: Should really try to click on the border of the window,
    clicking inside can cause issues since programs have windows inside of other windows (that are not modal).
 */
-pub fn get_window_under_cursor() -> Result<WindowInfo> {
+pub fn get_window_under_cursor(
+    backend: Arc<dyn WindowBackend>,
+    config: Config,
+) -> Result<WindowInfo> {
     let window = MouseInfo::new()?;
     let handle_weak = window.as_weak();
     let (tx, rx): (Sender<WindowInfo>, Receiver<WindowInfo>) = bounded(1);
 
     let window_thread = thread::spawn(move || {
-        let mut click_point = POINT::default();
-        let mut click_point_old = POINT::default();
+        let mut click_point = (0, 0);
+        let mut click_point_old = (0, 0);
         let mut last_window_check = Instant::now();
         let mut window_info_old = WindowInfo::default();
 
         let window_check_interval = Duration::from_millis(25);
-        let is_admin = is_running_as_admin();
+        let is_admin = backend.is_running_as_admin();
 
         loop {
             let now = Instant::now();
 
-            unsafe {
-                if GetCursorPos(&mut click_point).is_ok() && click_point != click_point_old {
-                    click_point_old = click_point;
-                    handle_weak.upgrade_in_event_loop(move |handle| {
-                        handle.window().set_position(PhysicalPosition {
-                            x: click_point.x + MOUSE_OFFSET,
-                            y: click_point.y + MOUSE_OFFSET,
-                        });
-                    })?;
-                }
+            if let Some(cursor) = backend.get_cursor_position()
+                && cursor != click_point_old
+            {
+                click_point = cursor;
+                click_point_old = cursor;
+                handle_weak.upgrade_in_event_loop(move |handle| {
+                    handle.window().set_position(PhysicalPosition {
+                        x: cursor.0 + MOUSE_OFFSET,
+                        y: cursor.1 + MOUSE_OFFSET,
+                    });
+                })?;
             }
 
             if now.duration_since(last_window_check) >= window_check_interval {
                 last_window_check = now;
 
-                if let Some(window_info) = get_window_info(click_point).ok()
+                if let Some(window_info) = get_window_info(backend.as_ref(), click_point).ok()
                     && window_info_old != window_info
                 {
                     window_info_old = window_info.clone();
+                    let window_elevated = is_elevated(backend.as_ref(), click_point);
+                    let excluded = backend
+                        .window_from_point(click_point.0, click_point.1)
+                        .is_some_and(|window| {
+                            config.get_exclusion(backend.as_ref(), window).is_some()
+                        });
 
                     handle_weak.upgrade_in_event_loop(move |handle| {
                         handle.set_class_name(window_info.class_name.into());
                         handle.set_process_name(window_info.process_name.into());
+                        handle.set_window_title(window_info.title.into());
 
-                        if is_elevated(click_point) && !is_admin {
+                        if excluded {
+                            handle.set_opacity_error(1);
+                            handle.set_error_string(
+                                "Excluded, WinAlpha will leave this one alone".into(),
+                            );
+                        } else if window_elevated && !is_admin {
                             handle.set_opacity_error(1);
                             handle.set_error_string(
                                 "Not happening, we need admin rights for this one".into(),
@@ -112,8 +111,8 @@ pub fn get_window_under_cursor() -> Result<WindowInfo> {
                 }
             }
 
-            if is_left_click() {
-                let window_io = get_window_info(click_point)?;
+            if backend.is_primary_button_down() {
+                let window_io = get_window_info(backend.as_ref(), click_point)?;
                 tx.send(window_io)?;
 
                 // Back to main we go!
@@ -147,89 +146,49 @@ pub fn get_window_under_cursor() -> Result<WindowInfo> {
     }
 }
 
-#[inline]
-fn is_left_click() -> bool {
-    unsafe { (GetAsyncKeyState(VK_LBUTTON.0.into()) & KEY_PRESSED) != 0 }
-}
-
 /*
   Gets information that will be used to store and identify the window
 */
-fn get_window_info(point: POINT) -> Result<WindowInfo> {
-    unsafe {
-        let hwnd = WindowFromPoint(point);
-        if hwnd.0.is_null() {
-            return Err(anyhow!("No window found at cursor position."));
-        }
-
-        let mut class_name = [0u16; 256];
-        let class_len = GetClassNameW(hwnd, &mut class_name);
-        let window_class = String::from_utf16_lossy(&class_name[..class_len as usize]);
-
-        let mut process_id = 0;
-        GetWindowThreadProcessId(hwnd, Some(&mut process_id));
-
-        Ok(WindowInfo {
-            class_name: window_class,
-            process_name: get_process_name(process_id)?,
-        })
+fn get_window_info(backend: &dyn WindowBackend, point: (i32, i32)) -> Result<WindowInfo> {
+    let window = backend
+        .window_from_point(point.0, point.1)
+        .ok_or_else(|| anyhow!("No window found at cursor position."))?;
+
+    // Clicks often land on a window inside the top level one, a rule for it needs the classes above it
+    let mut parent_classes = Vec::new();
+    let mut current = window;
+    while !backend.is_top_level(current)
+        && let Some(parent) = backend.get_parent(current)
+    {
+        parent_classes.push(backend.get_window_class(parent).unwrap_or_default());
+        current = parent;
     }
+    parent_classes.reverse();
+
+    Ok(WindowInfo {
+        class_name: backend.get_window_class(window).unwrap_or_default(),
+        process_name: backend
+            .get_window_process_name(window)
+            .ok_or_else(|| anyhow!("Failed to get application name."))?,
+        title: backend.get_window_title(window).unwrap_or_default(),
+        process_id: backend.get_process_id(window).unwrap_or_default(),
+        monitor: backend.get_window_monitor(current),
+        parent_classes,
+    })
 }
 
 /*
-  Gets the process name from a provided process id.
+  Lets the user click the window they want a rule for, then asks them for the percentage.
 */
-pub fn get_process_name(process_id: u32) -> Result<String> {
-    unsafe {
-        // A holding buffer for the process name.
-        let mut buffer = [0u16; MAX_PATH as usize];
-        let mut size = buffer.len() as u32;
-
-        let process_handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, process_id)
-            .map_err(|_| anyhow!("Failed to get process handle."))?;
-
-        // Get process name
-        QueryFullProcessImageNameW(
-            process_handle,
-            PROCESS_NAME_FORMAT(0),
-            PWSTR(buffer.as_mut_ptr()),
-            &mut size,
-        )
-        .map_err(|_| anyhow!("Failed to get process name."))?;
-
-        // Extract filename without extension
-        let buffer_path = String::from_utf16_lossy(&buffer[..size as usize]);
-        let split_name = buffer_path
-            .rsplit('\\')
-            .next()
-            .and_then(|s| s.split('.').next());
-
-        if let Some(file_name) = split_name {
-            Ok(file_name.to_owned())
-        } else {
-            Err(anyhow!("Failed to get application name."))
-        }
-    }
-}
+pub async fn add_window_rule(app_state: Arc<AppState>) -> Result<(), anyhow::Error> {
+    let backend = app_state.get_backend();
 
-/*
-  Convert a value from 1 - 100 to its u8 (255) equivalent.
-*/
-pub fn convert_to_full(mut value: i32) -> u8 {
-    if value < MINIMUM_TRANSPARENCY {
-        value = MINIMUM_TRANSPARENCY;
-    }
-    if value > 100 {
-        return 255;
+    if !backend.can_pick_windows() {
+        return Err(anyhow!("Picking windows is not supported on this desktop."));
     }
-    ((value as f32 / 100.0) * 255.0).round() as u8
-}
 
-/*
-  Takes a u8 (255) value and converts it to a measurable format (a percentage of 100)
-*/
-pub fn convert_to_human(value: u8) -> u8 {
-    ((value as f32 / 255.0) * 100.0).round() as u8
+    let window = get_window_under_cursor(backend, app_state.get_config().await)?;
+    create_percentage_window(window, app_state).await
 }
 
 /*
@@ -242,31 +201,84 @@ pub async fn create_percentage_window(
     let window = PercentageWindow::new()?;
     let window_handle = window.as_weak();
     let submit_handle = window_handle.clone();
+    let exclude_handle = window_handle.clone();
 
     {
         let globals = window.global::<PercentageInput>();
         globals.set_name(window_info.process_name.clone().into());
         globals.set_classname(window_info.class_name.clone().into());
+        globals.set_title(window_info.title.clone().into());
     }
 
-    window.on_submit(move |value: SharedString| {
-        if value.is_empty() {
-            return;
-        }
-
-        if let Ok(number) = value.parse::<u8>() {
-            let app_state = Arc::clone(&app_state);
+    let exclude_state = Arc::clone(&app_state);
+    let exclude_info = window_info.clone();
+
+    window.on_submit(
+        move |value: SharedString,
+              inactive: SharedString,
+              lifetime: i32,
+              roles: i32,
+              monitor: i32,
+              hover: bool,
+              title: SharedString,
+              title_match: SharedString| {
+            if value.is_empty() {
+                return;
+            }
 
-            app_state.spawn_update_config(WindowConfig::new(
-                &window_info,
-                convert_to_full(number.into()),
+            if let Ok(number) = value.parse::<u8>() {
+                let app_state = Arc::clone(&app_state);
+
+                let mut window_config =
+                    WindowConfig::new(&window_info, convert_to_full(number.into()));
+                window_config.set_inactive_transparency(
+                    inactive
+                        .parse::<u8>()
+                        .ok()
+                        .map(|inactive| convert_to_full(inactive.into())),
+                );
+                window_config.set_lifetime(get_lifetime(
+                    app_state.get_backend().as_ref(),
+                    &window_info,
+                    lifetime,
+                ));
+                window_config.set_roles(get_roles(roles));
+                window_config.set_monitor(get_monitor_match(&window_info, monitor));
+                window_config.set_hover(hover.then(HoverSettings::default));
+                set_title(&mut window_config, &title, &title_match);
+                app_state.spawn_update_config(window_config);
+
+                if let Some(window) = submit_handle.upgrade() {
+                    window.hide().expect("Failed to hide percentage window.");
+                }
+            }
+        },
+    );
+
+    // An exclusion is kept next to a transparency rule for the same window and wins over it, which resets the window
+    window.on_exclude(
+        move |lifetime: i32,
+              roles: i32,
+              monitor: i32,
+              title: SharedString,
+              title_match: SharedString| {
+            let mut exclusion = WindowConfig::new(&exclude_info, convert_to_full(100));
+            exclusion.set_exclude(true);
+            exclusion.set_lifetime(get_lifetime(
+                exclude_state.get_backend().as_ref(),
+                &exclude_info,
+                lifetime,
             ));
+            exclusion.set_roles(get_roles(roles));
+            exclusion.set_monitor(get_monitor_match(&exclude_info, monitor));
+            set_title(&mut exclusion, &title, &title_match);
+            exclude_state.spawn_update_config(exclusion);
 
-            if let Some(window) = submit_handle.upgrade() {
+            if let Some(window) = exclude_handle.upgrade() {
                 window.hide().expect("Failed to hide percentage window.");
             }
-        }
-    });
+        },
+    );
 
     window.on_cancel(move || {
         if let Some(window) = window_handle.upgrade() {
@@ -278,103 +290,92 @@ pub async fn create_percentage_window(
     Ok(())
 }
 
+// An empty title leaves the rule for windows with any title
+fn set_title(window_config: &mut WindowConfig, title: &str, title_match: &str) {
+    if title.is_empty() {
+        return;
+    }
+
+    window_config.set_window_title(Some(title.to_owned()));
+    window_config.set_title_match(title_match.parse().unwrap_or_default());
+}
+
 /*
-  Sets the transparency of the handles window.
+  Turns the choice in the percentage window into a rule lifetime, in the order of its lifetime list.
+  A rule for a process that already exited would expire straight away, it lasts the session instead.
 */
-pub fn set_window_alpha(window_handle: HWND, transparency: u8) -> Result<(), anyhow::Error> {
-    unsafe {
-        SetWindowLongW(
-            window_handle,
-            GWL_EXSTYLE,
-            GetWindowLongW(window_handle, GWL_EXSTYLE) | WS_EX_LAYERED.0 as i32,
-        );
-
-        match SetLayeredWindowAttributes(
-            window_handle,
-            COLORREF(0),
-            transparency,
-            LAYERED_WINDOW_ATTRIBUTES_FLAGS(2),
-        ) {
-            Ok(()) => (),
-            Err(err) => return Err(anyhow!("Failed to get process handle {}", err)),
-        }
+fn get_lifetime(
+    backend: &dyn WindowBackend,
+    window_info: &WindowInfo,
+    choice: i32,
+) -> RuleLifetime {
+    match choice {
+        1 => RuleLifetime::Session,
+        2 => RuleLifetime::lasting(Duration::from_secs(30 * 60)),
+        3 => RuleLifetime::lasting(Duration::from_secs(60 * 60)),
+        4 => RuleLifetime::until_exit(backend, window_info.process_id)
+            .unwrap_or(RuleLifetime::Session),
+        _ => RuleLifetime::Permanent,
     }
-    Ok(())
 }
 
 /*
-  Returns if the window below the cursor is running as admin.
-  Used by the UI to make the user aware when a program they want to select a administrator program.
-  Hopefully they will realize they need to run WinAlpha to select it.
+  Turns the choice in the percentage window into the kinds of window the rule is for, in the order of its list.
 */
-fn is_elevated(point: POINT) -> bool {
-    let mut process_id = 0;
-    let mut elevation = TOKEN_ELEVATION::default();
-    let mut size = size_of::<TOKEN_ELEVATION>() as u32;
-    let mut token = HANDLE::default();
-
-    unsafe {
-        let hwnd = WindowFromPoint(point);
-        if hwnd.0.is_null() {
-            return false;
-        }
-
-        GetWindowThreadProcessId(hwnd, Some(&mut process_id));
-
-        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, process_id)
-            .ok()
-            .unwrap();
-
-        if !OpenProcessToken(process, TOKEN_QUERY, &mut token).is_ok() {
-            return false;
-        }
-
-        GetTokenInformation(
-            token,
-            TokenElevation,
-            Some(&mut elevation as *mut _ as *mut c_void),
-            size,
-            &mut size,
-        )
-        .ok();
+fn get_roles(choice: i32) -> Vec<WindowRole> {
+    match choice {
+        1 => vec![WindowRole::Main],
+        2 => vec![WindowRole::Main, WindowRole::Popup, WindowRole::Tool],
+        3 => vec![WindowRole::Main, WindowRole::Dialog, WindowRole::Tool],
+        _ => Vec::new(),
     }
-
-    elevation.TokenIsElevated != 0
 }
 
 /*
- Check if we are running as admin.
+  Turns the choice in the percentage window into the monitor the rule is for, in the order of its list.
+  This monitor is kept by device name since indexes shift when displays come and go.
 */
-fn is_running_as_admin() -> bool {
-    let mut elevation = TOKEN_ELEVATION::default();
-    let mut size = std::mem::size_of::<TOKEN_ELEVATION>() as u32;
-    let mut token = HANDLE::default();
-
-    unsafe {
-        if !OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token).is_ok() {
-            return false;
-        }
-
-        GetTokenInformation(
-            token,
-            TokenElevation,
-            Some(&mut elevation as *mut _ as *mut c_void),
-            size,
-            &mut size,
-        )
-        .is_ok_and(|_| elevation.TokenIsElevated != 0)
+fn get_monitor_match(window_info: &WindowInfo, choice: i32) -> Option<MonitorMatch> {
+    match choice {
+        1 => window_info.monitor.as_ref().map(|monitor| {
+            if monitor.name.is_empty() {
+                MonitorMatch::Index(monitor.index)
+            } else {
+                MonitorMatch::Name(monitor.name.clone())
+            }
+        }),
+        2 => Some(MonitorMatch::Primary),
+        3 => Some(MonitorMatch::Secondary),
+        _ => None,
     }
 }
 
+/*
+  Returns if the window below the cursor is running as admin.
+  Used by the UI to make the user aware when a program they want to select a administrator program.
+  Hopefully they will realize they need to run WinAlpha to select it.
+*/
+fn is_elevated(backend: &dyn WindowBackend, point: (i32, i32)) -> bool {
+    backend
+        .window_from_point(point.0, point.1)
+        .and_then(|window| backend.get_process_id(window))
+        .is_some_and(|process_id| backend.is_elevated(process_id))
+}
+
 /*
  Enables/disables autostart of WinAlpha.
  Done by adding a registry key for the current user under "run" this key is created with the current path WinAlpha was executed with
 */
+#[cfg(target_os = "windows")]
 pub fn change_startup(current_state: bool) -> windows::core::Result<()> {
     let mut startup_key = HKEY::default();
 
-    let path_str = PCSTR::from_raw(b"Software\\Microsoft\\Windows\\CurrentVersion\\Run\0".as_ptr());
-    let app_name = PCSTR::from_raw(b"WinAlpha\0".as_ptr());
+    let path_str = PCSTR::from_raw(
+        c"Software\\Microsoft\\Windows\\CurrentVersion\\Run"
+            .as_ptr()
+            .cast(),
+    );
+    let app_name = PCSTR::from_raw(c"WinAlpha".as_ptr().cast());
     let exe_path = current_exe()
         .unwrap_or_default()
         .to_string_lossy()
@@ -417,10 +418,15 @@ pub fn change_startup(current_state: bool) -> windows::core::Result<()> {
  Returns if autostart is enabled.
  This is done by checking if the startup registry key exists for the current user.
 */
+#[cfg(target_os = "windows")]
 pub fn get_startup_state() -> bool {
     let key: HKEY = HKEY_CURRENT_USER;
-    let path_str = PCSTR::from_raw(b"Software\\Microsoft\\Windows\\CurrentVersion\\Run\0".as_ptr());
-    let app_name = PCSTR::from_raw(b"WinAlpha\0".as_ptr());
+    let path_str = PCSTR::from_raw(
+        c"Software\\Microsoft\\Windows\\CurrentVersion\\Run"
+            .as_ptr()
+            .cast(),
+    );
+    let app_name = PCSTR::from_raw(c"WinAlpha".as_ptr().cast());
 
     let mut startup_key = HKEY::default();
     let mut size = 0u32;
@@ -451,3 +457,45 @@ pub fn get_startup_state() -> bool {
         result == ERROR_SUCCESS
     }
 }
+
+/*
+ Enables/disables autostart of WinAlpha.
+ Done with an XDG autostart entry pointing at the current path WinAlpha was executed with
+*/
+#[cfg(not(target_os = "windows"))]
+pub fn change_startup(current_state: bool) -> Result<()> {
+    let autostart_path = get_autostart_path()?;
+
+    if current_state {
+        let exe_path = current_exe()?.to_string_lossy().to_string();
+        let entry = format!(
+            "[Desktop Entry]\nType=Application\nName=WinAlpha\nExec=\"{}\"\nX-GNOME-Autostart-enabled=true\n",
+            exe_path
+        );
+
+        if let Some(parent) = autostart_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(autostart_path, entry)?;
+    } else if autostart_path.exists() {
+        fs::remove_file(autostart_path)?;
+    }
+
+    Ok(())
+}
+
+/*
+ Returns if autostart is enabled.
+ This is done by checking if the XDG autostart entry exists for the current user.
+*/
+#[cfg(not(target_os = "windows"))]
+pub fn get_startup_state() -> bool {
+    get_autostart_path().is_ok_and(|path| path.exists())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn get_autostart_path() -> Result<std::path::PathBuf> {
+    directories::BaseDirs::new()
+        .map(|dirs| dirs.config_dir().join("autostart").join("winalpha.desktop"))
+        .ok_or_else(|| anyhow!("Failed to get the config directory."))
+}
@@ -0,0 +1,376 @@
+use crate::{
+    backend::{cached::CachedBackend, WindowBackend},
+    config::Config,
+    journal::get_journal_path,
+    window_config::WindowConfig,
+};
+use core::time::Duration;
+use std::{fs, path::PathBuf, sync::Arc};
+use tokio::{
+    sync::{broadcast, watch, RwLock},
+    time::timeout,
+};
+
+// How long quitting waits on the monitor to put the windows back. Windows ends the process about 5 seconds into a logoff.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(4);
+
+#[derive(Clone)]
+pub struct AppState {
+    config_tx: broadcast::Sender<Config>,
+    enabled_tx: broadcast::Sender<bool>,
+    config: Arc<RwLock<Config>>,
+    config_path: PathBuf,
+    enabled: Arc<RwLock<bool>>,
+    backend: Arc<dyn WindowBackend>,
+    shutdown_tx: watch::Sender<bool>,
+    stopped_tx: watch::Sender<bool>,
+}
+
+impl AppState {
+    pub fn new(config: Config, config_path: PathBuf, backend: Arc<dyn WindowBackend>) -> Self {
+        let (config_tx, _) = broadcast::channel(2);
+        let (enabled_tx, _) = broadcast::channel(2);
+        let (shutdown_tx, _) = watch::channel(false);
+        let (stopped_tx, _) = watch::channel(false);
+
+        Self {
+            config_tx,
+            enabled_tx,
+            config: Arc::new(RwLock::new(config)),
+            config_path,
+            enabled: Arc::new(RwLock::new(true)),
+            backend: Arc::new(CachedBackend::new(backend)),
+            shutdown_tx,
+            stopped_tx,
+        }
+    }
+
+    pub fn get_backend(&self) -> Arc<dyn WindowBackend> {
+        self.backend.clone()
+    }
+
+    /*
+      Asks the monitor to stop and waits until it has restored every window it changed.
+      The request sticks, a monitor that is busy or not started yet still sees it.
+    */
+    pub async fn quit(&self) {
+        let mut stopped = self.stopped_tx.subscribe();
+        self.shutdown_tx.send_replace(true);
+
+        // Nothing to wait on without a monitor, or it has already stopped
+        if self.shutdown_tx.receiver_count() == 0 {
+            return;
+        }
+
+        if timeout(SHUTDOWN_TIMEOUT, stopped.wait_for(|&stopped| stopped))
+            .await
+            .is_err()
+        {
+            eprintln!("The monitor did not restore the windows in time, quitting anyway");
+        }
+    }
+
+    pub fn subscribe_shutdown(&self) -> watch::Receiver<bool> {
+        self.shutdown_tx.subscribe()
+    }
+
+    /*
+      Called by the monitor once the windows are back the way they were.
+    */
+    pub fn confirm_shutdown(&self) {
+        self.stopped_tx.send_replace(true);
+    }
+
+    pub fn spawn_update_config(&self, value: WindowConfig) {
+        let app_state = Arc::new(self.clone());
+
+        tokio::spawn(async move {
+            if let Err(e) = app_state.add_window_config(value).await {
+                eprintln!("Failed to update window config: {}", e);
+            }
+        });
+    }
+
+    /*
+      Same as spawn_update_config for an edit that changed the key of the rule, the rule under the old key is replaced.
+    */
+    pub fn spawn_replace_config(&self, old_key: String, value: WindowConfig) {
+        let app_state = Arc::new(self.clone());
+
+        tokio::spawn(async move {
+            if let Err(e) = app_state.replace_window_config(&old_key, value).await {
+                eprintln!("Failed to update window config: {}", e);
+            }
+        });
+    }
+
+    pub fn spawn_reorder_rules(&self, keys: Vec<String>) {
+        let app_state = Arc::new(self.clone());
+
+        tokio::spawn(async move {
+            if let Err(e) = app_state.reorder_rules(keys).await {
+                eprintln!("Failed to reorder rules: {}", e);
+            }
+        });
+    }
+
+    pub async fn get_config(&self) -> Config {
+        self.config.read().await.clone()
+    }
+
+    pub fn get_config_path(&self) -> String {
+        self.config_path
+            .clone()
+            .into_os_string()
+            .into_string()
+            .expect("shut")
+    }
+
+    pub fn get_journal_path(&self) -> PathBuf {
+        get_journal_path(&self.config_path)
+    }
+
+    pub async fn get_config_mut(&self) -> tokio::sync::RwLockWriteGuard<'_, Config> {
+        self.config.write().await
+    }
+
+    pub async fn add_window_config(
+        &self,
+        window_config: WindowConfig,
+    ) -> Result<(), anyhow::Error> {
+        let mut config = self.get_config_mut().await;
+
+        config.insert_rule(window_config);
+
+        // Save the updated config
+        let config_json = serde_json::to_string_pretty(&*config)?;
+
+        self.config_tx.send(config.to_owned())?;
+        fs::write(self.get_config_path(), config_json)?;
+
+        Ok(())
+    }
+
+    pub async fn replace_window_config(
+        &self,
+        old_key: &str,
+        window_config: WindowConfig,
+    ) -> Result<(), anyhow::Error> {
+        let mut config = self.get_config_mut().await;
+
+        config.replace_rule(old_key, window_config);
+
+        let config_json = serde_json::to_string_pretty(&*config)?;
+
+        self.config_tx.send(config.to_owned())?;
+        fs::write(self.get_config_path(), config_json)?;
+
+        Ok(())
+    }
+
+    /*
+      Gives the rules priorities in the order of the keys, the first key wins over all the others.
+    */
+    pub async fn reorder_rules(&self, keys: Vec<String>) -> Result<(), anyhow::Error> {
+        let mut config = self.get_config_mut().await;
+
+        config.set_rule_order(&keys);
+
+        let config_json = serde_json::to_string_pretty(&*config)?;
+
+        self.config_tx.send(config.to_owned())?;
+        fs::write(self.get_config_path(), config_json)?;
+
+        Ok(())
+    }
+
+    /*
+      Switches to the profile, None goes back to the base rules.
+      The monitor moves every window straight to its new alpha, windows no rule holds anymore are restored.
+    */
+    pub async fn set_active_profile(&self, profile: Option<String>) -> Result<(), anyhow::Error> {
+        let mut config = self.get_config_mut().await;
+
+        config.set_active_profile(profile)?;
+
+        let config_json = serde_json::to_string_pretty(&*config)?;
+
+        self.config_tx.send(config.to_owned())?;
+        fs::write(self.get_config_path(), config_json)?;
+
+        Ok(())
+    }
+
+    /*
+      Adds an empty profile and switches to it.
+    */
+    pub async fn add_profile(&self, name: String) -> Result<(), anyhow::Error> {
+        let mut config = self.get_config_mut().await;
+
+        config.add_profile(name.clone())?;
+        config.set_active_profile(Some(name))?;
+
+        let config_json = serde_json::to_string_pretty(&*config)?;
+
+        self.config_tx.send(config.to_owned())?;
+        fs::write(self.get_config_path(), config_json)?;
+
+        Ok(())
+    }
+
+    /*
+      Removes the profile with its rules, the base rules take over if it was active.
+    */
+    pub async fn remove_profile(&self, name: String) -> Result<(), anyhow::Error> {
+        let mut config = self.get_config_mut().await;
+
+        config.remove_profile(&name)?;
+
+        let config_json = serde_json::to_string_pretty(&*config)?;
+
+        self.config_tx.send(config.to_owned())?;
+        fs::write(self.get_config_path(), config_json)?;
+
+        Ok(())
+    }
+
+    /*
+      Removes the rules whose lifetime is over, the monitor restores their windows when it gets the new config.
+    */
+    pub async fn remove_expired_rules(&self) -> Result<(), anyhow::Error> {
+        // Checked under the read lock, this runs every second
+        if !self.config.read().await.has_expired(self.backend.as_ref()) {
+            return Ok(());
+        }
+
+        let mut config = self.get_config_mut().await;
+
+        for key in config.remove_expired(self.backend.as_ref()) {
+            eprintln!("Rule {} expired, removed it", key);
+        }
+
+        let config_json = serde_json::to_string_pretty(&*config)?;
+
+        self.config_tx.send(config.to_owned())?;
+        fs::write(self.get_config_path(), config_json)?;
+
+        Ok(())
+    }
+
+    pub fn subscribe_config_updates(&self) -> broadcast::Receiver<Config> {
+        self.config_tx.subscribe()
+    }
+
+    pub fn subscribe_enabled_updates(&self) -> broadcast::Receiver<bool> {
+        self.enabled_tx.subscribe()
+    }
+
+    pub async fn is_enabled(&self) -> bool {
+        *self.enabled.read().await
+    }
+
+    pub async fn enabled(&self) {
+        self.set_enable_state(true).await
+    }
+
+    pub async fn disable(&self) {
+        self.set_enable_state(false).await
+    }
+
+    async fn set_enable_state(&self, new_state: bool) {
+        *self.enabled.write().await = new_state;
+
+        // Fails only when the monitor is not running, it picks the state up when it starts
+        if self.enabled_tx.send(new_state).is_err() {
+            eprintln!("No monitor running to apply the enabled state");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backend::memory::{MemoryBackend, MemoryWindow},
+        monitor::monitor_windows,
+        window_config::WindowInfo,
+    };
+    use tokio::time::Instant;
+
+    fn app_state(backend: Arc<MemoryBackend>, name: &str) -> (Arc<AppState>, PathBuf) {
+        let dir =
+            std::env::temp_dir().join(format!("win_alpha_quit_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&dir).expect("Failed to create the test directory");
+
+        let mut config = Config::new();
+        config.insert_rule(WindowConfig::new(
+            &WindowInfo {
+                process_name: "app".to_owned(),
+                class_name: "Main".to_owned(),
+                ..Default::default()
+            },
+            128,
+        ));
+
+        (
+            Arc::new(AppState::new(config, dir.join("config.json"), backend)),
+            dir,
+        )
+    }
+
+    #[tokio::test]
+    async fn quit_returns_once_the_monitor_restored_the_windows() {
+        let backend = Arc::new(MemoryBackend::new());
+        backend.add_process(1, "app", false);
+        let window = backend.add_window(MemoryWindow {
+            class_name: "Main".to_owned(),
+            process_id: 1,
+            alpha: Some(200),
+            ..Default::default()
+        });
+        let (app_state, dir) = app_state(backend.clone(), "monitor");
+
+        let monitor = tokio::spawn(monitor_windows(Arc::clone(&app_state)));
+        timeout(Duration::from_secs(5), async {
+            while backend.get_window(window).and_then(|window| window.alpha) != Some(128) {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .expect("The rule was never applied");
+
+        app_state.quit().await;
+
+        // Restored by the time quit returns, not just once the monitor task ends
+        assert_eq!(
+            backend.get_window(window).and_then(|window| window.alpha),
+            Some(200)
+        );
+        monitor.await.expect("The monitor panicked");
+        _ = fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn quit_without_a_monitor_returns_at_once() {
+        let (app_state, dir) = app_state(Arc::new(MemoryBackend::new()), "none");
+
+        timeout(Duration::from_millis(100), app_state.quit())
+            .await
+            .expect("Quit waited on a monitor that is not running");
+        _ = fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn quit_gives_up_on_a_monitor_that_never_stops() {
+        let (app_state, dir) = app_state(Arc::new(MemoryBackend::new()), "stuck");
+        // Listens for the request like a monitor, then never confirms
+        let _shutdown = app_state.subscribe_shutdown();
+
+        let started = Instant::now();
+        app_state.quit().await;
+
+        assert!(started.elapsed() >= SHUTDOWN_TIMEOUT);
+        assert!(started.elapsed() < SHUTDOWN_TIMEOUT + Duration::from_secs(1));
+        _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,261 @@
+use super::{
+    MonitorInfo, OriginalWindowState, ProcessIdentity, WindowBackend, WindowEvent, WindowHandle,
+    WindowRole,
+};
+use anyhow::Result;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+struct CachedProcess {
+    start_time: u64,
+    identity: ProcessIdentity,
+}
+
+/*
+  Wraps a backend and remembers the identity of every process it has looked up, so the monitor, the rule matching and the picker all see the same process.
+  Entries are keyed by process id and start time. A new process that reuses the id of an exited one is resolved again instead of inheriting its identity.
+*/
+pub struct CachedBackend {
+    inner: Arc<dyn WindowBackend>,
+    processes: Mutex<HashMap<u32, CachedProcess>>,
+}
+
+impl CachedBackend {
+    pub fn new(inner: Arc<dyn WindowBackend>) -> Self {
+        Self {
+            inner,
+            processes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<u32, CachedProcess>> {
+        self.processes
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl WindowBackend for CachedBackend {
+    fn enumerate_windows(&self) -> Vec<WindowHandle> {
+        self.inner.enumerate_windows()
+    }
+
+    fn enumerate_child_windows(&self, parent: WindowHandle) -> Vec<WindowHandle> {
+        self.inner.enumerate_child_windows(parent)
+    }
+
+    fn get_parent(&self, window: WindowHandle) -> Option<WindowHandle> {
+        self.inner.get_parent(window)
+    }
+
+    fn is_top_level(&self, window: WindowHandle) -> bool {
+        self.inner.is_top_level(window)
+    }
+
+    fn is_window_visible(&self, window: WindowHandle) -> bool {
+        self.inner.is_window_visible(window)
+    }
+
+    fn get_window_class(&self, window: WindowHandle) -> Option<String> {
+        self.inner.get_window_class(window)
+    }
+
+    fn get_window_title(&self, window: WindowHandle) -> Option<String> {
+        self.inner.get_window_title(window)
+    }
+
+    fn get_window_role(&self, window: WindowHandle) -> WindowRole {
+        self.inner.get_window_role(window)
+    }
+
+    fn get_process_id(&self, window: WindowHandle) -> Option<u32> {
+        self.inner.get_process_id(window)
+    }
+
+    fn get_process_name(&self, process_id: u32) -> Option<String> {
+        self.get_process_identity(process_id)
+            .map(|identity| identity.name)
+    }
+
+    fn get_process_path(&self, process_id: u32) -> Option<String> {
+        self.get_process_identity(process_id)
+            .map(|identity| identity.path)
+    }
+
+    fn get_process_start_time(&self, process_id: u32) -> Option<u64> {
+        self.inner.get_process_start_time(process_id)
+    }
+
+    fn get_window_alpha(&self, window: WindowHandle) -> Option<u8> {
+        self.inner.get_window_alpha(window)
+    }
+
+    fn set_window_alpha(&self, window: WindowHandle, transparency: u8) -> Result<()> {
+        self.inner.set_window_alpha(window, transparency)
+    }
+
+    fn capture_window_state(&self, window: WindowHandle) -> Option<OriginalWindowState> {
+        self.inner.capture_window_state(window)
+    }
+
+    fn restore_window_state(
+        &self,
+        window: WindowHandle,
+        state: &OriginalWindowState,
+    ) -> Result<()> {
+        self.inner.restore_window_state(window, state)
+    }
+
+    fn is_elevated(&self, process_id: u32) -> bool {
+        self.get_process_identity(process_id)
+            .is_some_and(|identity| identity.elevated)
+    }
+
+    fn get_monitors(&self) -> Vec<MonitorInfo> {
+        self.inner.get_monitors()
+    }
+
+    fn get_window_monitor(&self, window: WindowHandle) -> Option<MonitorInfo> {
+        self.inner.get_window_monitor(window)
+    }
+
+    // Not cached, the focus changes far more often than anything else here
+    fn get_foreground_window(&self) -> Option<WindowHandle> {
+        self.inner.get_foreground_window()
+    }
+
+    fn window_from_point(&self, x: i32, y: i32) -> Option<WindowHandle> {
+        self.inner.window_from_point(x, y)
+    }
+
+    fn get_cursor_position(&self) -> Option<(i32, i32)> {
+        self.inner.get_cursor_position()
+    }
+
+    fn is_primary_button_down(&self) -> bool {
+        self.inner.is_primary_button_down()
+    }
+
+    fn can_pick_windows(&self) -> bool {
+        self.inner.can_pick_windows()
+    }
+
+    fn subscribe_events(&self) -> Option<UnboundedReceiver<WindowEvent>> {
+        self.inner.subscribe_events()
+    }
+
+    fn find_windows_by_class(&self, class_name: &str) -> Vec<WindowHandle> {
+        self.inner.find_windows_by_class(class_name)
+    }
+
+    /*
+      Only the start time is asked for on a hit, the rest comes from the cache.
+      Processes we can not get a start time for are never cached.
+    */
+    fn get_process_identity(&self, process_id: u32) -> Option<ProcessIdentity> {
+        let Some(start_time) = self.inner.get_process_start_time(process_id) else {
+            return self.inner.get_process_identity(process_id);
+        };
+
+        if let Some(cached) = self
+            .lock()
+            .get(&process_id)
+            .filter(|cached| cached.start_time == start_time)
+        {
+            return Some(cached.identity.clone());
+        }
+
+        let identity = self.inner.get_process_identity(process_id)?;
+        self.lock().insert(
+            process_id,
+            CachedProcess {
+                start_time,
+                identity: identity.clone(),
+            },
+        );
+
+        Some(identity)
+    }
+
+    fn prune_processes(&self) {
+        let cached: Vec<(u32, u64)> = self
+            .lock()
+            .iter()
+            .map(|(&process_id, cached)| (process_id, cached.start_time))
+            .collect();
+
+        // Start times are looked up without holding the lock
+        let exited: Vec<(u32, u64)> = cached
+            .into_iter()
+            .filter(|&(process_id, start_time)| {
+                self.inner.get_process_start_time(process_id) != Some(start_time)
+            })
+            .collect();
+
+        let mut processes = self.lock();
+        for (process_id, start_time) in exited {
+            if processes
+                .get(&process_id)
+                .is_some_and(|cached| cached.start_time == start_time)
+            {
+                processes.remove(&process_id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::memory::MemoryBackend;
+
+    fn cached(memory: &Arc<MemoryBackend>) -> CachedBackend {
+        CachedBackend::new(memory.clone())
+    }
+
+    #[test]
+    fn reused_process_id_is_looked_up_again() {
+        let memory = Arc::new(MemoryBackend::new());
+        let backend = cached(&memory);
+        memory.add_process_with_path(7, "old.exe", r"C:\Old\old.exe", true);
+
+        assert_eq!(backend.get_process_name(7).as_deref(), Some("old.exe"));
+        assert!(backend.is_elevated(7));
+
+        // Another process gets the same id, with a later start time
+        memory.remove_process(7);
+        memory.add_process_with_path(7, "new.exe", r"C:\New\new.exe", false);
+
+        assert_eq!(backend.get_process_name(7).as_deref(), Some("new.exe"));
+        assert_eq!(
+            backend.get_process_path(7).as_deref(),
+            Some(r"C:\New\new.exe")
+        );
+        assert!(!backend.is_elevated(7));
+    }
+
+    #[test]
+    fn pruning_drops_exited_processes() {
+        let memory = Arc::new(MemoryBackend::new());
+        let backend = cached(&memory);
+        memory.add_process(1, "kept", false);
+        memory.add_process(2, "exited", false);
+        memory.add_process(3, "reused", false);
+        for process_id in 1..=3 {
+            backend.get_process_identity(process_id);
+        }
+
+        memory.remove_process(2);
+        memory.add_process(3, "reused again", false);
+        backend.prune_processes();
+
+        let mut left: Vec<u32> = backend.lock().keys().copied().collect();
+        left.sort();
+        assert_eq!(left, vec![1]);
+        assert_eq!(backend.get_process_name(2), None);
+        assert_eq!(backend.get_process_name(3).as_deref(), Some("reused again"));
+    }
+}
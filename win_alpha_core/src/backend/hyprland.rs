@@ -0,0 +1,524 @@
+use super::{
+    forget_window, monitor_at, procfs, reconnect, MonitorInfo, WindowBackend, WindowEvent,
+    WindowHandle,
+};
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    env,
+    io::{BufRead, BufReader, Read, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+#[derive(Deserialize, Clone)]
+struct HyprlandClient {
+    address: String,
+    #[serde(default)]
+    class: String,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    pid: i64,
+    #[serde(default)]
+    at: [i32; 2],
+    #[serde(default)]
+    size: [i32; 2],
+    #[serde(default)]
+    hidden: bool,
+}
+
+#[derive(Deserialize)]
+struct HyprlandMonitor {
+    name: String,
+    #[serde(default)]
+    x: i32,
+    #[serde(default)]
+    y: i32,
+    #[serde(default)]
+    width: i32,
+    #[serde(default)]
+    height: i32,
+}
+
+#[derive(Deserialize)]
+struct HyprlandCursor {
+    x: i32,
+    y: i32,
+}
+
+/*
+  Hyprland addresses are hex, "0x" prefixed from hyprctl and bare in events.
+*/
+fn parse_address(address: &str) -> Option<WindowHandle> {
+    let digits = address.trim().trim_start_matches("0x");
+    u64::from_str_radix(digits, 16)
+        .ok()
+        .map(|address| address as WindowHandle)
+}
+
+/*
+  Turns one line of the event socket ("openwindow>>ADDRESS,WORKSPACE,CLASS,TITLE") into a window event.
+*/
+fn parse_event(line: &str) -> Option<WindowEvent> {
+    let (name, data) = line.split_once(">>")?;
+
+    // The address is empty when the focus went to an empty workspace
+    if name == "activewindowv2" {
+        return Some(WindowEvent::Foreground(parse_address(data)));
+    }
+
+    let handle = parse_address(data.split(',').next()?)?;
+
+    match name {
+        "openwindow" => Some(WindowEvent::Created(handle)),
+        "closewindow" => Some(WindowEvent::Destroyed(handle)),
+        "windowtitle" | "windowtitlev2" | "movewindow" | "movewindowv2" => {
+            Some(WindowEvent::Changed(handle))
+        }
+        _ => None,
+    }
+}
+
+/*
+  Drives Hyprland through the same sockets hyprctl uses.
+  One request per connection on the command socket, window events stream in line by line on the second socket.
+*/
+pub struct HyprlandBackend {
+    command_socket: PathBuf,
+    event_socket: PathBuf,
+    windows: Arc<Mutex<HashMap<WindowHandle, HyprlandClient>>>,
+    opacity: Mutex<HashMap<WindowHandle, u8>>,
+}
+
+impl HyprlandBackend {
+    /*
+      Uses the given sockets, a fake server works just as well as Hyprland.
+    */
+    pub fn new(command_socket: impl Into<PathBuf>, event_socket: impl Into<PathBuf>) -> Self {
+        Self {
+            command_socket: command_socket.into(),
+            event_socket: event_socket.into(),
+            windows: Arc::new(Mutex::new(HashMap::new())),
+            opacity: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /*
+      Sockets live in $XDG_RUNTIME_DIR/hypr/<signature>, older releases used /tmp/hypr/<signature>.
+    */
+    pub fn from_env() -> Result<Self> {
+        let signature = env::var("HYPRLAND_INSTANCE_SIGNATURE")?;
+        let runtime_dir = env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".to_owned());
+
+        let mut socket_dir = PathBuf::from(runtime_dir).join("hypr").join(&signature);
+        if !socket_dir.exists() {
+            socket_dir = PathBuf::from("/tmp/hypr").join(&signature);
+        }
+
+        Ok(Self::new(
+            socket_dir.join(".socket.sock"),
+            socket_dir.join(".socket2.sock"),
+        ))
+    }
+
+    fn request(&self, command: &str) -> Result<String> {
+        let mut stream = UnixStream::connect(&self.command_socket)?;
+        stream.write_all(command.as_bytes())?;
+
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply)?;
+
+        Ok(reply)
+    }
+
+    fn refresh_windows(&self) -> Vec<WindowHandle> {
+        let Some(clients) = self
+            .request("j/clients")
+            .ok()
+            .and_then(|reply| serde_json::from_str::<Vec<HyprlandClient>>(&reply).ok())
+        else {
+            return Vec::new();
+        };
+
+        let windows: HashMap<WindowHandle, HyprlandClient> = clients
+            .into_iter()
+            .filter(|client| client.pid > 0)
+            .filter_map(|client| Some((parse_address(&client.address)?, client)))
+            .collect();
+
+        let handles: Vec<WindowHandle> = windows.keys().copied().collect();
+        if let Ok(mut cached) = self.windows.lock() {
+            *cached = windows;
+        }
+        if let Ok(mut opacity) = self.opacity.lock() {
+            opacity.retain(|handle, _| handles.contains(handle));
+        }
+
+        handles
+    }
+
+    /*
+      Looks the window up in the last client list, asking Hyprland again when it is not there yet.
+      Events drop the entries of the windows they are about, so a renamed window is read again.
+    */
+    fn get_window(&self, window: WindowHandle) -> Option<HyprlandClient> {
+        if let Some(found) = self.windows.lock().ok()?.get(&window).cloned() {
+            return Some(found);
+        }
+
+        self.refresh_windows();
+        self.windows.lock().ok()?.get(&window).cloned()
+    }
+}
+
+impl WindowBackend for HyprlandBackend {
+    fn enumerate_windows(&self) -> Vec<WindowHandle> {
+        self.refresh_windows()
+    }
+
+    // Hyprland clients are flat, there is no window tree
+    fn enumerate_child_windows(&self, _parent: WindowHandle) -> Vec<WindowHandle> {
+        Vec::new()
+    }
+
+    fn get_parent(&self, _window: WindowHandle) -> Option<WindowHandle> {
+        None
+    }
+
+    fn get_window_class(&self, window: WindowHandle) -> Option<String> {
+        Some(self.get_window(window)?.class)
+    }
+
+    fn get_window_title(&self, window: WindowHandle) -> Option<String> {
+        Some(self.get_window(window)?.title)
+    }
+
+    fn get_process_id(&self, window: WindowHandle) -> Option<u32> {
+        u32::try_from(self.get_window(window)?.pid).ok()
+    }
+
+    fn get_process_name(&self, process_id: u32) -> Option<String> {
+        procfs::get_process_name(process_id)
+    }
+
+    fn get_process_path(&self, process_id: u32) -> Option<String> {
+        procfs::get_process_path(process_id)
+    }
+
+    fn get_process_start_time(&self, process_id: u32) -> Option<u64> {
+        procfs::get_process_start_time(process_id)
+    }
+
+    fn get_window_alpha(&self, window: WindowHandle) -> Option<u8> {
+        self.opacity.lock().ok()?.get(&window).copied()
+    }
+
+    fn set_window_alpha(&self, window: WindowHandle, transparency: u8) -> Result<()> {
+        let command = format!(
+            "setprop address:0x{:x} alpha {:.3}",
+            window,
+            transparency as f32 / 255.0
+        );
+        let reply = self.request(&command)?;
+
+        if reply.trim() != "ok" {
+            return Err(anyhow!("Failed to set window opacity {}", reply.trim()));
+        }
+
+        if let Ok(mut opacity) = self.opacity.lock() {
+            opacity.insert(window, transparency);
+        }
+
+        Ok(())
+    }
+
+    fn is_elevated(&self, process_id: u32) -> bool {
+        procfs::is_elevated(process_id)
+    }
+
+    fn window_from_point(&self, x: i32, y: i32) -> Option<WindowHandle> {
+        self.refresh_windows();
+
+        self.windows
+            .lock()
+            .ok()?
+            .iter()
+            .find(|(_, client)| {
+                let [left, top] = client.at;
+                let [width, height] = client.size;
+                !client.hidden && x >= left && x < left + width && y >= top && y < top + height
+            })
+            .map(|(&handle, _)| handle)
+    }
+
+    /*
+      Monitors in the order Hyprland lists them. Hyprland has no primary monitor, the first one stands in.
+    */
+    fn get_monitors(&self) -> Vec<MonitorInfo> {
+        let Some(monitors) = self
+            .request("j/monitors")
+            .ok()
+            .and_then(|reply| serde_json::from_str::<Vec<HyprlandMonitor>>(&reply).ok())
+        else {
+            return Vec::new();
+        };
+
+        monitors
+            .into_iter()
+            .enumerate()
+            .map(|(index, monitor)| MonitorInfo {
+                index,
+                name: monitor.name,
+                primary: index == 0,
+                rect: (
+                    monitor.x,
+                    monitor.y,
+                    monitor.x + monitor.width,
+                    monitor.y + monitor.height,
+                ),
+            })
+            .collect()
+    }
+
+    fn get_window_monitor(&self, window: WindowHandle) -> Option<MonitorInfo> {
+        let client = self.get_window(window)?;
+        let [left, top] = client.at;
+        let [width, height] = client.size;
+
+        monitor_at(self.get_monitors(), (left + width / 2, top + height / 2))
+    }
+
+    // An empty object when nothing has the focus, which does not parse
+    fn get_foreground_window(&self) -> Option<WindowHandle> {
+        let reply = self.request("j/activewindow").ok()?;
+        let client: HyprlandClient = serde_json::from_str(&reply).ok()?;
+
+        parse_address(&client.address)
+    }
+
+    fn get_cursor_position(&self) -> Option<(i32, i32)> {
+        let reply = self.request("j/cursorpos").ok()?;
+        let cursor: HyprlandCursor = serde_json::from_str(&reply).ok()?;
+
+        Some((cursor.x, cursor.y))
+    }
+
+    // Button state is not exposed over IPC
+    fn is_primary_button_down(&self) -> bool {
+        false
+    }
+
+    fn can_pick_windows(&self) -> bool {
+        false
+    }
+
+    fn subscribe_events(&self) -> Option<UnboundedReceiver<WindowEvent>> {
+        let mut stream = UnixStream::connect(&self.event_socket).ok()?;
+        let event_socket = self.event_socket.clone();
+        let windows = Arc::clone(&self.windows);
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        thread::spawn(move || loop {
+            for line in BufReader::new(stream).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+
+                let Some(event) = parse_event(&line) else {
+                    continue;
+                };
+
+                forget_window(&windows, event);
+                if tx.send(event).is_err() {
+                    return;
+                }
+            }
+
+            // Hyprland restarted or dropped us, the event socket needs nothing but a new connection
+            match reconnect(|| UnixStream::connect(&event_socket)) {
+                Some(connection) => stream = connection,
+                None => return,
+            }
+        });
+
+        Some(rx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        fs,
+        os::unix::net::UnixListener,
+        process,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    static NEXT_SOCKET: AtomicUsize = AtomicUsize::new(0);
+
+    fn socket_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!(
+            "win_alpha_hypr_{}_{}_{}.sock",
+            process::id(),
+            NEXT_SOCKET.fetch_add(1, Ordering::Relaxed),
+            name
+        ))
+    }
+
+    /*
+      Answers one command the way Hyprland does, reading the request and closing the connection after the reply.
+    */
+    fn answer(listener: &UnixListener, reply: &str) -> String {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = [0u8; 256];
+        let length = stream.read(&mut request).unwrap();
+        stream.write_all(reply.as_bytes()).unwrap();
+
+        String::from_utf8(request[..length].to_vec()).unwrap()
+    }
+
+    #[test]
+    fn event_lines_are_parsed() {
+        assert_eq!(
+            parse_event("openwindow>>55aa,2,kitty,~/src"),
+            Some(WindowEvent::Created(0x55aa))
+        );
+        assert_eq!(
+            parse_event("closewindow>>55aa"),
+            Some(WindowEvent::Destroyed(0x55aa))
+        );
+        assert_eq!(
+            parse_event("windowtitlev2>>55aa,vim, with a comma"),
+            Some(WindowEvent::Changed(0x55aa))
+        );
+        assert_eq!(
+            parse_event("movewindowv2>>55aa,3,3"),
+            Some(WindowEvent::Changed(0x55aa))
+        );
+        assert_eq!(
+            parse_event("activewindowv2>>55aa"),
+            Some(WindowEvent::Foreground(Some(0x55aa)))
+        );
+        // Focus moved to an empty workspace
+        assert_eq!(
+            parse_event("activewindowv2>>"),
+            Some(WindowEvent::Foreground(None))
+        );
+        assert_eq!(parse_event("workspace>>2"), None);
+        assert_eq!(parse_event("openwindow>>not hex,2,kitty,"), None);
+        assert_eq!(parse_event("garbage"), None);
+    }
+
+    #[test]
+    fn clients_are_read_and_alpha_is_set() {
+        let command_path = socket_path("command");
+        let listener = UnixListener::bind(&command_path).unwrap();
+
+        let server = thread::spawn(move || {
+            assert_eq!(
+                answer(
+                    &listener,
+                    r#"[{"address":"0x55aa","class":"kitty","title":"~/src","pid":42,
+                         "at":[10,20],"size":[300,200]},
+                        {"address":"0x66bb","class":"","title":"","pid":-1}]"#,
+                ),
+                "j/clients"
+            );
+            assert_eq!(
+                answer(&listener, "ok"),
+                "setprop address:0x55aa alpha 0.502"
+            );
+            answer(&listener, "window not found");
+        });
+
+        let backend = HyprlandBackend::new(&command_path, socket_path("events"));
+
+        // Layer surfaces and other clients without a process are left out
+        assert_eq!(backend.enumerate_windows(), vec![0x55aa]);
+        assert_eq!(backend.get_window_class(0x55aa).as_deref(), Some("kitty"));
+        assert_eq!(backend.get_window_title(0x55aa).as_deref(), Some("~/src"));
+        assert_eq!(backend.get_process_id(0x55aa), Some(42));
+
+        backend.set_window_alpha(0x55aa, 128).unwrap();
+        assert_eq!(backend.get_window_alpha(0x55aa), Some(128));
+        assert!(backend.set_window_alpha(0x55aa, 64).is_err());
+        assert_eq!(backend.get_window_alpha(0x55aa), Some(128));
+
+        server.join().unwrap();
+        let _ = fs::remove_file(&command_path);
+    }
+
+    #[test]
+    fn events_reconnect_after_eof() {
+        let event_path = socket_path("events");
+        let listener = UnixListener::bind(&event_path).unwrap();
+        let backend = HyprlandBackend::new(socket_path("command"), &event_path);
+
+        let server = thread::spawn(move || {
+            let (mut first, _) = listener.accept().unwrap();
+            first
+                .write_all(b"openwindow>>55aa,1,kitty,~\nworkspace>>1\nactivewindowv2>>55aa\n")
+                .unwrap();
+            // Hyprland goes away, the backend has to connect again
+            drop(first);
+
+            let (mut second, _) = listener.accept().unwrap();
+            second.write_all(b"closewindow>>55aa\n").unwrap();
+            second
+        });
+
+        let mut events = backend.subscribe_events().unwrap();
+        let received: Vec<WindowEvent> = (0..3).filter_map(|_| events.blocking_recv()).collect();
+
+        assert_eq!(
+            received,
+            vec![
+                WindowEvent::Created(0x55aa),
+                WindowEvent::Foreground(Some(0x55aa)),
+                WindowEvent::Destroyed(0x55aa),
+            ]
+        );
+
+        let _second = server.join().unwrap();
+        let _ = fs::remove_file(&event_path);
+    }
+
+    #[test]
+    fn events_drop_the_cached_client() {
+        let command_path = socket_path("command");
+        let event_path = socket_path("events");
+        let commands = UnixListener::bind(&command_path).unwrap();
+        let event_listener = UnixListener::bind(&event_path).unwrap();
+
+        let server = thread::spawn(move || {
+            answer(
+                &commands,
+                r#"[{"address":"0x55aa","class":"kitty","title":"~/src","pid":42}]"#,
+            );
+            let (mut events, _) = event_listener.accept().unwrap();
+            events.write_all(b"windowtitlev2>>55aa,vim\n").unwrap();
+            answer(
+                &commands,
+                r#"[{"address":"0x55aa","class":"kitty","title":"vim","pid":42}]"#,
+            );
+            events
+        });
+
+        let backend = HyprlandBackend::new(&command_path, &event_path);
+        assert_eq!(backend.get_window_title(0x55aa).as_deref(), Some("~/src"));
+
+        let mut events = backend.subscribe_events().unwrap();
+        assert_eq!(events.blocking_recv(), Some(WindowEvent::Changed(0x55aa)));
+        assert_eq!(backend.get_window_title(0x55aa).as_deref(), Some("vim"));
+
+        let _events = server.join().unwrap();
+        let _ = fs::remove_file(&command_path);
+        let _ = fs::remove_file(&event_path);
+    }
+}
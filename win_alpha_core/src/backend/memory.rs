@@ -0,0 +1,389 @@
+use super::{
+    monitor_at, MonitorInfo, OriginalWindowState, WindowBackend, WindowEvent, WindowHandle,
+    WindowRole,
+};
+use anyhow::{anyhow, Result};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Mutex, MutexGuard},
+};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+/*
+  A window that only exists inside the memory backend.
+*/
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MemoryWindow {
+    pub class_name: String,
+    pub title: String,
+    pub process_id: u32,
+    pub parent: Option<WindowHandle>,
+    pub hidden: bool,
+    pub alpha: Option<u8>,
+    pub rect: (i32, i32, i32, i32),
+    pub role: WindowRole,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MemoryProcess {
+    pub name: String,
+    pub path: String,
+    pub elevated: bool,
+    pub start_time: u64,
+}
+
+#[derive(Default)]
+struct MemoryState {
+    next_handle: WindowHandle,
+    next_start_time: u64,
+    windows: BTreeMap<WindowHandle, MemoryWindow>,
+    processes: HashMap<u32, MemoryProcess>,
+    alpha_writes: Vec<(WindowHandle, u8)>,
+    cursor: Option<(i32, i32)>,
+    primary_button_down: bool,
+    subscribers: Vec<UnboundedSender<WindowEvent>>,
+    monitors: Vec<MonitorInfo>,
+    foreground: Option<WindowHandle>,
+}
+
+impl MemoryState {
+    fn emit(&mut self, event: WindowEvent) {
+        self.subscribers.retain(|tx| tx.send(event).is_ok());
+    }
+}
+
+/*
+  A scriptable desktop kept entirely in memory.
+  Tests add processes and windows, hand the backend to the monitor, then check what alpha each window ended up with.
+*/
+#[derive(Default)]
+pub struct MemoryBackend {
+    state: Mutex<MemoryState>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MemoryState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /*
+      Adds a process, adding it again under the same id acts like the id got reused by a new process.
+    */
+    pub fn add_process(&self, process_id: u32, name: &str, elevated: bool) {
+        self.add_process_with_path(process_id, name, name, elevated);
+    }
+
+    /*
+      Same as add_process, for processes that need a full executable path.
+    */
+    pub fn add_process_with_path(&self, process_id: u32, name: &str, path: &str, elevated: bool) {
+        let mut state = self.lock();
+        state.next_start_time += 1;
+        let start_time = state.next_start_time;
+
+        state.processes.insert(
+            process_id,
+            MemoryProcess {
+                name: name.to_owned(),
+                path: path.to_owned(),
+                elevated,
+                start_time,
+            },
+        );
+    }
+
+    /*
+      Removes the process and every window it owns.
+    */
+    pub fn remove_process(&self, process_id: u32) {
+        let owned: Vec<WindowHandle> = {
+            let mut state = self.lock();
+            state.processes.remove(&process_id);
+            state
+                .windows
+                .iter()
+                .filter(|(_, window)| window.process_id == process_id)
+                .map(|(&handle, _)| handle)
+                .collect()
+        };
+
+        for handle in owned {
+            self.remove_window(handle);
+        }
+    }
+
+    /*
+      Adds a window and returns the handle it was given.
+    */
+    pub fn add_window(&self, window: MemoryWindow) -> WindowHandle {
+        let mut state = self.lock();
+        state.next_handle += 1;
+        let handle = state.next_handle;
+        state.windows.insert(handle, window);
+        state.emit(WindowEvent::Created(handle));
+
+        handle
+    }
+
+    /*
+      Removes the window and all of its children.
+    */
+    pub fn remove_window(&self, window: WindowHandle) {
+        let mut state = self.lock();
+        let mut pending = vec![window];
+
+        while let Some(handle) = pending.pop() {
+            if state.windows.remove(&handle).is_some() {
+                state.emit(WindowEvent::Destroyed(handle));
+            }
+            if state.foreground == Some(handle) {
+                state.foreground = None;
+            }
+            pending.extend(
+                state
+                    .windows
+                    .iter()
+                    .filter(|(_, child)| child.parent == Some(handle))
+                    .map(|(&child, _)| child),
+            );
+        }
+    }
+
+    /*
+      Changes a window in place, for example to rename its class or title.
+    */
+    pub fn update_window(&self, window: WindowHandle, update: impl FnOnce(&mut MemoryWindow)) {
+        let mut state = self.lock();
+
+        if let Some(existing) = state.windows.get_mut(&window) {
+            update(existing);
+            state.emit(WindowEvent::Changed(window));
+        }
+    }
+
+    /*
+      Replaces the monitor layout, indexes follow the order given. Every window is reported as changed, like a display being plugged in.
+    */
+    pub fn set_monitors(&self, monitors: Vec<MonitorInfo>) {
+        let mut state = self.lock();
+
+        state.monitors = monitors
+            .into_iter()
+            .enumerate()
+            .map(|(index, monitor)| MonitorInfo { index, ..monitor })
+            .collect();
+
+        let windows: Vec<WindowHandle> = state.windows.keys().copied().collect();
+        for window in windows {
+            state.emit(WindowEvent::Changed(window));
+        }
+    }
+
+    /*
+      Gives the window the focus, None focuses the desktop.
+    */
+    pub fn set_foreground(&self, window: Option<WindowHandle>) {
+        let mut state = self.lock();
+
+        if state.foreground != window {
+            state.foreground = window;
+            state.emit(WindowEvent::Foreground(window));
+        }
+    }
+
+    pub fn get_window(&self, window: WindowHandle) -> Option<MemoryWindow> {
+        self.lock().windows.get(&window).cloned()
+    }
+
+    /*
+      Every alpha value that was written, in order.
+    */
+    pub fn alpha_writes(&self) -> Vec<(WindowHandle, u8)> {
+        self.lock().alpha_writes.clone()
+    }
+
+    pub fn clear_alpha_writes(&self) {
+        self.lock().alpha_writes.clear();
+    }
+
+    pub fn set_cursor(&self, x: i32, y: i32, primary_button_down: bool) {
+        let mut state = self.lock();
+        state.cursor = Some((x, y));
+        state.primary_button_down = primary_button_down;
+    }
+}
+
+impl WindowBackend for MemoryBackend {
+    fn enumerate_windows(&self) -> Vec<WindowHandle> {
+        self.lock()
+            .windows
+            .iter()
+            .filter(|(_, window)| window.parent.is_none())
+            .map(|(&handle, _)| handle)
+            .collect()
+    }
+
+    fn enumerate_child_windows(&self, parent: WindowHandle) -> Vec<WindowHandle> {
+        let state = self.lock();
+        let mut children = Vec::new();
+        let mut pending = vec![parent];
+
+        while let Some(current) = pending.pop() {
+            for (&handle, window) in state.windows.iter() {
+                if window.parent == Some(current) {
+                    children.push(handle);
+                    pending.push(handle);
+                }
+            }
+        }
+
+        children
+    }
+
+    fn get_parent(&self, window: WindowHandle) -> Option<WindowHandle> {
+        self.lock().windows.get(&window)?.parent
+    }
+
+    fn is_window_visible(&self, window: WindowHandle) -> bool {
+        self.lock()
+            .windows
+            .get(&window)
+            .is_some_and(|window| !window.hidden)
+    }
+
+    fn get_window_class(&self, window: WindowHandle) -> Option<String> {
+        Some(self.lock().windows.get(&window)?.class_name.clone())
+    }
+
+    fn get_window_title(&self, window: WindowHandle) -> Option<String> {
+        Some(self.lock().windows.get(&window)?.title.clone())
+    }
+
+    fn get_window_role(&self, window: WindowHandle) -> WindowRole {
+        self.lock()
+            .windows
+            .get(&window)
+            .map(|window| window.role)
+            .unwrap_or_default()
+    }
+
+    fn get_process_id(&self, window: WindowHandle) -> Option<u32> {
+        Some(self.lock().windows.get(&window)?.process_id)
+    }
+
+    fn get_process_name(&self, process_id: u32) -> Option<String> {
+        Some(self.lock().processes.get(&process_id)?.name.clone())
+    }
+
+    fn get_process_path(&self, process_id: u32) -> Option<String> {
+        Some(self.lock().processes.get(&process_id)?.path.clone())
+    }
+
+    fn get_process_start_time(&self, process_id: u32) -> Option<u64> {
+        Some(self.lock().processes.get(&process_id)?.start_time)
+    }
+
+    fn get_window_alpha(&self, window: WindowHandle) -> Option<u8> {
+        self.lock().windows.get(&window)?.alpha
+    }
+
+    fn set_window_alpha(&self, window: WindowHandle, transparency: u8) -> Result<()> {
+        let mut state = self.lock();
+        let existing = state
+            .windows
+            .get_mut(&window)
+            .ok_or_else(|| anyhow!("No window with handle {}", window))?;
+
+        existing.alpha = Some(transparency);
+        state.alpha_writes.push((window, transparency));
+
+        Ok(())
+    }
+
+    /*
+      Puts the alpha back exactly, a window that had none gets none again.
+    */
+    fn restore_window_state(
+        &self,
+        window: WindowHandle,
+        state: &OriginalWindowState,
+    ) -> Result<()> {
+        let OriginalWindowState::Alpha(alpha) = *state else {
+            return Err(anyhow!(
+                "Window state {:?} was not captured by memory",
+                state
+            ));
+        };
+
+        let mut state = self.lock();
+        let existing = state
+            .windows
+            .get_mut(&window)
+            .ok_or_else(|| anyhow!("No window with handle {}", window))?;
+
+        existing.alpha = alpha;
+        state.alpha_writes.push((window, alpha.unwrap_or(u8::MAX)));
+
+        Ok(())
+    }
+
+    fn is_elevated(&self, process_id: u32) -> bool {
+        self.lock()
+            .processes
+            .get(&process_id)
+            .is_some_and(|process| process.elevated)
+    }
+
+    fn window_from_point(&self, x: i32, y: i32) -> Option<WindowHandle> {
+        self.lock()
+            .windows
+            .iter()
+            .rev()
+            .find(|(_, window)| {
+                let (left, top, right, bottom) = window.rect;
+                x >= left && x < right && y >= top && y < bottom
+            })
+            .map(|(&handle, _)| handle)
+    }
+
+    fn get_monitors(&self) -> Vec<MonitorInfo> {
+        self.lock().monitors.clone()
+    }
+
+    /*
+      The monitor the center of the window is on.
+    */
+    fn get_window_monitor(&self, window: WindowHandle) -> Option<MonitorInfo> {
+        let (left, top, right, bottom) = self.lock().windows.get(&window)?.rect;
+
+        monitor_at(
+            self.get_monitors(),
+            ((left + right) / 2, (top + bottom) / 2),
+        )
+    }
+
+    fn get_foreground_window(&self) -> Option<WindowHandle> {
+        self.lock().foreground
+    }
+
+    fn get_cursor_position(&self) -> Option<(i32, i32)> {
+        self.lock().cursor
+    }
+
+    fn is_primary_button_down(&self) -> bool {
+        self.lock().primary_button_down
+    }
+
+    fn subscribe_events(&self) -> Option<UnboundedReceiver<WindowEvent>> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.lock().subscribers.push(tx);
+
+        Some(rx)
+    }
+}
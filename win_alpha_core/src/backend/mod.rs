@@ -0,0 +1,465 @@
+use anyhow::Result;
+use core::{fmt, str::FromStr};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+pub mod cached;
+
+#[cfg(target_os = "linux")]
+pub mod hyprland;
+// Scriptable backend for tests and embedders, always there for the crates own tests and otherwise enabled with the `memory-backend` feature.
+#[cfg(any(test, feature = "memory-backend"))]
+pub mod memory;
+#[cfg(target_os = "linux")]
+mod procfs;
+#[cfg(target_os = "linux")]
+pub mod sway;
+#[cfg(target_os = "windows")]
+pub mod win32;
+#[cfg(target_os = "linux")]
+pub mod x11;
+
+/*
+  Identifies a window for whichever backend is in use.
+  On Windows this is the HWND, other backends map their own ids onto it.
+*/
+pub type WindowHandle = isize;
+
+/*
+  Something happened to a window, pushed by backends that can watch the desktop instead of being polled.
+  Backends may also send these for windows that are not top level, the monitor checks with is_top_level.
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WindowEvent {
+    Created(WindowHandle),
+    Destroyed(WindowHandle),
+    // Shown, or its class, title or position changed
+    Changed(WindowHandle),
+    // Another top level window got the focus, None when nothing has it (the desktop)
+    Foreground(Option<WindowHandle>),
+}
+
+/*
+  Everything WinAlpha needs to know about a process, looked up together so it can be cached together.
+*/
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProcessIdentity {
+    pub path: String,
+    pub name: String,
+    pub elevated: bool,
+}
+
+/*
+  The part a window plays in its app, from its styles on Windows and _NET_WM_WINDOW_TYPE on X11.
+*/
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowRole {
+    // A window of its own, nothing owns it
+    #[default]
+    Main,
+    // Owned windows with a frame: dialogs, message boxes, transient windows on X11
+    Dialog,
+    // Menus, dropdowns, tooltips and notifications
+    Popup,
+    // Tool windows, palettes and floating toolbars
+    Tool,
+}
+
+impl WindowRole {
+    /*
+      Maps an EWMH window type (the part after _NET_WM_WINDOW_TYPE_, lowercase) onto a role. Sway reports the same names.
+      Unknown types are treated as main windows.
+    */
+    pub fn from_window_type(window_type: &str) -> Self {
+        match window_type {
+            "dialog" => WindowRole::Dialog,
+            "menu" | "dropdown_menu" | "popup_menu" | "tooltip" | "combo" | "notification"
+            | "dnd" => WindowRole::Popup,
+            "utility" | "toolbar" | "splash" => WindowRole::Tool,
+            _ => WindowRole::Main,
+        }
+    }
+}
+
+impl fmt::Display for WindowRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            WindowRole::Main => "main",
+            WindowRole::Dialog => "dialog",
+            WindowRole::Popup => "popup",
+            WindowRole::Tool => "tool",
+        })
+    }
+}
+
+impl FromStr for WindowRole {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "main" => Ok(WindowRole::Main),
+            "dialog" => Ok(WindowRole::Dialog),
+            "popup" => Ok(WindowRole::Popup),
+            "tool" => Ok(WindowRole::Tool),
+            _ => Err(anyhow::anyhow!("Unknown window role: {}", value)),
+        }
+    }
+}
+
+/*
+  One display as the backend sees it.
+*/
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MonitorInfo {
+    // Position in the backends monitor list, starting at 0
+    pub index: usize,
+    // The device (\\.\DISPLAY2) or output (DP-1) name
+    pub name: String,
+    pub primary: bool,
+    // Left, top, right, bottom in desktop coordinates
+    pub rect: (i32, i32, i32, i32),
+}
+
+/*
+  Returns the monitor the point is on, for backends that only know where windows and monitors are.
+*/
+pub fn monitor_at(monitors: Vec<MonitorInfo>, (x, y): (i32, i32)) -> Option<MonitorInfo> {
+    monitors.into_iter().find(|monitor| {
+        let (left, top, right, bottom) = monitor.rect;
+        x >= left && x < right && y >= top && y < bottom
+    })
+}
+
+/*
+  Tries to get an event connection back after the compositor closed it, waiting a little longer before every attempt.
+  Gives up after a few seconds so the monitor can fall back to polling.
+*/
+#[cfg(target_os = "linux")]
+pub fn reconnect<T>(mut connect: impl FnMut() -> std::io::Result<T>) -> Option<T> {
+    let mut delay = core::time::Duration::from_millis(100);
+
+    for _ in 0..5 {
+        std::thread::sleep(delay);
+        if let Ok(connection) = connect() {
+            return Some(connection);
+        }
+        delay *= 2;
+    }
+
+    None
+}
+
+/*
+  Drops what a backend remembers about the window an event is for, so its next lookup asks the compositor again.
+  Without this a renamed or moved window kept its old title and position until it was closed.
+*/
+#[cfg(target_os = "linux")]
+pub fn forget_window<T>(
+    windows: &std::sync::Mutex<std::collections::HashMap<WindowHandle, T>>,
+    event: WindowEvent,
+) {
+    let (WindowEvent::Created(handle)
+    | WindowEvent::Changed(handle)
+    | WindowEvent::Destroyed(handle)) = event
+    else {
+        return;
+    };
+
+    if let Ok(mut windows) = windows.lock() {
+        windows.remove(&handle);
+    }
+}
+
+/*
+  What a window looked like before WinAlpha first changed it, so it can be put back exactly.
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OriginalWindowState {
+    // The extended style, plus (color key, alpha, flags) when the window was already layered
+    Win32 {
+        ex_style: u32,
+        layered: Option<(u32, u8, u32)>,
+    },
+    // The raw _NET_WM_WINDOW_OPACITY, None when it was not set
+    X11 {
+        opacity: Option<u32>,
+    },
+    // Backends that only know an alpha
+    Alpha(Option<u8>),
+}
+
+/*
+  Every window operation WinAlpha performs goes through this trait.
+  The monitor and the app state only ever talk to a backend, so the rule logic does not care what desktop it runs on.
+*/
+pub trait WindowBackend: Send + Sync {
+    /*
+      Returns all the top level windows.
+    */
+    fn enumerate_windows(&self) -> Vec<WindowHandle>;
+
+    /*
+      Returns every window below the parent, children of children included.
+    */
+    fn enumerate_child_windows(&self, parent: WindowHandle) -> Vec<WindowHandle>;
+
+    fn get_parent(&self, window: WindowHandle) -> Option<WindowHandle>;
+
+    /*
+      Returns if the window is one enumerate_windows would return, only those are matched against rules.
+    */
+    fn is_top_level(&self, window: WindowHandle) -> bool {
+        self.get_parent(window).is_none()
+    }
+
+    /*
+      Walks up from the window to the top level one it is in, rules are matched on that one.
+    */
+    fn get_top_level(&self, window: WindowHandle) -> WindowHandle {
+        let mut root = window;
+        while !self.is_top_level(root) {
+            let Some(parent) = self.get_parent(root) else {
+                break;
+            };
+            root = parent;
+        }
+
+        root
+    }
+
+    /*
+      Returns if the window is shown to the user, hidden and cloaked (other virtual desktop, suspended UWP) windows are not.
+    */
+    fn is_window_visible(&self, _window: WindowHandle) -> bool {
+        true
+    }
+
+    fn get_window_class(&self, window: WindowHandle) -> Option<String>;
+
+    fn get_window_title(&self, window: WindowHandle) -> Option<String>;
+
+    /*
+      Returns what kind of window it is, backends that cannot tell call everything a main window.
+    */
+    fn get_window_role(&self, _window: WindowHandle) -> WindowRole {
+        WindowRole::Main
+    }
+
+    fn get_process_id(&self, window: WindowHandle) -> Option<u32>;
+
+    /*
+      Returns the executable name of the process, without the extension.
+    */
+    fn get_process_name(&self, process_id: u32) -> Option<String>;
+
+    /*
+      Returns the full path of the executable the process was started from.
+    */
+    fn get_process_path(&self, process_id: u32) -> Option<String>;
+
+    /*
+      Returns when the process started, in whatever unit the platform uses.
+      Together with the process id it tells a process apart from a later one that reused the id.
+    */
+    fn get_process_start_time(&self, process_id: u32) -> Option<u64>;
+
+    /*
+      Returns the current alpha of the window, None when the window has not been made transparent.
+    */
+    fn get_window_alpha(&self, window: WindowHandle) -> Option<u8>;
+
+    fn set_window_alpha(&self, window: WindowHandle, transparency: u8) -> Result<()>;
+
+    /*
+      Records everything set_window_alpha is about to change, called before the first time a window is touched.
+    */
+    fn capture_window_state(&self, window: WindowHandle) -> Option<OriginalWindowState> {
+        Some(OriginalWindowState::Alpha(self.get_window_alpha(window)))
+    }
+
+    /*
+      Puts a window back the way capture_window_state found it.
+    */
+    fn restore_window_state(
+        &self,
+        window: WindowHandle,
+        state: &OriginalWindowState,
+    ) -> Result<()> {
+        match state {
+            OriginalWindowState::Alpha(alpha) => {
+                self.set_window_alpha(window, alpha.unwrap_or(u8::MAX))
+            }
+            _ => Err(anyhow::anyhow!(
+                "Window state {:?} was not captured by this backend",
+                state
+            )),
+        }
+    }
+
+    /*
+      Returns the monitors in the order the desktop lists them, empty when the backend cannot tell.
+    */
+    fn get_monitors(&self) -> Vec<MonitorInfo> {
+        Vec::new()
+    }
+
+    /*
+      Returns the monitor most of the window is on.
+    */
+    fn get_window_monitor(&self, _window: WindowHandle) -> Option<MonitorInfo> {
+        None
+    }
+
+    /*
+      Returns the top level window that has the keyboard focus.
+    */
+    fn get_foreground_window(&self) -> Option<WindowHandle> {
+        None
+    }
+
+    /*
+      Returns if the process is running with elevated (admin) rights.
+    */
+    fn is_elevated(&self, process_id: u32) -> bool;
+
+    /*
+      Returns the window at the given screen position.
+    */
+    fn window_from_point(&self, x: i32, y: i32) -> Option<WindowHandle>;
+
+    fn get_cursor_position(&self) -> Option<(i32, i32)>;
+
+    /*
+      This is "left click"
+    */
+    fn is_primary_button_down(&self) -> bool;
+
+    /*
+      Returns if the user can pick a window by clicking on it, some compositors do not share the cursor.
+    */
+    fn can_pick_windows(&self) -> bool {
+        true
+    }
+
+    /*
+      Returns a stream of window events, None when the backend can only be polled.
+    */
+    fn subscribe_events(&self) -> Option<UnboundedReceiver<WindowEvent>> {
+        None
+    }
+
+    /*
+      Returns all the top level windows with the given class.
+      Backends that can look up a class directly should override this.
+    */
+    fn find_windows_by_class(&self, class_name: &str) -> Vec<WindowHandle> {
+        self.enumerate_windows()
+            .into_iter()
+            .filter(|&window| self.get_window_class(window).as_deref() == Some(class_name))
+            .collect()
+    }
+
+    /*
+      Resolves the path, name and elevation of the process in one go.
+      Backends are not expected to cache this, CachedBackend does that on top of any backend.
+    */
+    fn get_process_identity(&self, process_id: u32) -> Option<ProcessIdentity> {
+        Some(ProcessIdentity {
+            path: self.get_process_path(process_id).unwrap_or_default(),
+            name: self.get_process_name(process_id)?,
+            elevated: self.is_elevated(process_id),
+        })
+    }
+
+    /*
+      Forgets anything remembered about processes that have exited, called once per full refresh.
+    */
+    fn prune_processes(&self) {}
+
+    fn get_window_process_identity(&self, window: WindowHandle) -> Option<ProcessIdentity> {
+        self.get_process_id(window)
+            .and_then(|process_id| self.get_process_identity(process_id))
+    }
+
+    /*
+      Returns the process name that owns the window.
+    */
+    fn get_window_process_name(&self, window: WindowHandle) -> Option<String> {
+        self.get_process_id(window)
+            .and_then(|process_id| self.get_process_name(process_id))
+    }
+
+    /*
+      Check if WinAlpha itself is running as admin.
+    */
+    fn is_running_as_admin(&self) -> bool {
+        self.is_elevated(std::process::id())
+    }
+}
+
+/*
+  Picks the backend for the desktop WinAlpha was started on.
+*/
+#[cfg(target_os = "windows")]
+pub fn default_backend() -> Result<std::sync::Arc<dyn WindowBackend>> {
+    Ok(std::sync::Arc::new(win32::Win32Backend::new()))
+}
+
+/*
+  On Wayland only the compositor can change another windows opacity, so sway and Hyprland are driven over their IPC.
+  Everything else goes through X11 (XWayland included).
+*/
+#[cfg(target_os = "linux")]
+pub fn default_backend() -> Result<std::sync::Arc<dyn WindowBackend>> {
+    if std::env::var_os("SWAYSOCK").is_some() {
+        return Ok(std::sync::Arc::new(sway::SwayBackend::from_env()?));
+    }
+
+    if std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+        return Ok(std::sync::Arc::new(hyprland::HyprlandBackend::from_env()?));
+    }
+
+    Ok(std::sync::Arc::new(x11::X11Backend::connect(None)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_types_map_onto_roles() {
+        // Every _NET_WM_WINDOW_TYPE the X11 backend knows, without the prefix
+        let expected = [
+            ("normal", WindowRole::Main),
+            ("dialog", WindowRole::Dialog),
+            ("combo", WindowRole::Popup),
+            ("dnd", WindowRole::Popup),
+            ("dropdown_menu", WindowRole::Popup),
+            ("menu", WindowRole::Popup),
+            ("notification", WindowRole::Popup),
+            ("popup_menu", WindowRole::Popup),
+            ("tooltip", WindowRole::Popup),
+            ("splash", WindowRole::Tool),
+            ("toolbar", WindowRole::Tool),
+            ("utility", WindowRole::Tool),
+        ];
+
+        for (window_type, role) in expected {
+            assert_eq!(
+                WindowRole::from_window_type(window_type),
+                role,
+                "{}",
+                window_type
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_window_types_are_main_windows() {
+        assert_eq!(WindowRole::from_window_type("desktop"), WindowRole::Main);
+        assert_eq!(WindowRole::from_window_type(""), WindowRole::Main);
+        // The names are lowercase, as the backends hand them over
+        assert_eq!(WindowRole::from_window_type("DIALOG"), WindowRole::Main);
+    }
+}
@@ -0,0 +1,48 @@
+use std::fs;
+
+/*
+  Process lookups through /proc, shared by every Linux backend.
+*/
+
+/*
+  Gets the process name from a provided process id.
+*/
+pub fn get_process_name(process_id: u32) -> Option<String> {
+    let comm = fs::read_to_string(format!("/proc/{}/comm", process_id)).ok()?;
+    let name = comm.trim_end();
+
+    (!name.is_empty()).then(|| name.to_owned())
+}
+
+pub fn get_process_path(process_id: u32) -> Option<String> {
+    fs::read_link(format!("/proc/{}/exe", process_id))
+        .ok()
+        .map(|path| path.to_string_lossy().into_owned())
+}
+
+/*
+  Field 22 of /proc/pid/stat, clock ticks since boot.
+  The command name before it can hold spaces and brackets, so the fields are counted from the last ')'.
+*/
+pub fn get_process_start_time(process_id: u32) -> Option<u64> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", process_id)).ok()?;
+    let (_, fields) = stat.rsplit_once(')')?;
+
+    fields.split_whitespace().nth(19)?.parse().ok()
+}
+
+/*
+  A process counts as elevated when it runs as root.
+*/
+pub fn is_elevated(process_id: u32) -> bool {
+    fs::read_to_string(format!("/proc/{}/status", process_id))
+        .ok()
+        .and_then(|status| {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix("Uid:"))
+                .and_then(|uids| uids.split_whitespace().nth(1))
+                .map(|effective_uid| effective_uid == "0")
+        })
+        .unwrap_or(false)
+}
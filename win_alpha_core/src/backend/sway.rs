@@ -0,0 +1,656 @@
+use super::{
+    forget_window, monitor_at, procfs, reconnect, MonitorInfo, WindowBackend, WindowEvent,
+    WindowHandle, WindowRole,
+};
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    env,
+    io::{self, Read, Write},
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+const IPC_MAGIC: &[u8; 6] = b"i3-ipc";
+const RUN_COMMAND: u32 = 0;
+const SUBSCRIBE: u32 = 2;
+const GET_OUTPUTS: u32 = 3;
+const GET_TREE: u32 = 4;
+// Events have the high bit set, window events are number 3
+const WINDOW_EVENT: u32 = 0x8000_0003;
+
+#[derive(Deserialize, Default, Clone, Copy)]
+struct SwayRect {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+#[derive(Deserialize, Default)]
+struct SwayWindowProperties {
+    #[serde(default)]
+    class: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SwayNode {
+    id: i64,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    pid: Option<u32>,
+    #[serde(default)]
+    app_id: Option<String>,
+    #[serde(default)]
+    window_properties: Option<SwayWindowProperties>,
+    // Only XWayland windows have one
+    #[serde(default)]
+    window_type: Option<String>,
+    #[serde(default)]
+    rect: SwayRect,
+    #[serde(default)]
+    visible: Option<bool>,
+    #[serde(default)]
+    focused: bool,
+    #[serde(default)]
+    nodes: Vec<SwayNode>,
+    #[serde(default)]
+    floating_nodes: Vec<SwayNode>,
+}
+
+#[derive(Deserialize)]
+struct SwayOutput {
+    name: String,
+    #[serde(default)]
+    active: bool,
+    #[serde(default)]
+    primary: bool,
+    #[serde(default)]
+    rect: SwayRect,
+}
+
+#[derive(Deserialize)]
+struct SwayWindowChange {
+    change: String,
+    container: SwayNode,
+}
+
+#[derive(Deserialize)]
+struct SwayCommandReply {
+    success: bool,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+#[derive(Clone)]
+struct SwayWindow {
+    class_name: String,
+    title: String,
+    process_id: u32,
+    rect: SwayRect,
+    visible: bool,
+    focused: bool,
+    role: WindowRole,
+}
+
+impl SwayNode {
+    /*
+      Native Wayland windows have an app_id, XWayland ones only have the X11 class.
+    */
+    fn class_name(&self) -> Option<String> {
+        self.app_id
+            .clone()
+            .filter(|app_id| !app_id.is_empty())
+            .or_else(|| self.window_properties.as_ref()?.class.clone())
+    }
+
+    fn collect_windows(&self, windows: &mut HashMap<WindowHandle, SwayWindow>) {
+        if let (Some(process_id), Some(class_name)) = (self.pid, self.class_name()) {
+            windows.insert(
+                self.id as WindowHandle,
+                SwayWindow {
+                    class_name,
+                    title: self.name.clone().unwrap_or_default(),
+                    process_id,
+                    rect: self.rect,
+                    visible: self.visible.unwrap_or(false),
+                    focused: self.focused,
+                    role: self
+                        .window_type
+                        .as_deref()
+                        .map(WindowRole::from_window_type)
+                        .unwrap_or_default(),
+                },
+            );
+        }
+
+        for node in self.nodes.iter().chain(self.floating_nodes.iter()) {
+            node.collect_windows(windows);
+        }
+    }
+}
+
+fn write_message(stream: &mut UnixStream, kind: u32, payload: &[u8]) -> io::Result<()> {
+    let mut message = Vec::with_capacity(IPC_MAGIC.len() + 8 + payload.len());
+    message.extend_from_slice(IPC_MAGIC);
+    message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+    message.extend_from_slice(&kind.to_ne_bytes());
+    message.extend_from_slice(payload);
+
+    stream.write_all(&message)
+}
+
+fn read_message(stream: &mut UnixStream) -> io::Result<(u32, Vec<u8>)> {
+    let mut header = [0u8; 14];
+    stream.read_exact(&mut header)?;
+
+    if &header[..6] != IPC_MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Reply is not an i3-ipc message.",
+        ));
+    }
+
+    let length = u32::from_ne_bytes([header[6], header[7], header[8], header[9]]);
+    let kind = u32::from_ne_bytes([header[10], header[11], header[12], header[13]]);
+
+    let mut payload = vec![0u8; length as usize];
+    stream.read_exact(&mut payload)?;
+
+    Ok((kind, payload))
+}
+
+/*
+  Opens a connection of its own for events, sway sends nothing else on a subscribed connection.
+*/
+fn subscribe_window_events(socket_path: &Path) -> io::Result<UnixStream> {
+    let mut stream = UnixStream::connect(socket_path)?;
+    write_message(&mut stream, SUBSCRIBE, br#"["window"]"#)?;
+    read_message(&mut stream)?;
+
+    Ok(stream)
+}
+
+fn parse_window_event(kind: u32, payload: &[u8]) -> Option<WindowEvent> {
+    if kind != WINDOW_EVENT {
+        return None;
+    }
+
+    let window_change = serde_json::from_slice::<SwayWindowChange>(payload).ok()?;
+    let handle = window_change.container.id as WindowHandle;
+
+    Some(match window_change.change.as_str() {
+        "new" => WindowEvent::Created(handle),
+        "close" => WindowEvent::Destroyed(handle),
+        "focus" => WindowEvent::Foreground(Some(handle)),
+        _ => WindowEvent::Changed(handle),
+    })
+}
+
+/*
+  Drives sway over its i3-ipc socket. Sway does not report opacity back, so the last value we set is remembered instead.
+*/
+pub struct SwayBackend {
+    socket_path: PathBuf,
+    connection: Mutex<UnixStream>,
+    windows: Arc<Mutex<HashMap<WindowHandle, SwayWindow>>>,
+    opacity: Mutex<HashMap<WindowHandle, u8>>,
+}
+
+impl SwayBackend {
+    /*
+      Connects to the socket at the given path, a fake server works just as well as sway.
+    */
+    pub fn connect(socket_path: impl Into<PathBuf>) -> Result<Self> {
+        let socket_path = socket_path.into();
+        let connection = UnixStream::connect(&socket_path)?;
+
+        Ok(Self {
+            socket_path,
+            connection: Mutex::new(connection),
+            windows: Arc::new(Mutex::new(HashMap::new())),
+            opacity: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn from_env() -> Result<Self> {
+        Self::connect(env::var("SWAYSOCK")?)
+    }
+
+    fn request(&self, kind: u32, payload: &[u8]) -> Result<Vec<u8>> {
+        let mut stream = self
+            .connection
+            .lock()
+            .map_err(|_| anyhow!("Sway connection lock poisoned."))?;
+
+        write_message(&mut stream, kind, payload)?;
+
+        loop {
+            let (reply_kind, reply) = read_message(&mut stream)?;
+            if reply_kind == kind {
+                return Ok(reply);
+            }
+        }
+    }
+
+    fn refresh_windows(&self) -> Vec<WindowHandle> {
+        let Some(tree) = self
+            .request(GET_TREE, &[])
+            .ok()
+            .and_then(|reply| serde_json::from_slice::<SwayNode>(&reply).ok())
+        else {
+            return Vec::new();
+        };
+
+        let mut windows = HashMap::new();
+        tree.collect_windows(&mut windows);
+
+        let handles: Vec<WindowHandle> = windows.keys().copied().collect();
+        if let Ok(mut cached) = self.windows.lock() {
+            *cached = windows;
+        }
+        if let Ok(mut opacity) = self.opacity.lock() {
+            opacity.retain(|handle, _| handles.contains(handle));
+        }
+
+        handles
+    }
+
+    /*
+      Looks the window up in the last tree, asking sway again when it is not there yet.
+      Events drop the entries of the windows they are about, so a renamed window is read again.
+    */
+    fn get_window(&self, window: WindowHandle) -> Option<SwayWindow> {
+        if let Some(found) = self.windows.lock().ok()?.get(&window).cloned() {
+            return Some(found);
+        }
+
+        self.refresh_windows();
+        self.windows.lock().ok()?.get(&window).cloned()
+    }
+}
+
+impl WindowBackend for SwayBackend {
+    fn enumerate_windows(&self) -> Vec<WindowHandle> {
+        self.refresh_windows()
+    }
+
+    // Sway containers that hold an application have no children of their own
+    fn enumerate_child_windows(&self, _parent: WindowHandle) -> Vec<WindowHandle> {
+        Vec::new()
+    }
+
+    fn get_parent(&self, _window: WindowHandle) -> Option<WindowHandle> {
+        None
+    }
+
+    fn get_window_class(&self, window: WindowHandle) -> Option<String> {
+        Some(self.get_window(window)?.class_name)
+    }
+
+    fn get_window_title(&self, window: WindowHandle) -> Option<String> {
+        Some(self.get_window(window)?.title)
+    }
+
+    fn get_window_role(&self, window: WindowHandle) -> WindowRole {
+        self.get_window(window)
+            .map(|window| window.role)
+            .unwrap_or_default()
+    }
+
+    fn get_process_id(&self, window: WindowHandle) -> Option<u32> {
+        Some(self.get_window(window)?.process_id)
+    }
+
+    fn get_process_name(&self, process_id: u32) -> Option<String> {
+        procfs::get_process_name(process_id)
+    }
+
+    fn get_process_path(&self, process_id: u32) -> Option<String> {
+        procfs::get_process_path(process_id)
+    }
+
+    fn get_process_start_time(&self, process_id: u32) -> Option<u64> {
+        procfs::get_process_start_time(process_id)
+    }
+
+    fn get_window_alpha(&self, window: WindowHandle) -> Option<u8> {
+        self.opacity.lock().ok()?.get(&window).copied()
+    }
+
+    fn set_window_alpha(&self, window: WindowHandle, transparency: u8) -> Result<()> {
+        let command = format!(
+            "[con_id={}] opacity {:.3}",
+            window,
+            transparency as f32 / 255.0
+        );
+        let reply = self.request(RUN_COMMAND, command.as_bytes())?;
+        let results: Vec<SwayCommandReply> = serde_json::from_slice(&reply)?;
+
+        if let Some(failed) = results.into_iter().find(|result| !result.success) {
+            return Err(anyhow!(
+                "Failed to set window opacity {}",
+                failed.error.unwrap_or_default()
+            ));
+        }
+
+        if let Ok(mut opacity) = self.opacity.lock() {
+            opacity.insert(window, transparency);
+        }
+
+        Ok(())
+    }
+
+    fn is_elevated(&self, process_id: u32) -> bool {
+        procfs::is_elevated(process_id)
+    }
+
+    fn window_from_point(&self, x: i32, y: i32) -> Option<WindowHandle> {
+        self.refresh_windows();
+
+        self.windows
+            .lock()
+            .ok()?
+            .iter()
+            .find(|(_, window)| {
+                let rect = window.rect;
+                window.visible
+                    && x >= rect.x
+                    && x < rect.x + rect.width
+                    && y >= rect.y
+                    && y < rect.y + rect.height
+            })
+            .map(|(&handle, _)| handle)
+    }
+
+    /*
+      Active outputs in the order sway lists them. Sway never marks one primary, so the first one stands in.
+    */
+    fn get_monitors(&self) -> Vec<MonitorInfo> {
+        let Some(outputs) = self
+            .request(GET_OUTPUTS, &[])
+            .ok()
+            .and_then(|reply| serde_json::from_slice::<Vec<SwayOutput>>(&reply).ok())
+        else {
+            return Vec::new();
+        };
+
+        let outputs: Vec<SwayOutput> = outputs.into_iter().filter(|output| output.active).collect();
+        let has_primary = outputs.iter().any(|output| output.primary);
+
+        outputs
+            .into_iter()
+            .enumerate()
+            .map(|(index, output)| MonitorInfo {
+                index,
+                name: output.name,
+                primary: output.primary || (!has_primary && index == 0),
+                rect: (
+                    output.rect.x,
+                    output.rect.y,
+                    output.rect.x + output.rect.width,
+                    output.rect.y + output.rect.height,
+                ),
+            })
+            .collect()
+    }
+
+    fn get_window_monitor(&self, window: WindowHandle) -> Option<MonitorInfo> {
+        let rect = self.get_window(window)?.rect;
+
+        monitor_at(
+            self.get_monitors(),
+            (rect.x + rect.width / 2, rect.y + rect.height / 2),
+        )
+    }
+
+    fn get_foreground_window(&self) -> Option<WindowHandle> {
+        self.refresh_windows();
+
+        self.windows
+            .lock()
+            .ok()?
+            .iter()
+            .find(|(_, window)| window.focused)
+            .map(|(&handle, _)| handle)
+    }
+
+    // Sway does not hand the cursor out over IPC
+    fn get_cursor_position(&self) -> Option<(i32, i32)> {
+        None
+    }
+
+    fn is_primary_button_down(&self) -> bool {
+        false
+    }
+
+    fn can_pick_windows(&self) -> bool {
+        false
+    }
+
+    /*
+      Opens a second connection subscribed to window events, a thread forwards them until the receiver is dropped.
+    */
+    fn subscribe_events(&self) -> Option<UnboundedReceiver<WindowEvent>> {
+        let mut stream = subscribe_window_events(&self.socket_path).ok()?;
+        let socket_path = self.socket_path.clone();
+        let windows = Arc::clone(&self.windows);
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        thread::spawn(move || loop {
+            while let Ok((kind, payload)) = read_message(&mut stream) {
+                let Some(event) = parse_window_event(kind, &payload) else {
+                    continue;
+                };
+
+                forget_window(&windows, event);
+                if tx.send(event).is_err() {
+                    return;
+                }
+            }
+
+            // Sway restarted or dropped us, the subscription has to be made again on a new connection
+            match reconnect(|| subscribe_window_events(&socket_path)) {
+                Some(connection) => stream = connection,
+                None => return,
+            }
+        });
+
+        Some(rx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        fs,
+        os::unix::net::UnixListener,
+        process,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    static NEXT_SOCKET: AtomicUsize = AtomicUsize::new(0);
+
+    fn socket_path() -> PathBuf {
+        env::temp_dir().join(format!(
+            "win_alpha_sway_{}_{}.sock",
+            process::id(),
+            NEXT_SOCKET.fetch_add(1, Ordering::Relaxed)
+        ))
+    }
+
+    // Built by hand rather than with write_message, so the framing is checked against the protocol and not against itself
+    fn frame(kind: u32, payload: &str) -> Vec<u8> {
+        let mut message = b"i3-ipc".to_vec();
+        message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+        message.extend_from_slice(&kind.to_ne_bytes());
+        message.extend_from_slice(payload.as_bytes());
+        message
+    }
+
+    fn read_frame(stream: &mut UnixStream) -> (u32, String) {
+        let mut header = [0u8; 14];
+        stream.read_exact(&mut header).unwrap();
+        assert_eq!(&header[..6], b"i3-ipc");
+
+        let length = u32::from_ne_bytes(header[6..10].try_into().unwrap());
+        let kind = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+        let mut payload = vec![0u8; length as usize];
+        stream.read_exact(&mut payload).unwrap();
+
+        (kind, String::from_utf8(payload).unwrap())
+    }
+
+    fn window_event(change: &str, id: i64) -> Vec<u8> {
+        frame(
+            WINDOW_EVENT,
+            &format!(r#"{{"change":"{}","container":{{"id":{}}}}}"#, change, id),
+        )
+    }
+
+    /*
+      Answers the subscribe request of an event connection and sends the given messages after it.
+    */
+    fn serve_subscription(listener: &UnixListener, messages: &[Vec<u8>]) -> UnixStream {
+        let (mut stream, _) = listener.accept().unwrap();
+        assert_eq!(
+            read_frame(&mut stream),
+            (SUBSCRIBE, r#"["window"]"#.to_owned())
+        );
+        stream
+            .write_all(&frame(SUBSCRIBE, r#"{"success":true}"#))
+            .unwrap();
+
+        for message in messages {
+            stream.write_all(message).unwrap();
+        }
+
+        stream
+    }
+
+    #[test]
+    fn requests_are_framed_and_tree_is_read() {
+        let path = socket_path();
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            assert_eq!(read_frame(&mut stream), (GET_TREE, String::new()));
+
+            // An event that slipped in before the reply has to be skipped
+            stream.write_all(&window_event("title", 7)).unwrap();
+            stream
+                .write_all(&frame(
+                    GET_TREE,
+                    r#"{"id":1,"nodes":[{"id":7,"name":"Terminal","pid":42,"app_id":"foot","visible":true,
+                        "rect":{"x":10,"y":20,"width":300,"height":200}},
+                        {"id":8,"name":"workspace"}],
+                       "floating_nodes":[{"id":9,"name":"Game","pid":43,"app_id":"",
+                        "window_properties":{"class":"Steam"}}]}"#,
+                ))
+                .unwrap();
+
+            assert_eq!(
+                read_frame(&mut stream),
+                (RUN_COMMAND, "[con_id=7] opacity 0.502".to_owned())
+            );
+            stream
+                .write_all(&frame(RUN_COMMAND, r#"[{"success":true}]"#))
+                .unwrap();
+
+            assert_eq!(read_frame(&mut stream).0, RUN_COMMAND);
+            stream
+                .write_all(&frame(
+                    RUN_COMMAND,
+                    r#"[{"success":false,"error":"No matching node."}]"#,
+                ))
+                .unwrap();
+        });
+
+        let backend = SwayBackend::connect(&path).unwrap();
+        let mut windows = backend.enumerate_windows();
+        windows.sort();
+
+        assert_eq!(windows, vec![7, 9]);
+        assert_eq!(backend.get_window_class(7).as_deref(), Some("foot"));
+        assert_eq!(backend.get_window_class(9).as_deref(), Some("Steam"));
+        assert_eq!(backend.get_window_title(7).as_deref(), Some("Terminal"));
+        assert_eq!(backend.get_process_id(9), Some(43));
+
+        backend.set_window_alpha(7, 128).unwrap();
+        assert_eq!(backend.get_window_alpha(7), Some(128));
+        assert!(backend.set_window_alpha(7, 64).is_err());
+        assert_eq!(backend.get_window_alpha(7), Some(128));
+
+        server.join().unwrap();
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn window_events_are_parsed() {
+        assert_eq!(
+            parse_window_event(WINDOW_EVENT, &window_event("new", 5)[14..]),
+            Some(WindowEvent::Created(5))
+        );
+        assert_eq!(
+            parse_window_event(WINDOW_EVENT, &window_event("close", 5)[14..]),
+            Some(WindowEvent::Destroyed(5))
+        );
+        assert_eq!(
+            parse_window_event(WINDOW_EVENT, &window_event("focus", 5)[14..]),
+            Some(WindowEvent::Foreground(Some(5)))
+        );
+        assert_eq!(
+            parse_window_event(WINDOW_EVENT, &window_event("move", 5)[14..]),
+            Some(WindowEvent::Changed(5))
+        );
+        // Workspace events and broken payloads are not window events
+        assert_eq!(parse_window_event(0x8000_0000, b"{}"), None);
+        assert_eq!(parse_window_event(WINDOW_EVENT, b"not json"), None);
+    }
+
+    #[test]
+    fn events_reconnect_after_eof() {
+        let path = socket_path();
+        let listener = UnixListener::bind(&path).unwrap();
+        let backend = SwayBackend::connect(&path).unwrap();
+        // The request connection of the backend, unused here
+        let (_requests, _) = listener.accept().unwrap();
+
+        let server = thread::spawn(move || {
+            let first = serve_subscription(
+                &listener,
+                &[
+                    window_event("new", 1),
+                    frame(0x8000_0000, r#"{"change":"focus"}"#),
+                    window_event("focus", 1),
+                ],
+            );
+            // Sway goes away, the backend has to subscribe again
+            drop(first);
+
+            serve_subscription(&listener, &[window_event("close", 1)])
+        });
+
+        let mut events = backend.subscribe_events().unwrap();
+        let received: Vec<WindowEvent> = (0..3).filter_map(|_| events.blocking_recv()).collect();
+
+        assert_eq!(
+            received,
+            vec![
+                WindowEvent::Created(1),
+                WindowEvent::Foreground(Some(1)),
+                WindowEvent::Destroyed(1),
+            ]
+        );
+
+        let _second = server.join().unwrap();
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,722 @@
+use super::{
+    MonitorInfo, OriginalWindowState, WindowBackend, WindowEvent, WindowHandle, WindowRole,
+};
+use anyhow::{anyhow, Result};
+use core::{cell::RefCell, ffi::c_void, iter::once, mem::size_of};
+use std::{sync::mpsc as std_mpsc, thread};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use windows::{
+    core::{PCWSTR, PWSTR},
+    Win32::{
+        Foundation::{
+            CloseHandle, BOOL, COLORREF, ERROR_INSUFFICIENT_BUFFER, FILETIME, HANDLE, HWND, LPARAM,
+            MAX_PATH, POINT, RECT,
+        },
+        Graphics::{
+            Dwm::{DwmGetWindowAttribute, DWMWA_CLOAKED},
+            Gdi::{
+                EnumDisplayMonitors, GetMonitorInfoW, MonitorFromWindow, HDC, HMONITOR,
+                MONITORINFO, MONITORINFOEXW, MONITOR_DEFAULTTONEAREST,
+            },
+        },
+        Security::{GetTokenInformation, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY},
+        System::Threading::{
+            GetProcessTimes, OpenProcess, OpenProcessToken, QueryFullProcessImageNameW,
+            PROCESS_NAME_FORMAT, PROCESS_QUERY_LIMITED_INFORMATION,
+        },
+        UI::{
+            Accessibility::{SetWinEventHook, UnhookWinEvent, HWINEVENTHOOK},
+            Input::KeyboardAndMouse::{GetAsyncKeyState, VK_LBUTTON},
+            WindowsAndMessaging::{
+                DispatchMessageW, EnumChildWindows, EnumWindows, FindWindowExW, FindWindowW,
+                GetAncestor, GetClassNameW, GetCursorPos, GetForegroundWindow,
+                GetLayeredWindowAttributes, GetMessageW, GetParent, GetWindow, GetWindowLongW,
+                GetWindowTextW, GetWindowThreadProcessId, IsWindowVisible, PostQuitMessage,
+                SetLayeredWindowAttributes, SetWindowLongW, WindowFromPoint, CHILDID_SELF,
+                EVENT_OBJECT_CREATE, EVENT_OBJECT_DESTROY, EVENT_OBJECT_NAMECHANGE,
+                EVENT_OBJECT_SHOW, EVENT_SYSTEM_FOREGROUND, EVENT_SYSTEM_MOVESIZEEND, GA_ROOT,
+                GWL_EXSTYLE, GWL_STYLE, GW_OWNER, LAYERED_WINDOW_ATTRIBUTES_FLAGS, LWA_ALPHA,
+                MONITORINFOF_PRIMARY, MSG, OBJID_WINDOW, WINEVENT_OUTOFCONTEXT,
+                WINEVENT_SKIPOWNPROCESS, WS_CAPTION, WS_EX_DLGMODALFRAME, WS_EX_LAYERED,
+                WS_EX_TOOLWINDOW, WS_POPUP,
+            },
+        },
+    },
+};
+
+// High bit of GetAsyncKeyState, the key is currently down
+const KEY_PRESSED: i16 = 0x8000u16 as i16;
+
+// Longest path Windows hands out, in UTF-16 units
+const MAX_LONG_PATH: usize = 32768;
+
+thread_local! {
+    // WinEvent callbacks carry no user data, so every hook thread keeps its sender here.
+    static EVENT_SENDER: RefCell<Option<UnboundedSender<WindowEvent>>> = const { RefCell::new(None) };
+}
+
+/*
+  The real desktop, every call here goes straight to the Win32 api.
+*/
+#[derive(Default)]
+pub struct Win32Backend;
+
+impl Win32Backend {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[inline]
+fn to_hwnd(window: WindowHandle) -> HWND {
+    HWND(window as *mut c_void)
+}
+
+#[inline]
+fn from_hwnd(hwnd: HWND) -> WindowHandle {
+    hwnd.0 as WindowHandle
+}
+
+unsafe extern "system" fn collect_windows_proc(hwnd: HWND, lparam: LPARAM) -> BOOL {
+    let handles = &mut *(lparam.0 as *mut Vec<WindowHandle>);
+    handles.push(from_hwnd(hwnd));
+    true.into()
+}
+
+unsafe extern "system" fn collect_monitors_proc(
+    monitor: HMONITOR,
+    _hdc: HDC,
+    _rect: *mut RECT,
+    lparam: LPARAM,
+) -> BOOL {
+    let monitors = &mut *(lparam.0 as *mut Vec<HMONITOR>);
+    monitors.push(monitor);
+    true.into()
+}
+
+/*
+  Every monitor handle, in the order Windows enumerates them.
+*/
+fn enumerate_monitor_handles() -> Vec<HMONITOR> {
+    let mut monitors: Vec<HMONITOR> = Vec::new();
+
+    unsafe {
+        _ = EnumDisplayMonitors(
+            None,
+            None,
+            Some(collect_monitors_proc),
+            LPARAM(&mut monitors as *mut _ as isize),
+        );
+    }
+
+    monitors
+}
+
+fn get_monitor_info(index: usize, monitor: HMONITOR) -> Option<MonitorInfo> {
+    let mut info = MONITORINFOEXW::default();
+    info.monitorInfo.cbSize = size_of::<MONITORINFOEXW>() as u32;
+
+    unsafe {
+        if !GetMonitorInfoW(
+            monitor,
+            &mut info as *mut MONITORINFOEXW as *mut MONITORINFO,
+        )
+        .as_bool()
+        {
+            return None;
+        }
+    }
+
+    let name_length = info
+        .szDevice
+        .iter()
+        .position(|&c| c == 0)
+        .unwrap_or(info.szDevice.len());
+    let rect = info.monitorInfo.rcMonitor;
+
+    Some(MonitorInfo {
+        index,
+        name: String::from_utf16_lossy(&info.szDevice[..name_length]),
+        primary: info.monitorInfo.dwFlags & MONITORINFOF_PRIMARY != 0,
+        rect: (rect.left, rect.top, rect.right, rect.bottom),
+    })
+}
+
+/*
+  Sorts a window into a role by its style, extended style and whether another window owns it.
+  Captionless popups that are owned or tool windows are menus, dropdowns and tooltips.
+  An unowned captionless popup is left as a main window, borderless apps draw their own frame that way.
+*/
+fn classify_window(style: u32, ex_style: u32, owned: bool) -> WindowRole {
+    let tool = ex_style & WS_EX_TOOLWINDOW.0 != 0;
+    let captionless_popup = style & WS_POPUP.0 != 0 && style & WS_CAPTION.0 != WS_CAPTION.0;
+
+    if captionless_popup && (owned || tool) {
+        WindowRole::Popup
+    } else if tool {
+        WindowRole::Tool
+    } else if owned || ex_style & WS_EX_DLGMODALFRAME.0 != 0 {
+        WindowRole::Dialog
+    } else {
+        WindowRole::Main
+    }
+}
+
+/*
+  Called on the hook thread for every window event, only top level windows are forwarded since child controls never match a rule.
+  Quits the hook threads message loop once nobody is listening anymore.
+*/
+unsafe extern "system" fn win_event_proc(
+    _hook: HWINEVENTHOOK,
+    event: u32,
+    hwnd: HWND,
+    id_object: i32,
+    id_child: i32,
+    _event_thread: u32,
+    _event_time: u32,
+) {
+    if id_object != OBJID_WINDOW.0 || id_child != CHILDID_SELF as i32 || hwnd.is_invalid() {
+        return;
+    }
+
+    // Destroyed windows no longer have an ancestor to check
+    if event != EVENT_OBJECT_DESTROY && GetAncestor(hwnd, GA_ROOT) != hwnd {
+        return;
+    }
+
+    let window = from_hwnd(hwnd);
+    let window_event = match event {
+        EVENT_OBJECT_CREATE => WindowEvent::Created(window),
+        EVENT_OBJECT_DESTROY => WindowEvent::Destroyed(window),
+        EVENT_SYSTEM_FOREGROUND => WindowEvent::Foreground(Some(window)),
+        _ => WindowEvent::Changed(window),
+    };
+
+    let sent = EVENT_SENDER.with_borrow(|sender| {
+        sender
+            .as_ref()
+            .is_some_and(|tx| tx.send(window_event).is_ok())
+    });
+
+    if !sent {
+        PostQuitMessage(0);
+    }
+}
+
+impl WindowBackend for Win32Backend {
+    fn enumerate_windows(&self) -> Vec<WindowHandle> {
+        let mut handles: Vec<WindowHandle> = Vec::new();
+
+        unsafe {
+            _ = EnumWindows(
+                Some(collect_windows_proc),
+                LPARAM(&mut handles as *mut _ as isize),
+            );
+        }
+
+        handles
+    }
+
+    fn enumerate_child_windows(&self, parent: WindowHandle) -> Vec<WindowHandle> {
+        let mut handles: Vec<WindowHandle> = Vec::new();
+
+        unsafe {
+            _ = EnumChildWindows(
+                Some(to_hwnd(parent)),
+                Some(collect_windows_proc),
+                LPARAM(&mut handles as *mut _ as isize),
+            );
+        }
+
+        handles
+    }
+
+    fn get_parent(&self, window: WindowHandle) -> Option<WindowHandle> {
+        unsafe { GetParent(to_hwnd(window)).ok().map(from_hwnd) }
+    }
+
+    /*
+      Owned windows (dialogs, tool windows) are still top level, GetParent would return their owner.
+    */
+    fn is_top_level(&self, window: WindowHandle) -> bool {
+        let hwnd = to_hwnd(window);
+
+        unsafe { GetAncestor(hwnd, GA_ROOT) == hwnd }
+    }
+
+    fn is_window_visible(&self, window: WindowHandle) -> bool {
+        let hwnd = to_hwnd(window);
+        let mut cloaked = 0u32;
+
+        unsafe {
+            if !IsWindowVisible(hwnd).as_bool() {
+                return false;
+            }
+
+            DwmGetWindowAttribute(
+                hwnd,
+                DWMWA_CLOAKED,
+                &mut cloaked as *mut u32 as *mut c_void,
+                size_of::<u32>() as u32,
+            )
+            .is_err()
+                || cloaked == 0
+        }
+    }
+
+    fn get_window_class(&self, window: WindowHandle) -> Option<String> {
+        let mut class_name = [0u16; MAX_PATH as usize];
+
+        unsafe {
+            let length = GetClassNameW(to_hwnd(window), &mut class_name);
+
+            if length == 0 {
+                return None;
+            }
+
+            String::from_utf16_lossy(&class_name[..length as usize])
+                .trim_end_matches('\0')
+                .to_string()
+                .into()
+        }
+    }
+
+    fn get_window_title(&self, window: WindowHandle) -> Option<String> {
+        let mut title = [0u16; 512];
+
+        unsafe {
+            let length = GetWindowTextW(to_hwnd(window), &mut title);
+
+            if length <= 0 {
+                return None;
+            }
+
+            Some(String::from_utf16_lossy(&title[..length as usize]))
+        }
+    }
+
+    fn get_window_role(&self, window: WindowHandle) -> WindowRole {
+        let hwnd = to_hwnd(window);
+
+        unsafe {
+            let style = GetWindowLongW(hwnd, GWL_STYLE) as u32;
+            let ex_style = GetWindowLongW(hwnd, GWL_EXSTYLE) as u32;
+            let owned = GetWindow(hwnd, GW_OWNER).is_ok();
+
+            classify_window(style, ex_style, owned)
+        }
+    }
+
+    fn get_process_id(&self, window: WindowHandle) -> Option<u32> {
+        let mut process_id = 0;
+
+        unsafe {
+            GetWindowThreadProcessId(to_hwnd(window), Some(&mut process_id));
+        }
+
+        (process_id != 0).then_some(process_id)
+    }
+
+    /*
+      Gets the process name from a provided process id.
+    */
+    fn get_process_name(&self, process_id: u32) -> Option<String> {
+        // The file name without its extension, only the last one so my.tool.exe is my.tool
+        let path = self.get_process_path(process_id)?;
+        let file_name = path.rsplit('\\').next()?;
+
+        Some(
+            file_name
+                .rsplit_once('.')
+                .map_or(file_name, |(stem, _)| stem)
+                .to_owned(),
+        )
+    }
+
+    /*
+      The wide version so paths with non ASCII characters come back intact.
+      Starts at MAX_PATH and grows for long paths (\\?\ prefixed installs).
+    */
+    fn get_process_path(&self, process_id: u32) -> Option<String> {
+        unsafe {
+            let process_handle =
+                OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, process_id).ok()?;
+
+            let mut buffer = vec![0u16; MAX_PATH as usize];
+            let path = loop {
+                let mut size = buffer.len() as u32;
+                let result = QueryFullProcessImageNameW(
+                    process_handle,
+                    PROCESS_NAME_FORMAT(0),
+                    PWSTR(buffer.as_mut_ptr()),
+                    &mut size,
+                );
+
+                match result {
+                    Ok(()) => break Some(String::from_utf16_lossy(&buffer[..size as usize])),
+                    Err(e)
+                        if e.code() == ERROR_INSUFFICIENT_BUFFER.to_hresult()
+                            && buffer.len() < MAX_LONG_PATH =>
+                    {
+                        buffer.resize(buffer.len() * 2, 0);
+                    }
+                    Err(_) => break None,
+                }
+            };
+            _ = CloseHandle(process_handle);
+
+            path
+        }
+    }
+
+    /*
+      The creation time of the process as a FILETIME.
+    */
+    fn get_process_start_time(&self, process_id: u32) -> Option<u64> {
+        let mut creation_time = FILETIME::default();
+        let mut exit_time = FILETIME::default();
+        let mut kernel_time = FILETIME::default();
+        let mut user_time = FILETIME::default();
+
+        unsafe {
+            let process_handle =
+                OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, process_id).ok()?;
+
+            let result = GetProcessTimes(
+                process_handle,
+                &mut creation_time,
+                &mut exit_time,
+                &mut kernel_time,
+                &mut user_time,
+            );
+            _ = CloseHandle(process_handle);
+            result.ok()?;
+        }
+
+        Some(((creation_time.dwHighDateTime as u64) << 32) | creation_time.dwLowDateTime as u64)
+    }
+
+    fn get_window_alpha(&self, window: WindowHandle) -> Option<u8> {
+        let hwnd = to_hwnd(window);
+        let mut alpha = 0u8;
+        let mut flags = LAYERED_WINDOW_ATTRIBUTES_FLAGS(0);
+
+        unsafe {
+            if GetWindowLongW(hwnd, GWL_EXSTYLE) & WS_EX_LAYERED.0 as i32 == 0 {
+                return None;
+            }
+
+            GetLayeredWindowAttributes(hwnd, None, Some(&mut alpha), Some(&mut flags)).ok()?;
+        }
+
+        flags.contains(LWA_ALPHA).then_some(alpha)
+    }
+
+    /*
+      Sets the transparency of the handles window.
+    */
+    fn set_window_alpha(&self, window: WindowHandle, transparency: u8) -> Result<()> {
+        let window_handle = to_hwnd(window);
+
+        unsafe {
+            SetWindowLongW(
+                window_handle,
+                GWL_EXSTYLE,
+                GetWindowLongW(window_handle, GWL_EXSTYLE) | WS_EX_LAYERED.0 as i32,
+            );
+
+            SetLayeredWindowAttributes(window_handle, COLORREF(0), transparency, LWA_ALPHA)
+                .map_err(|err| anyhow!("Failed to set window alpha {}", err))
+        }
+    }
+
+    fn capture_window_state(&self, window: WindowHandle) -> Option<OriginalWindowState> {
+        let hwnd = to_hwnd(window);
+
+        unsafe {
+            let ex_style = GetWindowLongW(hwnd, GWL_EXSTYLE) as u32;
+            let mut layered = None;
+
+            if ex_style & WS_EX_LAYERED.0 != 0 {
+                let mut color_key = COLORREF(0);
+                let mut alpha = 0u8;
+                let mut flags = LAYERED_WINDOW_ATTRIBUTES_FLAGS(0);
+
+                // Fails for windows drawn with UpdateLayeredWindow, only their style is restored
+                if GetLayeredWindowAttributes(
+                    hwnd,
+                    Some(&mut color_key),
+                    Some(&mut alpha),
+                    Some(&mut flags),
+                )
+                .is_ok()
+                {
+                    layered = Some((color_key.0, alpha, flags.0));
+                }
+            }
+
+            Some(OriginalWindowState::Win32 { ex_style, layered })
+        }
+    }
+
+    /*
+      Only WS_EX_LAYERED goes back to how it was, the app may have changed the rest of its style since (topmost, tool window...) and keeps that.
+      A window that was not layered before loses it again, which also drops our alpha. One that was gets its layered attributes back.
+    */
+    fn restore_window_state(
+        &self,
+        window: WindowHandle,
+        state: &OriginalWindowState,
+    ) -> Result<()> {
+        let OriginalWindowState::Win32 { ex_style, layered } = *state else {
+            return Err(anyhow!(
+                "Window state {:?} was not captured by win32",
+                state
+            ));
+        };
+        let hwnd = to_hwnd(window);
+
+        unsafe {
+            let current = GetWindowLongW(hwnd, GWL_EXSTYLE) as u32;
+            let is_layered = current & WS_EX_LAYERED.0 != 0;
+
+            if ex_style & WS_EX_LAYERED.0 == 0 {
+                if is_layered {
+                    SetWindowLongW(hwnd, GWL_EXSTYLE, (current & !WS_EX_LAYERED.0) as i32);
+                }
+                return Ok(());
+            }
+
+            if !is_layered {
+                SetWindowLongW(hwnd, GWL_EXSTYLE, (current | WS_EX_LAYERED.0) as i32);
+            }
+
+            if let Some((color_key, alpha, flags)) = layered {
+                SetLayeredWindowAttributes(
+                    hwnd,
+                    COLORREF(color_key),
+                    alpha,
+                    LAYERED_WINDOW_ATTRIBUTES_FLAGS(flags),
+                )
+                .map_err(|err| anyhow!("Failed to restore layered attributes {}", err))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /*
+      Returns if the process is running as admin.
+      Used by the UI to make the user aware when a program they want to select a administrator program.
+      Hopefully they will realize they need to run WinAlpha to select it.
+    */
+    fn is_elevated(&self, process_id: u32) -> bool {
+        let mut elevation = TOKEN_ELEVATION::default();
+        let mut size = size_of::<TOKEN_ELEVATION>() as u32;
+        let mut token = HANDLE::default();
+
+        unsafe {
+            let Ok(process) = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, process_id)
+            else {
+                return false;
+            };
+
+            let opened = OpenProcessToken(process, TOKEN_QUERY, &mut token).is_ok();
+            _ = CloseHandle(process);
+
+            if !opened {
+                return false;
+            }
+
+            let result = GetTokenInformation(
+                token,
+                TokenElevation,
+                Some(&mut elevation as *mut _ as *mut c_void),
+                size,
+                &mut size,
+            );
+            _ = CloseHandle(token);
+
+            result.is_ok_and(|_| elevation.TokenIsElevated != 0)
+        }
+    }
+
+    fn window_from_point(&self, x: i32, y: i32) -> Option<WindowHandle> {
+        unsafe {
+            let hwnd = WindowFromPoint(POINT { x, y });
+
+            (!hwnd.0.is_null()).then(|| from_hwnd(hwnd))
+        }
+    }
+
+    fn get_monitors(&self) -> Vec<MonitorInfo> {
+        enumerate_monitor_handles()
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, monitor)| get_monitor_info(index, monitor))
+            .collect()
+    }
+
+    fn get_window_monitor(&self, window: WindowHandle) -> Option<MonitorInfo> {
+        let monitor = unsafe { MonitorFromWindow(to_hwnd(window), MONITOR_DEFAULTTONEAREST) };
+        let index = enumerate_monitor_handles()
+            .into_iter()
+            .position(|handle| handle == monitor)?;
+
+        get_monitor_info(index, monitor)
+    }
+
+    fn get_foreground_window(&self) -> Option<WindowHandle> {
+        let hwnd = unsafe { GetForegroundWindow() };
+
+        (!hwnd.is_invalid()).then(|| from_hwnd(hwnd))
+    }
+
+    fn get_cursor_position(&self) -> Option<(i32, i32)> {
+        let mut point = POINT::default();
+
+        unsafe { GetCursorPos(&mut point).ok()? };
+
+        Some((point.x, point.y))
+    }
+
+    fn is_primary_button_down(&self) -> bool {
+        unsafe { (GetAsyncKeyState(VK_LBUTTON.0.into()) & KEY_PRESSED) != 0 }
+    }
+
+    /*
+      Returns all the current handles for the classname
+    */
+    fn find_windows_by_class(&self, class_name: &str) -> Vec<WindowHandle> {
+        let wide_class: Vec<u16> = class_name.encode_utf16().chain(once(0)).collect();
+
+        let class_ptr = PCWSTR::from_raw(wide_class.as_ptr());
+        let mut handles = Vec::new();
+
+        unsafe {
+            if let Ok(mut hwnd) = FindWindowW(class_ptr, None) {
+                while !hwnd.is_invalid() {
+                    handles.push(from_hwnd(hwnd));
+
+                    hwnd = match FindWindowExW(None, Some(hwnd), class_ptr, None) {
+                        Ok(next_hwnd) if !next_hwnd.is_invalid() => next_hwnd,
+                        _ => break,
+                    };
+                }
+            }
+        }
+
+        handles
+    }
+
+    /*
+      Hooks window create/destroy/show, title and focus changes, the hooks live on their own thread with a message loop.
+      Out of context hooks are delivered while that thread waits in GetMessageW.
+    */
+    fn subscribe_events(&self) -> Option<UnboundedReceiver<WindowEvent>> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let (hooked_tx, hooked_rx) = std_mpsc::channel();
+
+        thread::spawn(move || unsafe {
+            EVENT_SENDER.set(Some(tx));
+
+            let flags = WINEVENT_OUTOFCONTEXT | WINEVENT_SKIPOWNPROCESS;
+            let hooks = [
+                SetWinEventHook(
+                    EVENT_OBJECT_CREATE,
+                    EVENT_OBJECT_SHOW,
+                    None,
+                    Some(win_event_proc),
+                    0,
+                    0,
+                    flags,
+                ),
+                SetWinEventHook(
+                    EVENT_OBJECT_NAMECHANGE,
+                    EVENT_OBJECT_NAMECHANGE,
+                    None,
+                    Some(win_event_proc),
+                    0,
+                    0,
+                    flags,
+                ),
+                // A window dragged to another monitor. Location changes fire on every step of a drag, moves done without the mouse are left to the rescan.
+                SetWinEventHook(
+                    EVENT_SYSTEM_MOVESIZEEND,
+                    EVENT_SYSTEM_MOVESIZEEND,
+                    None,
+                    Some(win_event_proc),
+                    0,
+                    0,
+                    flags,
+                ),
+                // Skipped for our own windows like the rest, the app that had the focus before stays focused to the rules
+                SetWinEventHook(
+                    EVENT_SYSTEM_FOREGROUND,
+                    EVENT_SYSTEM_FOREGROUND,
+                    None,
+                    Some(win_event_proc),
+                    0,
+                    0,
+                    flags,
+                ),
+            ];
+
+            let hooked = hooks.iter().all(|hook| !hook.is_invalid());
+            _ = hooked_tx.send(hooked);
+
+            if hooked {
+                let mut message = MSG::default();
+
+                // -1 is an error, 0 is WM_QUIT
+                while GetMessageW(&mut message, None, 0, 0).0 > 0 {
+                    DispatchMessageW(&message);
+                }
+            }
+
+            for hook in hooks.into_iter().filter(|hook| !hook.is_invalid()) {
+                _ = UnhookWinEvent(hook);
+            }
+        });
+
+        hooked_rx.recv().ok()?.then_some(rx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use windows::Win32::UI::WindowsAndMessaging::{WS_BORDER, WS_OVERLAPPEDWINDOW};
+
+    #[test]
+    fn styles_are_sorted_into_roles() {
+        let overlapped = WS_OVERLAPPEDWINDOW.0;
+        let popup = WS_POPUP.0 | WS_BORDER.0;
+
+        assert_eq!(classify_window(overlapped, 0, false), WindowRole::Main);
+        assert_eq!(classify_window(overlapped, 0, true), WindowRole::Dialog);
+        assert_eq!(
+            classify_window(overlapped, WS_EX_DLGMODALFRAME.0, false),
+            WindowRole::Dialog
+        );
+        assert_eq!(
+            classify_window(overlapped, WS_EX_TOOLWINDOW.0, false),
+            WindowRole::Tool
+        );
+        assert_eq!(classify_window(popup, 0, true), WindowRole::Popup);
+        assert_eq!(
+            classify_window(popup, WS_EX_TOOLWINDOW.0, false),
+            WindowRole::Popup
+        );
+    }
+
+    #[test]
+    fn borderless_apps_and_captioned_popups_are_not_menus() {
+        // An unowned captionless popup is how borderless apps draw their own frame
+        assert_eq!(classify_window(WS_POPUP.0, 0, false), WindowRole::Main);
+        // A popup with a caption is a window of its own, owned it is a dialog
+        let captioned = WS_POPUP.0 | WS_CAPTION.0;
+        assert_eq!(classify_window(captioned, 0, false), WindowRole::Main);
+        assert_eq!(classify_window(captioned, 0, true), WindowRole::Dialog);
+    }
+}
@@ -0,0 +1,854 @@
+use super::{
+    monitor_at, procfs, MonitorInfo, OriginalWindowState, WindowBackend, WindowEvent, WindowHandle,
+    WindowRole,
+};
+use anyhow::{anyhow, Result};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, MutexGuard},
+    thread,
+};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use x11rb::{
+    atom_manager,
+    connection::Connection,
+    protocol::{
+        randr::{ConnectionExt as _, NotifyMask},
+        xproto::{
+            AtomEnum, ChangeWindowAttributesAux, ConnectionExt, EventMask, KeyButMask, MapState,
+            PropMode, Window,
+        },
+        Event,
+    },
+    rust_connection::RustConnection,
+    wrapper::ConnectionExt as _,
+};
+
+atom_manager! {
+    Atoms: AtomsCookie {
+        _NET_ACTIVE_WINDOW,
+        _NET_CLIENT_LIST,
+        _NET_WM_NAME,
+        _NET_WM_PID,
+        _NET_WM_WINDOW_OPACITY,
+        _NET_WM_WINDOW_TYPE,
+        _NET_WM_WINDOW_TYPE_COMBO,
+        _NET_WM_WINDOW_TYPE_DIALOG,
+        _NET_WM_WINDOW_TYPE_DND,
+        _NET_WM_WINDOW_TYPE_DROPDOWN_MENU,
+        _NET_WM_WINDOW_TYPE_MENU,
+        _NET_WM_WINDOW_TYPE_NORMAL,
+        _NET_WM_WINDOW_TYPE_NOTIFICATION,
+        _NET_WM_WINDOW_TYPE_POPUP_MENU,
+        _NET_WM_WINDOW_TYPE_SPLASH,
+        _NET_WM_WINDOW_TYPE_TOOLBAR,
+        _NET_WM_WINDOW_TYPE_TOOLTIP,
+        _NET_WM_WINDOW_TYPE_UTILITY,
+        UTF8_STRING,
+    }
+}
+
+// _NET_WM_WINDOW_OPACITY is a CARDINAL where 0xFFFFFFFF is fully opaque.
+const OPAQUE: u64 = u32::MAX as u64;
+
+/*
+  Talks to an X server directly, transparency is set through _NET_WM_WINDOW_OPACITY so a compositor has to be running for it to show.
+  Windows are identified by WM_CLASS and their process by _NET_WM_PID.
+*/
+pub struct X11Backend {
+    display: Option<String>,
+    connection: RustConnection,
+    root: Window,
+    atoms: Atoms,
+    desktop: Arc<Mutex<DesktopCache>>,
+}
+
+/*
+  The monitors and the client list as the event thread last saw them, so rules and events do not ask the X server for them every time.
+  Both are None while no event thread is running to keep them current, the backend reads them itself then.
+*/
+#[derive(Default)]
+struct DesktopCache {
+    monitors: Option<Vec<MonitorInfo>>,
+    clients: Option<HashSet<Window>>,
+}
+
+impl X11Backend {
+    /*
+      Connects to the given display, or $DISPLAY when none is given (Xvfb works fine for this).
+    */
+    pub fn connect(display: Option<&str>) -> Result<Self> {
+        let (connection, screen) = RustConnection::connect(display)?;
+        let root = connection.setup().roots[screen].root;
+        let atoms = Atoms::new(&connection)?.reply()?;
+
+        Ok(Self {
+            display: display.map(str::to_owned),
+            connection,
+            root,
+            atoms,
+            desktop: Arc::new(Mutex::new(DesktopCache::default())),
+        })
+    }
+
+    fn lock_desktop(&self) -> MutexGuard<'_, DesktopCache> {
+        lock_desktop(&self.desktop)
+    }
+
+    fn to_window(window: WindowHandle) -> Window {
+        window as Window
+    }
+
+    fn get_property_32(&self, window: Window, property: u32, kind: AtomEnum) -> Option<Vec<u32>> {
+        let reply = self
+            .connection
+            .get_property(false, window, property, kind, 0, u32::MAX / 4)
+            .ok()?
+            .reply()
+            .ok()?;
+
+        let values = reply.value32()?.collect();
+
+        Some(values)
+    }
+
+    fn get_property_string(&self, window: Window, property: u32, kind: u32) -> Option<Vec<u8>> {
+        let reply = self
+            .connection
+            .get_property(false, window, property, kind, 0, u32::MAX / 4)
+            .ok()?
+            .reply()
+            .ok()?;
+
+        (reply.value_len > 0).then_some(reply.value)
+    }
+
+    fn query_children(&self, window: Window) -> Vec<Window> {
+        self.connection
+            .query_tree(window)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .map(|reply| reply.children)
+            .unwrap_or_default()
+    }
+
+    fn has_class(&self, window: Window) -> bool {
+        self.get_property_string(window, AtomEnum::WM_CLASS.into(), AtomEnum::STRING.into())
+            .is_some()
+    }
+}
+
+/*
+  WM_CLASS holds "instance\0class\0", the class half is what gets matched. Some clients only set the instance.
+*/
+fn parse_wm_class(value: &[u8]) -> Option<String> {
+    let mut parts = value
+        .split(|&byte| byte == 0)
+        .filter(|part| !part.is_empty());
+    let instance = parts.next()?;
+    let class = parts.next().unwrap_or(instance);
+
+    Some(String::from_utf8_lossy(class).into_owned())
+}
+
+fn opacity_to_alpha(opacity: u32) -> u8 {
+    ((opacity as u64 * 255 + OPAQUE / 2) / OPAQUE) as u8
+}
+
+fn alpha_to_opacity(alpha: u8) -> u32 {
+    (alpha as u64 * OPAQUE / 255) as u32
+}
+
+/*
+  Asks for property changes (class, title) and destruction of the window on the event connection.
+*/
+fn watch_window(connection: &RustConnection, window: Window) {
+    _ = connection.change_window_attributes(
+        window,
+        &ChangeWindowAttributesAux::new()
+            .event_mask(EventMask::PROPERTY_CHANGE | EventMask::STRUCTURE_NOTIFY),
+    );
+}
+
+fn lock_desktop(desktop: &Mutex<DesktopCache>) -> MutexGuard<'_, DesktopCache> {
+    desktop
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/*
+  The monitors from RandR, an X server without RandR 1.5 has none.
+*/
+fn read_monitors(connection: &RustConnection, root: Window) -> Vec<MonitorInfo> {
+    let Some(reply) = connection
+        .randr_get_monitors(root, true)
+        .ok()
+        .and_then(|cookie| cookie.reply().ok())
+    else {
+        return Vec::new();
+    };
+
+    reply
+        .monitors
+        .into_iter()
+        .enumerate()
+        .map(|(index, monitor)| {
+            let name = connection
+                .get_atom_name(monitor.name)
+                .ok()
+                .and_then(|cookie| cookie.reply().ok())
+                .map(|reply| String::from_utf8_lossy(&reply.name).into_owned())
+                .unwrap_or_default();
+            let (left, top) = (i32::from(monitor.x), i32::from(monitor.y));
+
+            MonitorInfo {
+                index,
+                name,
+                primary: monitor.primary,
+                rect: (
+                    left,
+                    top,
+                    left + i32::from(monitor.width),
+                    top + i32::from(monitor.height),
+                ),
+            }
+        })
+        .collect()
+}
+
+/*
+  The center of the window in root coordinates, windows are usually inside a window manager frame.
+*/
+fn read_window_center(
+    connection: &RustConnection,
+    root: Window,
+    window: Window,
+) -> Option<(i32, i32)> {
+    let geometry = connection.get_geometry(window).ok()?.reply().ok()?;
+    let position = connection
+        .translate_coordinates(window, root, 0, 0)
+        .ok()?
+        .reply()
+        .ok()?;
+
+    Some((
+        i32::from(position.dst_x) + i32::from(geometry.width) / 2,
+        i32::from(position.dst_y) + i32::from(geometry.height) / 2,
+    ))
+}
+
+/*
+  The index of the monitor the window is on in a layout read before, saves asking RandR again on every move.
+*/
+fn find_window_monitor(
+    connection: &RustConnection,
+    root: Window,
+    monitors: &[MonitorInfo],
+    window: Window,
+) -> Option<usize> {
+    monitor_at(
+        monitors.to_vec(),
+        read_window_center(connection, root, window)?,
+    )
+    .map(|monitor| monitor.index)
+}
+
+fn read_client_list(connection: &RustConnection, root: Window, atoms: &Atoms) -> HashSet<Window> {
+    connection
+        .get_property(
+            false,
+            root,
+            atoms._NET_CLIENT_LIST,
+            AtomEnum::WINDOW,
+            0,
+            u32::MAX / 4,
+        )
+        .ok()
+        .and_then(|cookie| cookie.reply().ok())
+        .and_then(|reply| reply.value32().map(|clients| clients.collect()))
+        .unwrap_or_default()
+}
+
+/*
+  The window the window manager says has the focus, None when it set 0 because nothing has.
+*/
+fn read_active_window(connection: &RustConnection, root: Window, atoms: &Atoms) -> Option<Window> {
+    connection
+        .get_property(
+            false,
+            root,
+            atoms._NET_ACTIVE_WINDOW,
+            AtomEnum::WINDOW,
+            0,
+            1,
+        )
+        .ok()?
+        .reply()
+        .ok()?
+        .value32()?
+        .next()
+        .filter(|&window| window != x11rb::NONE)
+}
+
+impl WindowBackend for X11Backend {
+    /*
+      Uses the window managers client list, falling back to the roots children when there is no window manager (Xvfb).
+    */
+    fn enumerate_windows(&self) -> Vec<WindowHandle> {
+        let windows = self
+            .get_property_32(self.root, self.atoms._NET_CLIENT_LIST, AtomEnum::WINDOW)
+            .filter(|clients| !clients.is_empty())
+            .unwrap_or_else(|| self.query_children(self.root));
+
+        windows
+            .into_iter()
+            .map(|window| window as WindowHandle)
+            .collect()
+    }
+
+    fn enumerate_child_windows(&self, parent: WindowHandle) -> Vec<WindowHandle> {
+        let mut children = Vec::new();
+        let mut pending = vec![Self::to_window(parent)];
+
+        while let Some(current) = pending.pop() {
+            for child in self.query_children(current) {
+                children.push(child as WindowHandle);
+                pending.push(child);
+            }
+        }
+
+        children
+    }
+
+    /*
+      Reparenting window managers put clients inside a frame, so the parent can not be used here.
+      Without a window manager the client list is empty and the roots children are asked for instead.
+    */
+    fn is_top_level(&self, window: WindowHandle) -> bool {
+        if let Some(clients) = self
+            .lock_desktop()
+            .clients
+            .as_ref()
+            .filter(|clients| !clients.is_empty())
+        {
+            return clients.contains(&Self::to_window(window));
+        }
+
+        self.enumerate_windows().contains(&window)
+    }
+
+    /*
+      Unmapped clients (minimized, other workspace) are still in the client list.
+    */
+    fn is_window_visible(&self, window: WindowHandle) -> bool {
+        self.connection
+            .get_window_attributes(Self::to_window(window))
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .is_some_and(|reply| reply.map_state == MapState::VIEWABLE)
+    }
+
+    fn get_parent(&self, window: WindowHandle) -> Option<WindowHandle> {
+        let reply = self
+            .connection
+            .query_tree(Self::to_window(window))
+            .ok()?
+            .reply()
+            .ok()?;
+
+        (reply.parent != self.root && reply.parent != x11rb::NONE)
+            .then_some(reply.parent as WindowHandle)
+    }
+
+    fn get_window_class(&self, window: WindowHandle) -> Option<String> {
+        parse_wm_class(&self.get_property_string(
+            Self::to_window(window),
+            AtomEnum::WM_CLASS.into(),
+            AtomEnum::STRING.into(),
+        )?)
+    }
+
+    fn get_window_title(&self, window: WindowHandle) -> Option<String> {
+        let window = Self::to_window(window);
+
+        self.get_property_string(window, self.atoms._NET_WM_NAME, self.atoms.UTF8_STRING)
+            .or_else(|| {
+                self.get_property_string(window, AtomEnum::WM_NAME.into(), AtomEnum::STRING.into())
+            })
+            .map(|title| String::from_utf8_lossy(&title).into_owned())
+    }
+
+    /*
+      The first _NET_WM_WINDOW_TYPE we know wins, they are listed most preferred first.
+      A window without a type is a dialog when it is transient for another one, as EWMH says.
+    */
+    fn get_window_role(&self, window: WindowHandle) -> WindowRole {
+        let window = Self::to_window(window);
+        let atoms = &self.atoms;
+        let types = [
+            (atoms._NET_WM_WINDOW_TYPE_COMBO, "combo"),
+            (atoms._NET_WM_WINDOW_TYPE_DIALOG, "dialog"),
+            (atoms._NET_WM_WINDOW_TYPE_DND, "dnd"),
+            (atoms._NET_WM_WINDOW_TYPE_DROPDOWN_MENU, "dropdown_menu"),
+            (atoms._NET_WM_WINDOW_TYPE_MENU, "menu"),
+            (atoms._NET_WM_WINDOW_TYPE_NORMAL, "normal"),
+            (atoms._NET_WM_WINDOW_TYPE_NOTIFICATION, "notification"),
+            (atoms._NET_WM_WINDOW_TYPE_POPUP_MENU, "popup_menu"),
+            (atoms._NET_WM_WINDOW_TYPE_SPLASH, "splash"),
+            (atoms._NET_WM_WINDOW_TYPE_TOOLBAR, "toolbar"),
+            (atoms._NET_WM_WINDOW_TYPE_TOOLTIP, "tooltip"),
+            (atoms._NET_WM_WINDOW_TYPE_UTILITY, "utility"),
+        ];
+
+        let window_type = self
+            .get_property_32(window, atoms._NET_WM_WINDOW_TYPE, AtomEnum::ATOM)
+            .unwrap_or_default()
+            .into_iter()
+            .find_map(|atom| {
+                types
+                    .iter()
+                    .find(|(known, _)| *known == atom)
+                    .map(|&(_, name)| name)
+            });
+
+        match window_type {
+            Some(window_type) => WindowRole::from_window_type(window_type),
+            None if self
+                .get_property_32(window, AtomEnum::WM_TRANSIENT_FOR.into(), AtomEnum::WINDOW)
+                .is_some_and(|owners| owners.iter().any(|&owner| owner != 0)) =>
+            {
+                WindowRole::Dialog
+            }
+            None => WindowRole::Main,
+        }
+    }
+
+    fn get_process_id(&self, window: WindowHandle) -> Option<u32> {
+        self.get_property_32(
+            Self::to_window(window),
+            self.atoms._NET_WM_PID,
+            AtomEnum::CARDINAL,
+        )?
+        .first()
+        .copied()
+    }
+
+    fn get_process_name(&self, process_id: u32) -> Option<String> {
+        procfs::get_process_name(process_id)
+    }
+
+    fn get_process_path(&self, process_id: u32) -> Option<String> {
+        procfs::get_process_path(process_id)
+    }
+
+    fn get_process_start_time(&self, process_id: u32) -> Option<u64> {
+        procfs::get_process_start_time(process_id)
+    }
+
+    fn get_window_alpha(&self, window: WindowHandle) -> Option<u8> {
+        let opacity = *self
+            .get_property_32(
+                Self::to_window(window),
+                self.atoms._NET_WM_WINDOW_OPACITY,
+                AtomEnum::CARDINAL,
+            )?
+            .first()?;
+
+        Some(opacity_to_alpha(opacity))
+    }
+
+    /*
+      Fully opaque removes the property so the compositor goes back to its own default.
+    */
+    fn set_window_alpha(&self, window: WindowHandle, transparency: u8) -> Result<()> {
+        let window = Self::to_window(window);
+
+        if transparency == u8::MAX {
+            self.connection
+                .delete_property(window, self.atoms._NET_WM_WINDOW_OPACITY)?;
+        } else {
+            let opacity = alpha_to_opacity(transparency);
+            self.connection.change_property32(
+                PropMode::REPLACE,
+                window,
+                self.atoms._NET_WM_WINDOW_OPACITY,
+                AtomEnum::CARDINAL,
+                &[opacity],
+            )?;
+        }
+
+        self.connection
+            .flush()
+            .map_err(|err| anyhow!("Failed to set window opacity {}", err))
+    }
+
+    fn capture_window_state(&self, window: WindowHandle) -> Option<OriginalWindowState> {
+        let opacity = self
+            .get_property_32(
+                Self::to_window(window),
+                self.atoms._NET_WM_WINDOW_OPACITY,
+                AtomEnum::CARDINAL,
+            )
+            .and_then(|values| values.first().copied());
+
+        Some(OriginalWindowState::X11 { opacity })
+    }
+
+    fn restore_window_state(
+        &self,
+        window: WindowHandle,
+        state: &OriginalWindowState,
+    ) -> Result<()> {
+        let OriginalWindowState::X11 { opacity } = *state else {
+            return Err(anyhow!("Window state {:?} was not captured by x11", state));
+        };
+        let window = Self::to_window(window);
+
+        match opacity {
+            Some(opacity) => {
+                self.connection.change_property32(
+                    PropMode::REPLACE,
+                    window,
+                    self.atoms._NET_WM_WINDOW_OPACITY,
+                    AtomEnum::CARDINAL,
+                    &[opacity],
+                )?;
+            }
+            None => {
+                self.connection
+                    .delete_property(window, self.atoms._NET_WM_WINDOW_OPACITY)?;
+            }
+        }
+
+        self.connection
+            .flush()
+            .map_err(|err| anyhow!("Failed to restore window opacity {}", err))
+    }
+
+    fn is_elevated(&self, process_id: u32) -> bool {
+        procfs::is_elevated(process_id)
+    }
+
+    fn get_monitors(&self) -> Vec<MonitorInfo> {
+        if let Some(monitors) = &self.lock_desktop().monitors {
+            return monitors.clone();
+        }
+
+        read_monitors(&self.connection, self.root)
+    }
+
+    fn get_window_monitor(&self, window: WindowHandle) -> Option<MonitorInfo> {
+        monitor_at(
+            self.get_monitors(),
+            read_window_center(&self.connection, self.root, Self::to_window(window))?,
+        )
+    }
+
+    fn get_foreground_window(&self) -> Option<WindowHandle> {
+        read_active_window(&self.connection, self.root, &self.atoms)
+            .map(|window| window as WindowHandle)
+    }
+
+    /*
+      Walks down from the root to the deepest window under the point, returning the first one that has a WM_CLASS.
+      With a reparenting window manager that skips the frame and lands on the client.
+    */
+    fn window_from_point(&self, x: i32, y: i32) -> Option<WindowHandle> {
+        let mut current = self.root;
+        let mut found = None;
+
+        loop {
+            let reply = self
+                .connection
+                .translate_coordinates(self.root, current, x as i16, y as i16)
+                .ok()?
+                .reply()
+                .ok()?;
+
+            if reply.child == x11rb::NONE {
+                break;
+            }
+
+            current = reply.child;
+
+            if self.has_class(current) {
+                found = Some(current as WindowHandle);
+                break;
+            }
+        }
+
+        found
+    }
+
+    fn get_cursor_position(&self) -> Option<(i32, i32)> {
+        let reply = self
+            .connection
+            .query_pointer(self.root)
+            .ok()?
+            .reply()
+            .ok()?;
+
+        Some((reply.root_x.into(), reply.root_y.into()))
+    }
+
+    fn is_primary_button_down(&self) -> bool {
+        self.connection
+            .query_pointer(self.root)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .is_some_and(|reply| reply.mask.contains(KeyButMask::BUTTON1))
+    }
+
+    /*
+      Opens a second connection that listens for CreateNotify/DestroyNotify on the root and PropertyNotify and ConfigureNotify on every client.
+      New clients also show up as a change of the window managers client list, which catches windows that set their class before we could watch them.
+      A RandR screen change reads the monitors again and reports the clients that ended up on another one.
+      The monitors and the client list are shared with the backend while the thread runs.
+    */
+    fn subscribe_events(&self) -> Option<UnboundedReceiver<WindowEvent>> {
+        let (connection, _) = RustConnection::connect(self.display.as_deref()).ok()?;
+        let root = self.root;
+        let atoms = self.atoms;
+        let desktop = Arc::clone(&self.desktop);
+
+        connection
+            .change_window_attributes(
+                root,
+                &ChangeWindowAttributesAux::new()
+                    .event_mask(EventMask::SUBSTRUCTURE_NOTIFY | EventMask::PROPERTY_CHANGE),
+            )
+            .ok()?
+            .check()
+            .ok()?;
+        // Without RandR there are no monitors to keep up with
+        if let Ok(cookie) = connection.randr_select_input(root, NotifyMask::SCREEN_CHANGE) {
+            _ = cookie.check();
+        }
+
+        let mut clients = read_client_list(&connection, root, &atoms);
+        for window in self.enumerate_windows() {
+            watch_window(&connection, window as Window);
+        }
+        connection.flush().ok()?;
+
+        let watched_properties = [
+            AtomEnum::WM_CLASS.into(),
+            AtomEnum::WM_NAME.into(),
+            atoms._NET_WM_NAME,
+        ];
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        thread::spawn(move || {
+            // Where each client was last seen, moves only matter when they cross onto another monitor
+            let mut client_monitors: HashMap<Window, Option<usize>> = HashMap::new();
+            // Read again only when RandR says the screen changed, windows are moved far more often than displays
+            let mut monitors = read_monitors(&connection, root);
+            {
+                let mut cache = lock_desktop(&desktop);
+                cache.monitors = Some(monitors.clone());
+                cache.clients = Some(clients.clone());
+            }
+
+            while let Ok(event) = connection.wait_for_event() {
+                let mut window_events = Vec::new();
+
+                match event {
+                    Event::CreateNotify(created) if !created.override_redirect => {
+                        watch_window(&connection, created.window);
+                        window_events.push(WindowEvent::Created(created.window as WindowHandle));
+                    }
+                    Event::ConfigureNotify(configured) if clients.contains(&configured.window) => {
+                        let monitor =
+                            find_window_monitor(&connection, root, &monitors, configured.window);
+
+                        if client_monitors
+                            .insert(configured.window, monitor)
+                            .is_some_and(|last| last != monitor)
+                        {
+                            window_events
+                                .push(WindowEvent::Changed(configured.window as WindowHandle));
+                        }
+                    }
+                    Event::RandrScreenChangeNotify(_) => {
+                        monitors = read_monitors(&connection, root);
+                        lock_desktop(&desktop).monitors = Some(monitors.clone());
+
+                        // Windows that stayed put can still be on another monitor now
+                        for (&window, last) in client_monitors.iter_mut() {
+                            let monitor = find_window_monitor(&connection, root, &monitors, window);
+
+                            if *last != monitor {
+                                *last = monitor;
+                                window_events.push(WindowEvent::Changed(window as WindowHandle));
+                            }
+                        }
+                    }
+                    Event::DestroyNotify(destroyed) => {
+                        if clients.remove(&destroyed.window) {
+                            lock_desktop(&desktop).clients = Some(clients.clone());
+                        }
+                        client_monitors.remove(&destroyed.window);
+                        window_events
+                            .push(WindowEvent::Destroyed(destroyed.window as WindowHandle));
+                    }
+                    Event::MapNotify(mapped) if !mapped.override_redirect => {
+                        window_events.push(WindowEvent::Changed(mapped.window as WindowHandle));
+                    }
+                    Event::PropertyNotify(property)
+                        if property.window == root && property.atom == atoms._NET_CLIENT_LIST =>
+                    {
+                        let current = read_client_list(&connection, root, &atoms);
+
+                        for &window in current.difference(&clients) {
+                            watch_window(&connection, window);
+                            window_events.push(WindowEvent::Changed(window as WindowHandle));
+                        }
+                        clients = current;
+                        lock_desktop(&desktop).clients = Some(clients.clone());
+                    }
+                    Event::PropertyNotify(property)
+                        if property.window == root && property.atom == atoms._NET_ACTIVE_WINDOW =>
+                    {
+                        window_events.push(WindowEvent::Foreground(
+                            read_active_window(&connection, root, &atoms)
+                                .map(|window| window as WindowHandle),
+                        ));
+                    }
+                    Event::PropertyNotify(property)
+                        if watched_properties.contains(&property.atom) =>
+                    {
+                        window_events.push(WindowEvent::Changed(property.window as WindowHandle));
+                    }
+                    _ => {}
+                }
+
+                if connection.flush().is_err()
+                    || window_events
+                        .into_iter()
+                        .any(|window_event| tx.send(window_event).is_err())
+                {
+                    break;
+                }
+            }
+
+            // Nothing keeps them current anymore
+            *lock_desktop(&desktop) = DesktopCache::default();
+        });
+
+        Some(rx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use x11rb::{
+        protocol::xproto::{CreateWindowAux, WindowClass},
+        COPY_DEPTH_FROM_PARENT,
+    };
+
+    #[test]
+    fn wm_class_is_the_class_half() {
+        assert_eq!(parse_wm_class(b"xterm\0XTerm\0").as_deref(), Some("XTerm"));
+        assert_eq!(
+            parse_wm_class(b"navigator\0firefox").as_deref(),
+            Some("firefox")
+        );
+    }
+
+    #[test]
+    fn wm_class_falls_back_to_the_instance() {
+        assert_eq!(parse_wm_class(b"xclock\0").as_deref(), Some("xclock"));
+        assert_eq!(parse_wm_class(b"\0\0"), None);
+        assert_eq!(parse_wm_class(b""), None);
+    }
+
+    #[test]
+    fn opacity_round_trips_every_alpha() {
+        for alpha in 0..=u8::MAX {
+            assert_eq!(opacity_to_alpha(alpha_to_opacity(alpha)), alpha);
+        }
+
+        assert_eq!(alpha_to_opacity(u8::MAX), u32::MAX);
+        assert_eq!(alpha_to_opacity(0), 0);
+    }
+
+    #[test]
+    fn opacity_from_other_clients_is_rounded() {
+        assert_eq!(opacity_to_alpha(u32::MAX / 4 * 3), 191);
+        assert_eq!(opacity_to_alpha(1), 0);
+    }
+
+    #[test]
+    #[ignore = "needs an X server, run with --ignored under xvfb-run"]
+    fn reads_and_sets_the_properties_of_a_real_window() {
+        let backend = X11Backend::connect(None).expect("No X server to connect to");
+        let connection = &backend.connection;
+
+        let window = connection.generate_id().expect("Out of window ids");
+        connection
+            .create_window(
+                COPY_DEPTH_FROM_PARENT,
+                window,
+                backend.root,
+                0,
+                0,
+                100,
+                100,
+                0,
+                WindowClass::INPUT_OUTPUT,
+                0,
+                &CreateWindowAux::new(),
+            )
+            .expect("Failed to create the window");
+        connection
+            .change_property8(
+                PropMode::REPLACE,
+                window,
+                AtomEnum::WM_CLASS,
+                AtomEnum::STRING,
+                b"test\0WinAlphaTest\0",
+            )
+            .expect("Failed to set WM_CLASS");
+        connection
+            .change_property32(
+                PropMode::REPLACE,
+                window,
+                backend.atoms._NET_WM_PID,
+                AtomEnum::CARDINAL,
+                &[std::process::id()],
+            )
+            .expect("Failed to set _NET_WM_PID");
+        connection
+            .map_window(window)
+            .expect("Failed to map the window");
+        // A round trip, everything above has been handled once it comes back
+        connection
+            .get_input_focus()
+            .expect("Failed to send")
+            .reply()
+            .expect("No reply from the X server");
+
+        let handle = window as WindowHandle;
+        assert!(backend.enumerate_windows().contains(&handle));
+        assert_eq!(
+            backend.get_window_class(handle).as_deref(),
+            Some("WinAlphaTest")
+        );
+        assert_eq!(backend.get_process_id(handle), Some(std::process::id()));
+
+        let original = backend.capture_window_state(handle).expect("No state");
+        backend
+            .set_window_alpha(handle, 128)
+            .expect("Failed to set the alpha");
+        assert_eq!(backend.get_window_alpha(handle), Some(128));
+
+        backend
+            .restore_window_state(handle, &original)
+            .expect("Failed to restore");
+        assert_eq!(backend.get_window_alpha(handle), None);
+
+        _ = connection.destroy_window(window);
+        _ = connection.flush();
+    }
+}
@@ -0,0 +1,638 @@
+use crate::{
+    backend::{WindowBackend, WindowHandle},
+    window_config::WindowConfig,
+};
+use anyhow::{anyhow, Result};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize, Serializer};
+use std::{collections::HashMap, fs::create_dir_all, path::PathBuf};
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Config {
+    // Rules that do not outlive the session are left out when saving
+    #[serde(serialize_with = "serialize_persistent")]
+    windows: HashMap<String, WindowConfig>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    profiles: HashMap<String, Profile>,
+    // None runs the base rules alone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    active_profile: Option<String>,
+}
+
+/*
+  A named set of rules on top of the base ones. A rule with the same key as a base rule replaces it while the profile is active.
+*/
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Profile {
+    #[serde(default, serialize_with = "serialize_persistent")]
+    windows: HashMap<String, WindowConfig>,
+    // Where base rules were moved to while the profile is active, by key. Saves copying a whole rule to change its order.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    priorities: HashMap<String, i32>,
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self {
+            windows: HashMap::new(),
+            profiles: HashMap::new(),
+            active_profile: None,
+        }
+    }
+
+    /*
+      The base rules, the ones every profile starts from.
+    */
+    pub fn get_windows(&mut self) -> &mut HashMap<String, WindowConfig> {
+        &mut self.windows
+    }
+
+    pub fn get_windows_non_mut(&self) -> &HashMap<String, WindowConfig> {
+        &self.windows
+    }
+
+    /*
+      The names of the profiles, sorted.
+    */
+    pub fn get_profile_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.profiles.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    pub fn get_active_profile(&self) -> Option<&str> {
+        self.active_profile.as_deref()
+    }
+
+    /*
+      Switches to the profile, None goes back to the base rules.
+    */
+    pub fn set_active_profile(&mut self, profile: Option<String>) -> Result<()> {
+        if let Some(name) = profile
+            .as_ref()
+            .filter(|name| !self.profiles.contains_key(*name))
+        {
+            return Err(anyhow!("No profile named {}", name));
+        }
+
+        self.active_profile = profile;
+        Ok(())
+    }
+
+    /*
+      Adds a profile with no rules of its own, an existing one is left as it is.
+    */
+    pub fn add_profile(&mut self, name: String) -> Result<()> {
+        if name.trim().is_empty() || name.eq_ignore_ascii_case("base") {
+            return Err(anyhow!("{:?} can not be used as a profile name", name));
+        }
+
+        self.profiles.entry(name).or_default();
+        Ok(())
+    }
+
+    /*
+      Removes the profile and its rules. Removing the active one goes back to the base rules.
+    */
+    pub fn remove_profile(&mut self, name: &str) -> Result<()> {
+        self.profiles
+            .remove(name)
+            .ok_or_else(|| anyhow!("No profile named {}", name))?;
+
+        if self.active_profile.as_deref() == Some(name) {
+            self.active_profile = None;
+        }
+
+        Ok(())
+    }
+
+    /*
+      The rules in effect: the base rules with the active profile on top.
+    */
+    pub fn get_active_rules(&self) -> Vec<&WindowConfig> {
+        let Some(profile) = self.get_profile() else {
+            return self.windows.values().collect();
+        };
+
+        self.windows
+            .iter()
+            .filter(|(key, _)| !profile.windows.contains_key(*key))
+            .map(|(_, window_config)| window_config)
+            .chain(profile.windows.values())
+            .collect()
+    }
+
+    /*
+      Adds or replaces a rule. While a profile is active the change goes to the profile, the base rules are left alone.
+    */
+    pub fn insert_rule(&mut self, window_config: WindowConfig) {
+        let key = window_config.get_key();
+
+        // The rule carries its own priority now
+        if let Some(profile) = self.get_profile_mut() {
+            profile.priorities.remove(&key);
+        }
+
+        self.get_active_windows().insert(key, window_config);
+    }
+
+    /*
+      Puts the rule in place of the one saved under old_key, for edits that change the key (a match mode or the title).
+      While a profile is active only a rule of the profile is replaced, a base rule stays in the base.
+    */
+    pub fn replace_rule(&mut self, old_key: &str, window_config: WindowConfig) {
+        if let Some(profile) = self.get_profile_mut() {
+            profile.priorities.remove(old_key);
+        }
+        self.get_active_windows().remove(old_key);
+
+        self.insert_rule(window_config);
+    }
+
+    /*
+      The priority the rule has in effect, a profile can move base rules without copying them.
+    */
+    pub fn get_priority(&self, window_config: &WindowConfig) -> i32 {
+        self.get_profile()
+            .filter(|profile| !profile.windows.contains_key(&window_config.get_key()))
+            .and_then(|profile| profile.priorities.get(&window_config.get_key()))
+            .copied()
+            .unwrap_or_else(|| window_config.get_priority())
+    }
+
+    /*
+      Gives the rules in effect priorities in the order of the keys, the first key wins over all the others.
+      While a profile is active only its own rules are changed, the base rules it did not replace get their priority stored in the profile.
+    */
+    pub fn set_rule_order(&mut self, keys: &[String]) {
+        let count = keys.len() as i32;
+        let base_keys: Vec<String> = self.windows.keys().cloned().collect();
+
+        for (position, key) in keys.iter().enumerate() {
+            let priority = count - position as i32;
+
+            match self.get_profile_mut() {
+                Some(profile) => {
+                    if let Some(window_config) = profile.windows.get_mut(key) {
+                        window_config.set_priority(priority);
+                    } else if base_keys.contains(key) {
+                        profile.priorities.insert(key.clone(), priority);
+                    }
+                }
+                None => {
+                    if let Some(window_config) = self.windows.get_mut(key) {
+                        window_config.set_priority(priority);
+                    }
+                }
+            }
+        }
+    }
+
+    /*
+      Rewrites rules saved by older versions, re-keying them since the key comes from the class.
+      Returns if anything changed and the config should be saved.
+    */
+    pub fn migrate(&mut self) -> bool {
+        let renamed = migrate_windows(&mut self.windows);
+        let mut changed = !renamed.is_empty();
+
+        for profile in self.profiles.values_mut() {
+            changed |= !migrate_windows(&mut profile.windows).is_empty();
+
+            // Base rules the profile moved keep their place under the new key
+            for (old_key, new_key) in &renamed {
+                if let Some(priority) = profile.priorities.remove(old_key) {
+                    profile.priorities.insert(new_key.clone(), priority);
+                }
+            }
+        }
+
+        changed
+    }
+
+    /*
+      The rules from the one that wins a window first to the one that loses, see WindowConfig::cmp_precedence.
+    */
+    pub fn get_windows_in_order(&self) -> Vec<&WindowConfig> {
+        let mut windows = self.get_active_rules();
+        windows
+            .sort_by(|a, b| a.cmp_precedence_with(self.get_priority(a), b, self.get_priority(b)));
+        windows
+    }
+
+    /*
+      Returns if any rule, in the base or a profile, has outlived its lifetime.
+    */
+    pub fn has_expired(&self, backend: &dyn WindowBackend) -> bool {
+        self.all_windows()
+            .any(|window_config| window_config.get_lifetime().is_expired(backend))
+    }
+
+    /*
+      Removes every rule whose lifetime is over, returns their keys.
+    */
+    pub fn remove_expired(&mut self, backend: &dyn WindowBackend) -> Vec<String> {
+        let mut removed = Vec::new();
+        let windows = std::iter::once(&mut self.windows).chain(
+            self.profiles
+                .values_mut()
+                .map(|profile| &mut profile.windows),
+        );
+
+        for windows in windows {
+            windows.retain(|key, window_config| {
+                let expired = window_config.get_lifetime().is_expired(backend);
+                if expired {
+                    removed.push(key.clone());
+                }
+                !expired
+            });
+        }
+
+        for profile in self.profiles.values_mut() {
+            profile
+                .priorities
+                .retain(|key, _| self.windows.contains_key(key));
+        }
+
+        removed
+    }
+
+    fn get_profile(&self) -> Option<&Profile> {
+        self.active_profile
+            .as_ref()
+            .and_then(|name| self.profiles.get(name))
+    }
+
+    fn get_profile_mut(&mut self) -> Option<&mut Profile> {
+        self.active_profile
+            .as_ref()
+            .and_then(|name| self.profiles.get_mut(name))
+    }
+
+    fn get_active_windows(&mut self) -> &mut HashMap<String, WindowConfig> {
+        match self
+            .active_profile
+            .as_ref()
+            .and_then(|name| self.profiles.get_mut(name))
+        {
+            Some(profile) => &mut profile.windows,
+            None => &mut self.windows,
+        }
+    }
+
+    fn all_windows(&self) -> impl Iterator<Item = &WindowConfig> {
+        self.windows.values().chain(
+            self.profiles
+                .values()
+                .flat_map(|profile| profile.windows.values()),
+        )
+    }
+
+    /*
+      Returns the exclusion that protects the window, if any. The window can be below a top level one (what the picker points at).
+    */
+    pub fn get_exclusion(
+        &self,
+        backend: &dyn WindowBackend,
+        window: WindowHandle,
+    ) -> Option<&WindowConfig> {
+        let root = backend.get_top_level(window);
+
+        self.get_active_rules()
+            .into_iter()
+            .filter(|window_config| window_config.is_active_exclusion())
+            .find(|window_config| {
+                let targets = window_config.get_targets(backend, root);
+                targets.contains(&root) || targets.contains(&window)
+            })
+    }
+}
+
+/*
+  Migrates the rules and re-keys them, returns the old and new key of every rule that changed.
+  Rules saved under a key older versions made (before match modes were part of it) are re-keyed too.
+*/
+fn migrate_windows(windows: &mut HashMap<String, WindowConfig>) -> Vec<(String, String)> {
+    let mut renamed = Vec::new();
+
+    for (key, window_config) in windows.iter_mut() {
+        if window_config.migrate_forced() || *key != window_config.get_key() {
+            renamed.push((key.clone(), window_config.get_key()));
+        }
+    }
+
+    if !renamed.is_empty() {
+        *windows = windows
+            .drain()
+            .map(|(_, window_config)| (window_config.get_key(), window_config))
+            .collect();
+    }
+
+    renamed
+}
+
+fn serialize_persistent<S: Serializer>(
+    windows: &HashMap<String, WindowConfig>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(
+        windows
+            .iter()
+            .filter(|(_, window_config)| window_config.get_lifetime().is_persistent()),
+    )
+}
+
+/*
+  Returns the directory WinAlpha keeps its files in, creating it when missing.
+*/
+pub fn get_config_dir() -> PathBuf {
+    let project_dirs = ProjectDirs::from("com", "windowtransparency", "winalpha")
+        .expect("Failed to get project config directories.");
+
+    let config_dir = project_dirs.config_dir();
+
+    create_dir_all(config_dir).ok();
+
+    config_dir.to_path_buf()
+}
+
+pub fn get_config_path() -> PathBuf {
+    get_config_dir().join("config.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backend::memory::MemoryBackend, lifetime::RuleLifetime, window_config::WindowInfo,
+    };
+    use core::time::Duration;
+
+    fn rule(process_name: &str, lifetime: RuleLifetime) -> WindowConfig {
+        let mut window_config = WindowConfig::new(
+            &WindowInfo {
+                process_name: process_name.to_owned(),
+                class_name: "Main".to_owned(),
+                ..Default::default()
+            },
+            128,
+        );
+        window_config.set_lifetime(lifetime);
+        window_config
+    }
+
+    // Base rules and a "work" profile with one rule of each lifetime
+    fn config_with_lifetimes(backend: &MemoryBackend) -> Config {
+        backend.add_process(1, "app", false);
+        let lifetimes = [
+            ("permanent", RuleLifetime::Permanent),
+            ("session", RuleLifetime::Session),
+            ("until", RuleLifetime::lasting(Duration::from_secs(3600))),
+            ("expired", RuleLifetime::Until(0)),
+            (
+                "process",
+                RuleLifetime::until_exit(backend, 1).expect("The process is running"),
+            ),
+        ];
+
+        let mut config = Config::new();
+        for (name, lifetime) in lifetimes {
+            config.insert_rule(rule(name, lifetime));
+        }
+        config.add_profile("work".to_owned()).unwrap();
+        config.set_active_profile(Some("work".to_owned())).unwrap();
+        for (name, lifetime) in lifetimes {
+            config.insert_rule(rule(&format!("work-{}", name), lifetime));
+        }
+        config.set_active_profile(None).unwrap();
+        config
+    }
+
+    fn sorted_names<'a>(windows: impl Iterator<Item = &'a WindowConfig>) -> Vec<String> {
+        let mut names: Vec<String> = windows.map(WindowConfig::get_name).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn session_and_process_rules_are_not_saved() {
+        let backend = MemoryBackend::new();
+        let config = config_with_lifetimes(&backend);
+
+        let saved: Config = serde_json::from_str(&serde_json::to_string(&config).unwrap()).unwrap();
+
+        assert_eq!(
+            sorted_names(saved.get_windows_non_mut().values()),
+            vec!["expired", "permanent", "until"]
+        );
+        assert_eq!(
+            sorted_names(saved.profiles["work"].windows.values()),
+            vec!["work-expired", "work-permanent", "work-until"]
+        );
+    }
+
+    #[test]
+    fn expired_rules_are_removed_from_the_base_and_profiles() {
+        let backend = MemoryBackend::new();
+        let mut config = config_with_lifetimes(&backend);
+
+        assert!(config.has_expired(&backend));
+        let mut removed = config.remove_expired(&backend);
+        removed.sort();
+        assert_eq!(removed, vec!["expired|Main", "work-expired|Main"]);
+        assert!(!config.has_expired(&backend));
+
+        backend.remove_process(1);
+        assert!(config.has_expired(&backend));
+        let mut removed = config.remove_expired(&backend);
+        removed.sort();
+        assert_eq!(removed, vec!["process|Main", "work-process|Main"]);
+
+        assert_eq!(
+            sorted_names(config.all_windows()),
+            vec![
+                "permanent",
+                "session",
+                "until",
+                "work-permanent",
+                "work-session",
+                "work-until"
+            ]
+        );
+    }
+
+    #[test]
+    fn migrate_rekeys_forced_rules_in_the_base_and_profiles() {
+        let mut config: Config = serde_json::from_str(
+            r#"{
+                "windows": {
+                    "ApplicationFrameHost|ApplicationFrameWindow": {
+                        "process_name": "ApplicationFrameHost", "window_class": "ApplicationFrameWindow",
+                        "transparency": 128, "enabled": true,
+                        "force": true, "old_class": "Windows.UI.Core.CoreWindow"
+                    },
+                    "notepad|Notepad": {
+                        "process_name": "notepad", "window_class": "Notepad", "transparency": 200, "enabled": true
+                    }
+                },
+                "profiles": {
+                    "work": { "windows": {
+                        "slack|Chrome_WidgetWin_1": {
+                            "process_name": "slack", "window_class": "Chrome_WidgetWin_1",
+                            "transparency": 100, "enabled": true,
+                            "force": false, "old_class": "Chrome_RenderWidgetHostHWND"
+                        }
+                    } }
+                }
+            }"#,
+        )
+        .expect("Configs from before class chains still parse");
+
+        assert!(config.migrate());
+
+        let mut keys: Vec<&String> = config.get_windows_non_mut().keys().collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            vec![
+                "ApplicationFrameHost|ApplicationFrameWindow > Windows.UI.Core.CoreWindow",
+                "notepad|Notepad",
+            ]
+        );
+
+        config.set_active_profile(Some("work".to_owned())).unwrap();
+        assert!(config
+            .get_active_rules()
+            .iter()
+            .any(|rule| rule.get_key() == "slack|Chrome_WidgetWin_1"));
+
+        // Nothing left to migrate, the config is not saved again on the next launch
+        let saved = serde_json::to_string(&config).unwrap();
+        let mut reloaded: Config = serde_json::from_str(&saved).unwrap();
+        assert!(!reloaded.migrate());
+    }
+
+    fn keys_in_order(config: &Config) -> Vec<String> {
+        config
+            .get_windows_in_order()
+            .into_iter()
+            .map(WindowConfig::get_key)
+            .collect()
+    }
+
+    #[test]
+    fn reordering_in_a_profile_does_not_copy_base_rules() {
+        let mut config = Config::new();
+        config.insert_rule(rule("first", RuleLifetime::Permanent));
+        config.insert_rule(rule("second", RuleLifetime::Permanent));
+        config.add_profile("work".to_owned()).unwrap();
+        config.set_active_profile(Some("work".to_owned())).unwrap();
+        config.insert_rule(rule("work", RuleLifetime::Permanent));
+
+        let order = ["second|Main", "work|Main", "first|Main"].map(str::to_owned);
+        config.set_rule_order(&order);
+
+        assert_eq!(keys_in_order(&config), order);
+        assert_eq!(
+            sorted_names(config.profiles["work"].windows.values()),
+            vec!["work"]
+        );
+        assert!(config
+            .get_windows_non_mut()
+            .values()
+            .all(|window_config| window_config.get_priority() == 0));
+
+        // The base keeps its own order, and the moves survive a save
+        config.set_active_profile(None).unwrap();
+        assert_eq!(keys_in_order(&config), vec!["first|Main", "second|Main"]);
+
+        let mut saved: Config =
+            serde_json::from_str(&serde_json::to_string(&config).unwrap()).unwrap();
+        saved.set_active_profile(Some("work".to_owned())).unwrap();
+        assert_eq!(keys_in_order(&saved), order);
+    }
+
+    #[test]
+    fn editing_a_moved_base_rule_keeps_its_place() {
+        let mut config = Config::new();
+        config.insert_rule(rule("first", RuleLifetime::Permanent));
+        config.insert_rule(rule("second", RuleLifetime::Permanent));
+        config.add_profile("work".to_owned()).unwrap();
+        config.set_active_profile(Some("work".to_owned())).unwrap();
+        config.set_rule_order(&["second|Main", "first|Main"].map(str::to_owned));
+
+        // What the rules window sends back, with the priority it showed
+        let mut edited = rule("second", RuleLifetime::Permanent);
+        edited.set_priority(2);
+        edited.set_enabled(false);
+        config.insert_rule(edited);
+
+        assert!(!config.profiles["work"]
+            .priorities
+            .contains_key("second|Main"));
+        assert_eq!(keys_in_order(&config), vec!["second|Main", "first|Main"]);
+    }
+
+    #[test]
+    fn profiles_are_added_and_removed() {
+        let mut config = Config::new();
+        config.add_profile("work".to_owned()).unwrap();
+        config.add_profile("games".to_owned()).unwrap();
+        assert!(config.add_profile("Base".to_owned()).is_err());
+        assert!(config.add_profile(" ".to_owned()).is_err());
+        assert_eq!(config.get_profile_names(), vec!["games", "work"]);
+
+        config.set_active_profile(Some("work".to_owned())).unwrap();
+        config.remove_profile("games").unwrap();
+        assert_eq!(config.get_active_profile(), Some("work"));
+
+        // Removing the active one goes back to the base rules
+        config.remove_profile("work").unwrap();
+        assert_eq!(config.get_active_profile(), None);
+        assert!(config.get_profile_names().is_empty());
+        assert!(config.remove_profile("work").is_err());
+    }
+
+    #[test]
+    fn rules_saved_under_an_older_key_are_rekeyed() {
+        let mut config: Config = serde_json::from_str(
+            r#"{
+                "windows": {
+                    "chrome|Chrome_*": {
+                        "process_name": "chrome", "window_class": "Chrome_*", "class_match": "glob",
+                        "transparency": 128, "enabled": true
+                    },
+                    "chrome|Chrome_WidgetWin_1": {
+                        "process_name": "chrome", "window_class": "Chrome_WidgetWin_1",
+                        "transparency": 200, "enabled": true
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert!(config.migrate());
+        let mut keys: Vec<&String> = config.get_windows_non_mut().keys().collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            vec!["chrome|Chrome_WidgetWin_1", "chrome|glob:Chrome_*"]
+        );
+        assert!(!config.migrate());
+    }
+
+    #[test]
+    fn replacing_a_rule_drops_the_old_key() {
+        let mut config = Config::new();
+        let mut window_config = rule("chrome", RuleLifetime::Permanent);
+        let old_key = window_config.get_key();
+        config.insert_rule(window_config.clone());
+
+        window_config.set_process_match(crate::pattern::MatchMode::Substring);
+        config.replace_rule(&old_key, window_config.clone());
+
+        let keys: Vec<&String> = config.get_windows_non_mut().keys().collect();
+        assert_eq!(keys, vec![&window_config.get_key()]);
+    }
+}
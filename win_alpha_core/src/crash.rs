@@ -0,0 +1,143 @@
+use crate::{backend::WindowBackend, journal::replay_journal};
+use std::{
+    backtrace::Backtrace,
+    fs, panic,
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/*
+  Installs a process wide panic hook that writes a crash report into report_dir, then puts back every window the monitor changed.
+  The windows come from the restore journal, so the hook does not need anything the panicking code might have been holding.
+  Made for panic = "abort": the hook runs before the process dies, the previous hook still runs afterwards.
+*/
+pub fn install_panic_hook(
+    backend: Arc<dyn WindowBackend>,
+    journal_path: PathBuf,
+    report_dir: PathBuf,
+) {
+    let previous_hook = panic::take_hook();
+
+    panic::set_hook(Box::new(move |info| {
+        handle_panic(
+            backend.as_ref(),
+            &journal_path,
+            &report_dir,
+            &info.to_string(),
+        );
+        previous_hook(info);
+    }));
+}
+
+/*
+  What the panic hook does, kept apart from it so it can be run without installing a process wide hook.
+*/
+fn handle_panic(
+    backend: &dyn WindowBackend,
+    journal_path: &Path,
+    report_dir: &Path,
+    message: &str,
+) {
+    let backtrace = Backtrace::force_capture();
+
+    match write_crash_report(report_dir, message, &backtrace) {
+        Ok(report_path) => eprintln!("Wrote crash report to {}", report_path.display()),
+        Err(e) => eprintln!("Failed to write crash report: {}", e),
+    }
+
+    match replay_journal(backend, journal_path) {
+        Ok(restored) => eprintln!("Restored {} windows before crashing", restored),
+        Err(e) => eprintln!("Failed to restore windows before crashing: {}", e),
+    }
+}
+
+fn write_crash_report(
+    report_dir: &Path,
+    message: &str,
+    backtrace: &Backtrace,
+) -> std::io::Result<PathBuf> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs())
+        .unwrap_or_default();
+
+    let report = format!(
+        "WinAlpha {} crashed on thread '{}'\n\n{}\n\nBacktrace:\n{}\n",
+        env!("CARGO_PKG_VERSION"),
+        thread::current().name().unwrap_or("<unnamed>"),
+        message,
+        backtrace
+    );
+
+    let report_path = report_dir.join(format!("crash-{}.txt", timestamp));
+    fs::write(&report_path, report)?;
+
+    Ok(report_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backend::memory::{MemoryBackend, MemoryWindow},
+        journal::get_journal_path,
+        original_state::OriginalStates,
+    };
+
+    #[test]
+    fn crash_report_has_the_message_and_a_backtrace() {
+        let dir = std::env::temp_dir().join(format!("win_alpha_report_{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("Failed to create the test directory");
+
+        let report_path =
+            write_crash_report(&dir, "something broke", &Backtrace::force_capture()).unwrap();
+        let report = fs::read_to_string(&report_path).unwrap();
+
+        assert!(report_path.starts_with(&dir));
+        assert!(report.contains(env!("CARGO_PKG_VERSION")));
+        assert!(report.contains("something broke"));
+        assert!(report.contains("Backtrace:"));
+
+        _ = fs::remove_dir_all(&dir);
+    }
+
+    // Runs what the hook runs rather than installing it, a process wide hook would catch the panics of tests running alongside
+    #[test]
+    fn panic_restores_windows_from_the_journal() {
+        let dir = std::env::temp_dir().join(format!("win_alpha_crash_{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("Failed to create the test directory");
+        let journal_path = get_journal_path(&dir.join("config.json"));
+
+        let backend = MemoryBackend::new();
+        backend.add_process(1, "app", false);
+        let window = backend.add_window(MemoryWindow {
+            class_name: "Main".to_owned(),
+            process_id: 1,
+            alpha: Some(200),
+            ..Default::default()
+        });
+
+        // Still held by the code that panicked, the hook only has the journal
+        let mut originals = OriginalStates::with_journal(journal_path.clone());
+        originals.set_alpha(&backend, window, 100);
+
+        handle_panic(&backend, &journal_path, &dir, "monitor died");
+
+        assert_eq!(backend.get_window(window).unwrap().alpha, Some(200));
+        assert!(!journal_path.exists());
+
+        let reports: Vec<PathBuf> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(reports.len(), 1);
+        assert!(fs::read_to_string(&reports[0])
+            .unwrap()
+            .contains("monitor died"));
+
+        drop(originals);
+        _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,332 @@
+use crate::backend::{WindowBackend, WindowHandle};
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, time::Instant};
+
+// How long a window takes to go from opaque back to its own alpha once the cursor is gone
+pub const FADE_DURATION: Duration = Duration::from_millis(300);
+
+/*
+  Makes the windows of a rule opaque while the cursor is over them.
+  The delays keep a cursor that only passes over a window from flashing it.
+*/
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct HoverSettings {
+    // Milliseconds the cursor has to stay on the window before it turns opaque
+    pub enter_delay: u64,
+    // Milliseconds after the cursor left before it goes back
+    pub leave_delay: u64,
+}
+
+impl Default for HoverSettings {
+    fn default() -> Self {
+        Self {
+            enter_delay: 200,
+            leave_delay: 500,
+        }
+    }
+}
+
+impl HoverSettings {
+    pub fn get_enter_delay(&self) -> Duration {
+        Duration::from_millis(self.enter_delay)
+    }
+
+    pub fn get_leave_delay(&self) -> Duration {
+        Duration::from_millis(self.leave_delay)
+    }
+}
+
+/*
+  Follows the cursor for the monitor and decides which top level windows are hovered.
+  The window under the cursor is only looked up again when the cursor moved, a resting cursor costs one position read per check.
+*/
+#[derive(Default, Debug)]
+pub struct HoverTracker {
+    cursor: Option<(i32, i32)>,
+    // The top level window under the cursor and since when
+    under_cursor: Option<(WindowHandle, Instant)>,
+    // Hovered windows and when the cursor left them, None while it is still on them
+    hovered: HashMap<WindowHandle, Option<Instant>>,
+    // Windows fading back, with when it started and how much of the way to opaque they still are (1.0 to 0.0)
+    fading: HashMap<WindowHandle, (Instant, f32)>,
+}
+
+impl HoverTracker {
+    pub fn is_hovered(&self, window: WindowHandle) -> bool {
+        self.hovered.contains_key(&window)
+    }
+
+    /*
+      The alpha the window should have given the one its rule wants: opaque while hovered, part of the way there while fading back.
+    */
+    pub fn get_alpha(&self, window: WindowHandle, alpha: u8) -> u8 {
+        if self.is_hovered(window) {
+            return u8::MAX;
+        }
+
+        match self.fading.get(&window) {
+            Some(&(_, left)) => alpha + (f32::from(u8::MAX - alpha) * left).round() as u8,
+            None => alpha,
+        }
+    }
+
+    /*
+      Reads the cursor and moves the windows of hover rules in and out of hover once their delay is up.
+      Leaving starts a fade back over FADE_DURATION, moved along a step by every check.
+      Returns the windows that changed, their alpha has to be set again.
+    */
+    pub fn update(
+        &mut self,
+        backend: &dyn WindowBackend,
+        windows: &HashMap<WindowHandle, HoverSettings>,
+        now: Instant,
+    ) -> Vec<WindowHandle> {
+        // Nothing to hover and nothing to put back, the cursor is not even read
+        if windows.is_empty() && self.hovered.is_empty() && self.fading.is_empty() {
+            return Vec::new();
+        }
+
+        let cursor = backend.get_cursor_position();
+        if cursor != self.cursor {
+            self.cursor = cursor;
+
+            let window = cursor
+                .and_then(|(x, y)| backend.window_from_point(x, y))
+                .map(|window| backend.get_top_level(window));
+            if self.under_cursor.map(|(under, _)| under) != window {
+                self.under_cursor = window.map(|window| (window, now));
+            }
+        }
+
+        // Windows no hover rule holds anymore go straight back to what their rule says
+        let mut changed: Vec<WindowHandle> = self
+            .hovered
+            .keys()
+            .chain(self.fading.keys())
+            .filter(|window| !windows.contains_key(window))
+            .copied()
+            .collect();
+        self.hovered
+            .retain(|window, _| windows.contains_key(window));
+        self.fading.retain(|window, _| windows.contains_key(window));
+
+        // Every check moves a fade a step further, the last one lands on the alpha of the rule
+        self.fading.retain(|&window, (started, left)| {
+            *left = 1.0 - now.duration_since(*started).as_secs_f32() / FADE_DURATION.as_secs_f32();
+            changed.push(window);
+            *left > 0.0
+        });
+
+        for (&window, settings) in windows {
+            let entered = self
+                .under_cursor
+                .filter(|&(under, _)| under == window)
+                .map(|(_, since)| since);
+
+            match (entered, self.hovered.get(&window).copied()) {
+                (Some(since), None) if now.duration_since(since) >= settings.get_enter_delay() => {
+                    // Back while it was still fading, it goes straight to opaque
+                    self.hovered.insert(window, None);
+                    self.fading.remove(&window);
+                    changed.push(window);
+                }
+                // Back before the leave delay was up
+                (Some(_), Some(Some(_))) => {
+                    self.hovered.insert(window, None);
+                }
+                (None, Some(None)) => {
+                    self.hovered.insert(window, Some(now));
+                }
+                (None, Some(Some(left)))
+                    if now.duration_since(left) >= settings.get_leave_delay() =>
+                {
+                    self.hovered.remove(&window);
+                    self.fading.insert(window, (now, 1.0));
+                }
+                _ => {}
+            }
+        }
+
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::memory::{MemoryBackend, MemoryWindow};
+
+    const SETTINGS: HoverSettings = HoverSettings {
+        enter_delay: 200,
+        leave_delay: 500,
+    };
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    // One window in the corner with a hover rule, and the cursor somewhere else
+    fn setup() -> (
+        MemoryBackend,
+        WindowHandle,
+        HashMap<WindowHandle, HoverSettings>,
+    ) {
+        let backend = MemoryBackend::new();
+        let window = backend.add_window(MemoryWindow {
+            class_name: "Main".to_owned(),
+            rect: (0, 0, 100, 100),
+            ..Default::default()
+        });
+        backend.set_cursor(500, 500, false);
+
+        (backend, window, HashMap::from([(window, SETTINGS)]))
+    }
+
+    // Puts the cursor on the window and waits out the enter delay, returns when it left
+    fn hover_then_leave(
+        backend: &MemoryBackend,
+        tracker: &mut HoverTracker,
+        windows: &HashMap<WindowHandle, HoverSettings>,
+        start: Instant,
+    ) -> Instant {
+        backend.set_cursor(50, 50, false);
+        tracker.update(backend, windows, start);
+        tracker.update(backend, windows, start + ms(200));
+
+        let left = start + ms(300);
+        backend.set_cursor(500, 500, false);
+        tracker.update(backend, windows, left);
+        left
+    }
+
+    #[test]
+    fn enter_waits_for_the_delay() {
+        let (backend, window, windows) = setup();
+        let mut tracker = HoverTracker::default();
+        let start = Instant::now();
+
+        backend.set_cursor(50, 50, false);
+        assert!(tracker.update(&backend, &windows, start).is_empty());
+        assert!(tracker
+            .update(&backend, &windows, start + ms(199))
+            .is_empty());
+        assert_eq!(tracker.get_alpha(window, 100), 100);
+
+        assert_eq!(
+            tracker.update(&backend, &windows, start + ms(200)),
+            vec![window]
+        );
+        assert!(tracker.is_hovered(window));
+        assert_eq!(tracker.get_alpha(window, 100), u8::MAX);
+
+        // Resting on it changes nothing more
+        assert!(tracker
+            .update(&backend, &windows, start + ms(1000))
+            .is_empty());
+    }
+
+    #[test]
+    fn passing_over_does_not_flash_the_window() {
+        let (backend, window, windows) = setup();
+        let mut tracker = HoverTracker::default();
+        let start = Instant::now();
+
+        backend.set_cursor(50, 50, false);
+        tracker.update(&backend, &windows, start);
+        backend.set_cursor(500, 500, false);
+        tracker.update(&backend, &windows, start + ms(100));
+
+        assert!(tracker
+            .update(&backend, &windows, start + ms(400))
+            .is_empty());
+        assert!(!tracker.is_hovered(window));
+    }
+
+    #[test]
+    fn leaving_waits_for_the_delay_then_fades_back() {
+        let (backend, window, windows) = setup();
+        let mut tracker = HoverTracker::default();
+        let left = hover_then_leave(&backend, &mut tracker, &windows, Instant::now());
+
+        assert!(tracker
+            .update(&backend, &windows, left + ms(499))
+            .is_empty());
+        assert_eq!(tracker.get_alpha(window, 100), u8::MAX);
+
+        // The fade starts from opaque once the delay is up
+        let faded = left + ms(500);
+        tracker.update(&backend, &windows, faded);
+        assert!(!tracker.is_hovered(window));
+        assert_eq!(tracker.get_alpha(window, 100), u8::MAX);
+
+        // Every check on the way is a step down
+        let mut last = u8::MAX;
+        for step in 1..FADE_DURATION.as_millis() as u64 / 50 {
+            assert_eq!(
+                tracker.update(&backend, &windows, faded + ms(step * 50)),
+                vec![window]
+            );
+            let alpha = tracker.get_alpha(window, 100);
+            assert!(alpha < last && alpha > 100, "{} after {}", alpha, last);
+            last = alpha;
+        }
+
+        assert_eq!(
+            tracker.update(&backend, &windows, faded + FADE_DURATION),
+            vec![window]
+        );
+        assert_eq!(tracker.get_alpha(window, 100), 100);
+        assert!(tracker
+            .update(&backend, &windows, faded + FADE_DURATION * 2)
+            .is_empty());
+    }
+
+    #[test]
+    fn coming_back_stops_the_leave_and_the_fade() {
+        let (backend, window, windows) = setup();
+        let mut tracker = HoverTracker::default();
+        let left = hover_then_leave(&backend, &mut tracker, &windows, Instant::now());
+
+        // Back before the leave delay was up, it never stopped being opaque
+        backend.set_cursor(60, 60, false);
+        assert!(tracker
+            .update(&backend, &windows, left + ms(300))
+            .is_empty());
+        assert!(tracker.is_hovered(window));
+
+        // Back halfway through the fade, opaque again once the enter delay is up
+        let left = hover_then_leave(&backend, &mut tracker, &windows, left + ms(1000));
+        tracker.update(&backend, &windows, left + ms(500));
+        tracker.update(&backend, &windows, left + ms(650));
+        assert!(tracker.get_alpha(window, 100) < u8::MAX);
+
+        backend.set_cursor(50, 50, false);
+        let back = left + ms(700);
+        tracker.update(&backend, &windows, back);
+        tracker.update(&backend, &windows, back + ms(200));
+        assert!(tracker.is_hovered(window));
+        assert_eq!(tracker.get_alpha(window, 100), u8::MAX);
+    }
+
+    #[test]
+    fn windows_without_a_hover_rule_go_straight_back() {
+        let (backend, window, windows) = setup();
+        let mut tracker = HoverTracker::default();
+        let start = Instant::now();
+
+        backend.set_cursor(50, 50, false);
+        tracker.update(&backend, &windows, start);
+        tracker.update(&backend, &windows, start + ms(200));
+
+        assert_eq!(
+            tracker.update(&backend, &HashMap::new(), start + ms(250)),
+            vec![window]
+        );
+        assert_eq!(tracker.get_alpha(window, 100), 100);
+        assert!(tracker
+            .update(&backend, &HashMap::new(), start + ms(300))
+            .is_empty());
+    }
+}
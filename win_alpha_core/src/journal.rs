@@ -0,0 +1,277 @@
+use crate::backend::{OriginalWindowState, WindowBackend, WindowHandle};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/*
+  One window WinAlpha changed, with enough of its identity to not restore a different window that later got the same handle.
+*/
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub window: WindowHandle,
+    pub state: OriginalWindowState,
+    pub class_name: Option<String>,
+    pub process_id: Option<u32>,
+    pub process_start_time: Option<u64>,
+}
+
+impl JournalEntry {
+    pub fn new(
+        backend: &dyn WindowBackend,
+        window: WindowHandle,
+        state: OriginalWindowState,
+    ) -> Self {
+        let process_id = backend.get_process_id(window);
+
+        Self {
+            window,
+            state,
+            class_name: backend.get_window_class(window),
+            process_id,
+            process_start_time: process_id
+                .and_then(|process_id| backend.get_process_start_time(process_id)),
+        }
+    }
+
+    /*
+      Returns if the window behind the handle is still the one that was recorded.
+    */
+    fn is_same_window(&self, backend: &dyn WindowBackend) -> bool {
+        let process_id = backend.get_process_id(self.window);
+
+        process_id.is_some()
+            && process_id == self.process_id
+            && backend.get_window_class(self.window) == self.class_name
+            && self.process_start_time.is_none_or(|start_time| {
+                process_id.and_then(|process_id| backend.get_process_start_time(process_id))
+                    == Some(start_time)
+            })
+    }
+}
+
+/*
+  The windows WinAlpha has changed, kept on disk next to config.json while they are changed.
+  When WinAlpha dies without restoring them the next launch (or `--restore`) replays it.
+*/
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    entries: HashMap<WindowHandle, JournalEntry>,
+}
+
+impl Journal {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, entry: JournalEntry) {
+        self.entries.insert(entry.window, entry);
+        self.save();
+    }
+
+    pub fn remove(&mut self, window: WindowHandle) {
+        if self.entries.remove(&window).is_some() {
+            self.save();
+        }
+    }
+
+    /*
+      Written to a temporary file first, a crash mid write leaves the old journal in place.
+      An empty journal is deleted.
+    */
+    fn save(&self) {
+        let result = if self.entries.is_empty() {
+            match fs::remove_file(&self.path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            }
+        } else {
+            write_journal(&self.path, self.entries.values().collect())
+        };
+
+        if let Err(e) = result {
+            eprintln!("Failed to write the restore journal: {}", e);
+        }
+    }
+}
+
+fn write_journal(path: &Path, entries: Vec<&JournalEntry>) -> anyhow::Result<()> {
+    let temp_path = path.with_extension("json.tmp");
+
+    fs::write(&temp_path, serde_json::to_string_pretty(&entries)?)?;
+    fs::rename(temp_path, path)?;
+
+    Ok(())
+}
+
+/*
+  Returns the journal path for a config path, they live in the same directory.
+*/
+pub fn get_journal_path(config_path: &Path) -> PathBuf {
+    config_path.with_file_name("journal.json")
+}
+
+/*
+  Restores every window a previous run left changed, then deletes the journal.
+  Windows that are gone or now belong to something else are skipped. Returns how many were restored.
+*/
+pub fn replay_journal(backend: &dyn WindowBackend, path: &Path) -> anyhow::Result<usize> {
+    let journal = match fs::read_to_string(path) {
+        Ok(journal) => journal,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+
+    let entries: Vec<JournalEntry> = serde_json::from_str(&journal)?;
+    let mut restored = 0;
+
+    for entry in entries {
+        if !entry.is_same_window(backend) {
+            continue;
+        }
+
+        match backend.restore_window_state(entry.window, &entry.state) {
+            Ok(()) => restored += 1,
+            Err(e) => eprintln!("Failed to restore window {:#x}: {}", entry.window, e),
+        }
+    }
+
+    fs::remove_file(path)?;
+
+    Ok(restored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backend::memory::{MemoryBackend, MemoryWindow},
+        original_state::OriginalStates,
+    };
+
+    // A journal path in a directory of its own per test
+    fn temp_journal_path(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("win_alpha_journal_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&dir).expect("Failed to create the test directory");
+        get_journal_path(&dir.join("config.json"))
+    }
+
+    fn read_entries(path: &Path) -> Vec<JournalEntry> {
+        serde_json::from_str(&fs::read_to_string(path).expect("The journal was not written"))
+            .expect("The journal is not valid")
+    }
+
+    fn add_window(backend: &MemoryBackend, alpha: Option<u8>) -> WindowHandle {
+        backend.add_window(MemoryWindow {
+            class_name: "Main".to_owned(),
+            process_id: 1,
+            alpha,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn journal_is_written_and_deleted_once_empty() {
+        let backend = MemoryBackend::new();
+        backend.add_process(1, "app", false);
+        let first = add_window(&backend, Some(200));
+        let second = add_window(&backend, None);
+        let path = temp_journal_path("write");
+
+        let mut journal = Journal::new(path.clone());
+        journal.record(JournalEntry::new(
+            &backend,
+            first,
+            OriginalWindowState::Alpha(Some(200)),
+        ));
+        journal.record(JournalEntry::new(
+            &backend,
+            second,
+            OriginalWindowState::Alpha(None),
+        ));
+
+        let mut entries = read_entries(&path);
+        entries.sort_by_key(|entry| entry.window);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].window, first);
+        assert_eq!(entries[0].state, OriginalWindowState::Alpha(Some(200)));
+        assert_eq!(entries[0].class_name.as_deref(), Some("Main"));
+        assert_eq!(entries[0].process_id, Some(1));
+        assert!(entries[0].process_start_time.is_some());
+
+        journal.remove(first);
+        assert_eq!(read_entries(&path).len(), 1);
+
+        journal.remove(second);
+        assert!(!path.exists());
+        assert!(!path.with_extension("json.tmp").exists());
+
+        _ = fs::remove_dir_all(path.parent().expect("Has a parent"));
+    }
+
+    #[test]
+    fn replay_restores_what_a_dead_run_left_changed() {
+        let backend = MemoryBackend::new();
+        backend.add_process(1, "app", false);
+        let layered = add_window(&backend, Some(200));
+        let plain = add_window(&backend, None);
+        let path = temp_journal_path("replay");
+
+        // A run that changes both windows and dies without restoring them
+        let mut originals = OriginalStates::with_journal(path.clone());
+        originals.set_alpha(&backend, layered, 100);
+        originals.set_alpha(&backend, plain, 100);
+        drop(originals);
+
+        assert_eq!(replay_journal(&backend, &path).unwrap(), 2);
+        assert_eq!(backend.get_window(layered).unwrap().alpha, Some(200));
+        assert_eq!(backend.get_window(plain).unwrap().alpha, None);
+        assert!(!path.exists());
+
+        // Nothing left to do the second time
+        assert_eq!(replay_journal(&backend, &path).unwrap(), 0);
+
+        _ = fs::remove_dir_all(path.parent().expect("Has a parent"));
+    }
+
+    #[test]
+    fn replay_skips_windows_that_are_not_the_recorded_one() {
+        let backend = MemoryBackend::new();
+        backend.add_process(1, "app", false);
+        let renamed = add_window(&backend, Some(100));
+        let restarted = add_window(&backend, Some(100));
+        let path = temp_journal_path("skip");
+
+        let mut entry = JournalEntry::new(&backend, restarted, OriginalWindowState::Alpha(None));
+        // The process behind the handle started later than the one that was recorded
+        entry.process_start_time = entry.process_start_time.map(|start_time| start_time + 1);
+        let mut journal = Journal::new(path.clone());
+        journal.record(JournalEntry::new(
+            &backend,
+            renamed,
+            OriginalWindowState::Alpha(None),
+        ));
+        journal.record(entry);
+        journal.record(JournalEntry::new(
+            &backend,
+            999,
+            OriginalWindowState::Alpha(None),
+        ));
+
+        backend.update_window(renamed, |window| window.class_name = "Other".to_owned());
+
+        assert_eq!(replay_journal(&backend, &path).unwrap(), 0);
+        assert_eq!(backend.get_window(renamed).unwrap().alpha, Some(100));
+        assert_eq!(backend.get_window(restarted).unwrap().alpha, Some(100));
+        assert!(!path.exists());
+
+        _ = fs::remove_dir_all(path.parent().expect("Has a parent"));
+    }
+}
@@ -0,0 +1,17 @@
+/*
+  The parts of WinAlpha that do not need a UI: the config model, rule matching, the window backends and the monitor loop.
+  The win_alpha binary is the tray and Slint windows on top of this.
+*/
+pub mod app_state;
+pub mod backend;
+pub mod config;
+pub mod crash;
+pub mod hover;
+pub mod journal;
+pub mod lifetime;
+pub mod monitor;
+pub mod monitor_match;
+pub mod original_state;
+pub mod pattern;
+pub mod window_config;
+pub mod window_index;
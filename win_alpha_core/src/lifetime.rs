@@ -0,0 +1,193 @@
+use crate::backend::WindowBackend;
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/*
+  How long a rule lives. Expired rules are removed by the monitor and their windows restored.
+  Session rules and rules tied to a process are never saved, a process id means nothing after a restart.
+*/
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleLifetime {
+    #[default]
+    Permanent,
+    Session,
+    // Unix time in seconds the rule ends at
+    Until(u64),
+    // Ends when this process exits, the start time tells it apart from a later process that reuses the id
+    Process {
+        process_id: u32,
+        start_time: u64,
+    },
+}
+
+impl RuleLifetime {
+    /*
+      A lifetime that ends the duration from now.
+    */
+    pub fn lasting(duration: Duration) -> Self {
+        RuleLifetime::Until(now_secs().saturating_add(duration.as_secs()))
+    }
+
+    /*
+      A lifetime that ends with the process, None when the process is already gone.
+    */
+    pub fn until_exit(backend: &dyn WindowBackend, process_id: u32) -> Option<Self> {
+        backend
+            .get_process_start_time(process_id)
+            .map(|start_time| RuleLifetime::Process {
+                process_id,
+                start_time,
+            })
+    }
+
+    pub fn is_permanent(&self) -> bool {
+        *self == RuleLifetime::Permanent
+    }
+
+    /*
+      Returns if a rule with this lifetime is written to the config file.
+    */
+    pub fn is_persistent(&self) -> bool {
+        matches!(self, RuleLifetime::Permanent | RuleLifetime::Until(_))
+    }
+
+    pub fn is_expired(&self, backend: &dyn WindowBackend) -> bool {
+        match *self {
+            RuleLifetime::Permanent | RuleLifetime::Session => false,
+            RuleLifetime::Until(at) => now_secs() >= at,
+            RuleLifetime::Process {
+                process_id,
+                start_time,
+            } => backend.get_process_start_time(process_id) != Some(start_time),
+        }
+    }
+
+    /*
+      What the rules window shows for the lifetime, empty for permanent rules.
+    */
+    pub fn describe(&self) -> String {
+        match *self {
+            RuleLifetime::Permanent => String::new(),
+            RuleLifetime::Session => "Until WinAlpha exits".to_owned(),
+            RuleLifetime::Until(at) => {
+                format!("{} min left", at.saturating_sub(now_secs()).div_ceil(60))
+            }
+            RuleLifetime::Process { .. } => "Until the app exits".to_owned(),
+        }
+    }
+}
+
+/*
+  Written as `permanent`, `session`, `until:<unix secs>` or `process:<id>:<start time>`, so the UI can carry a lifetime around as text.
+*/
+impl fmt::Display for RuleLifetime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleLifetime::Permanent => f.write_str("permanent"),
+            RuleLifetime::Session => f.write_str("session"),
+            RuleLifetime::Until(at) => write!(f, "until:{}", at),
+            RuleLifetime::Process {
+                process_id,
+                start_time,
+            } => write!(f, "process:{}:{}", process_id, start_time),
+        }
+    }
+}
+
+impl FromStr for RuleLifetime {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut parts = value.split(':');
+
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some("permanent"), None, None) => Ok(RuleLifetime::Permanent),
+            (Some("session"), None, None) => Ok(RuleLifetime::Session),
+            (Some("until"), Some(at), None) => Ok(RuleLifetime::Until(at.parse()?)),
+            (Some("process"), Some(process_id), Some(start_time)) => Ok(RuleLifetime::Process {
+                process_id: process_id.parse()?,
+                start_time: start_time.parse()?,
+            }),
+            _ => Err(anyhow::anyhow!("Unknown rule lifetime: {}", value)),
+        }
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::memory::MemoryBackend;
+
+    #[test]
+    fn timed_lifetime_expires_once_its_time_is_up() {
+        let backend = MemoryBackend::new();
+
+        assert!(RuleLifetime::Until(0).is_expired(&backend));
+        assert!(RuleLifetime::Until(now_secs()).is_expired(&backend));
+        assert!(!RuleLifetime::lasting(Duration::from_secs(3600)).is_expired(&backend));
+        assert!(!RuleLifetime::Permanent.is_expired(&backend));
+        assert!(!RuleLifetime::Session.is_expired(&backend));
+    }
+
+    #[test]
+    fn process_lifetime_expires_when_the_process_exits() {
+        let backend = MemoryBackend::new();
+        backend.add_process(1, "app", false);
+
+        let lifetime = RuleLifetime::until_exit(&backend, 1).expect("The process is running");
+        assert!(!lifetime.is_expired(&backend));
+
+        backend.remove_process(1);
+        assert!(lifetime.is_expired(&backend));
+
+        // A new process that got the same id is not the one the rule was for
+        backend.add_process(1, "app", false);
+        assert!(lifetime.is_expired(&backend));
+
+        assert_eq!(RuleLifetime::until_exit(&backend, 2), None);
+    }
+
+    #[test]
+    fn only_permanent_and_timed_lifetimes_are_saved() {
+        assert!(RuleLifetime::Permanent.is_persistent());
+        assert!(RuleLifetime::Until(10).is_persistent());
+        assert!(!RuleLifetime::Session.is_persistent());
+        assert!(!RuleLifetime::Process {
+            process_id: 1,
+            start_time: 2
+        }
+        .is_persistent());
+    }
+
+    #[test]
+    fn lifetime_round_trips_as_text() {
+        for lifetime in [
+            RuleLifetime::Permanent,
+            RuleLifetime::Session,
+            RuleLifetime::Until(1_700_000_000),
+            RuleLifetime::Process {
+                process_id: 42,
+                start_time: 7,
+            },
+        ] {
+            assert_eq!(
+                lifetime.to_string().parse::<RuleLifetime>().unwrap(),
+                lifetime
+            );
+        }
+        assert!("until".parse::<RuleLifetime>().is_err());
+        assert!("process:1".parse::<RuleLifetime>().is_err());
+    }
+}
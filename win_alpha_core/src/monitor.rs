@@ -0,0 +1,1462 @@
+use crate::{
+    app_state::AppState,
+    backend::{WindowBackend, WindowEvent, WindowHandle},
+    config::Config,
+    hover::{HoverSettings, HoverTracker},
+    journal::replay_journal,
+    original_state::OriginalStates,
+    window_config::WindowConfig,
+    window_index::WindowIndex,
+};
+use core::time::Duration;
+use std::{
+    collections::{HashMap, HashSet},
+    future::pending,
+    mem,
+    sync::Arc,
+    time::Instant,
+};
+use tokio::{
+    sync::{mpsc::UnboundedReceiver, watch},
+    time::{interval, Interval, MissedTickBehavior},
+};
+// Delays between window monitor runs
+// new windows, window updates etc.
+const MONITOR_DELAY: u64 = 120;
+
+// Delay between full rescans when the backend pushes window events, only catches what the events missed.
+const FALLBACK_DELAY: u64 = 5000;
+
+// Delay between cursor checks, only while a rule has hover on.
+const HOVER_DELAY: u64 = 50;
+
+// Delay between checks for rules whose lifetime is over, each looks up the processes the rules wait on.
+const EXPIRY_DELAY: u64 = 1000;
+
+// Apps that keep resetting their own alpha are retried after this, doubling every time up to the max.
+const DRIFT_BACKOFF: Duration = Duration::from_secs(1);
+const DRIFT_BACKOFF_MAX: Duration = Duration::from_secs(300);
+
+// A window that left its alpha alone this long starts over without backoff.
+const DRIFT_FORGET: Duration = Duration::from_secs(600);
+
+#[derive(Eq, PartialEq, Clone, Debug)]
+struct WindowHandleState {
+    handle: WindowHandle,
+    // The top level window the handle was found under, the handle itself unless a class chain matched a window below it
+    root: WindowHandle,
+    transparency: u8,
+    enabled: bool,
+    // How many times in a row the window changed its alpha back, and when we last saw it
+    drift_count: u32,
+    last_drift: Option<Instant>,
+    retry_at: Option<Instant>,
+}
+
+/*
+  The windows the monitor is tracking per rule, and what they looked like before they were touched.
+*/
+struct WindowCache {
+    rules: HashMap<String, Vec<WindowHandleState>>,
+    originals: OriginalStates,
+    // The top level window with the focus, for rules with an inactive transparency
+    foreground: Option<WindowHandle>,
+    hover: HoverTracker,
+}
+
+impl WindowHandleState {
+    pub fn new(handle: WindowHandle, root: WindowHandle) -> Self {
+        Self {
+            handle,
+            root,
+            transparency: 1,
+            enabled: false,
+            drift_count: 0,
+            last_drift: None,
+            retry_at: None,
+        }
+    }
+
+    pub fn get_handle(&self) -> WindowHandle {
+        self.handle
+    }
+
+    pub fn get_root(&self) -> WindowHandle {
+        self.root
+    }
+
+    pub fn get_transparency(&self) -> u8 {
+        self.transparency
+    }
+
+    pub fn update_state(&mut self, transparency: u8, enabled: bool) {
+        self.transparency = transparency;
+        self.enabled = enabled;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn refresh_window(&mut self, backend: &dyn WindowBackend, originals: &mut OriginalStates) {
+        self.enabled = false;
+        self.apply_alpha(backend, originals);
+    }
+
+    fn get_target_alpha(&self) -> u8 {
+        if self.enabled {
+            self.transparency
+        } else {
+            255
+        }
+    }
+
+    /*
+      A disabled window goes back to how it was before, not to 255.
+    */
+    fn apply_alpha(&self, backend: &dyn WindowBackend, originals: &mut OriginalStates) {
+        if self.enabled {
+            originals.set_alpha(backend, self.get_handle(), self.transparency);
+        } else {
+            originals.restore(backend, self.get_handle());
+        }
+    }
+
+    pub fn update_window(
+        &mut self,
+        backend: &dyn WindowBackend,
+        originals: &mut OriginalStates,
+        new_transparency: u8,
+        enabled: bool,
+    ) {
+        if self.get_transparency() != new_transparency || self.is_enabled() != enabled {
+            self.update_state(new_transparency, enabled);
+            self.drift_count = 0;
+            self.last_drift = None;
+            self.retry_at = None;
+            self.apply_alpha(backend, originals);
+        } else if self.is_enabled() {
+            self.check_drift(backend, originals, Instant::now());
+        }
+    }
+
+    /*
+      Reads the real alpha back from the window and reapplies ours when the app changed it (dropped WS_EX_LAYERED, went fullscreen...).
+      Every time the app changes it back the next retry waits twice as long.
+    */
+    fn check_drift(
+        &mut self,
+        backend: &dyn WindowBackend,
+        originals: &mut OriginalStates,
+        now: Instant,
+    ) {
+        let target = self.get_target_alpha();
+        let actual = backend.get_window_alpha(self.get_handle()).unwrap_or(255);
+
+        if actual == target {
+            if self
+                .last_drift
+                .is_some_and(|last_drift| now.duration_since(last_drift) >= DRIFT_FORGET)
+            {
+                self.drift_count = 0;
+                self.last_drift = None;
+            }
+            return;
+        }
+
+        if self.retry_at.is_some_and(|retry_at| now < retry_at) {
+            return;
+        }
+
+        self.drift_count += 1;
+        self.last_drift = Some(now);
+
+        let backoff = DRIFT_BACKOFF
+            .saturating_mul(2u32.saturating_pow(self.drift_count - 1))
+            .min(DRIFT_BACKOFF_MAX);
+        self.retry_at = Some(now + backoff);
+
+        eprintln!(
+            "Window {:#x} changed its alpha to {} (expected {}), reapplying. Changed {} time(s), next retry in {}s",
+            self.get_handle(),
+            actual,
+            target,
+            self.drift_count,
+            backoff.as_secs()
+        );
+
+        self.apply_alpha(backend, originals);
+    }
+}
+
+/*
+  Monitors the current windows specified in the config file. This is setup to target based on the window class rather than title (multiple windows open of X application...)
+  Backends that push window events get the rules applied to just the window in the event, with a slow rescan as fallback. The rest are polled.
+*/
+#[inline(always)]
+pub async fn monitor_windows(app_state: Arc<AppState>) {
+    let backend = app_state.get_backend();
+
+    // Subscribed first, a quit that comes in while starting up is not lost
+    let mut shutdown = app_state.subscribe_shutdown();
+
+    // Put back whatever a previous run that crashed left changed, before touching anything new
+    let journal_path = app_state.get_journal_path();
+    match replay_journal(backend.as_ref(), &journal_path) {
+        Ok(0) => {}
+        Ok(restored) => eprintln!("Restored {} windows left over by the last run", restored),
+        Err(e) => eprintln!("Failed to replay the restore journal: {}", e),
+    }
+
+    let mut window_cache = WindowCache {
+        rules: HashMap::new(),
+        originals: OriginalStates::with_journal(journal_path),
+        foreground: None,
+        hover: HoverTracker::default(),
+    };
+
+    let mut config = app_state.get_config().await;
+    let mut is_enabled = app_state.is_enabled().await;
+
+    // This is the in memory config
+    let mut application_config = app_state.subscribe_config_updates();
+
+    // Global application toggle.
+    let mut application_toggle = app_state.subscribe_enabled_updates();
+
+    // Window events from the backend, if it has any.
+    let mut window_events = backend.subscribe_events();
+    let mut rescan = rescan_interval(window_events.is_some());
+    let mut hover_check = hover_interval(&config);
+    let mut expiry_check = interval(Duration::from_millis(EXPIRY_DELAY));
+    expiry_check.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    if is_enabled {
+        apply_rules(backend.as_ref(), &mut config, &mut window_cache);
+    }
+
+    loop {
+        tokio::select! {
+            _ = wait_for_shutdown(&mut shutdown) => {
+                reset_windows(backend.as_ref(), &mut window_cache);
+                app_state.confirm_shutdown();
+                break;
+            }
+            Ok(new_config) = application_config.recv() => {
+                config = new_config;
+                hover_check = hover_interval(&config);
+
+                if is_enabled {
+                    apply_rules(backend.as_ref(), &mut config, &mut window_cache);
+                }
+            }
+            Ok(state) = application_toggle.recv() => {
+                if state != is_enabled && is_enabled {
+                    reset_windows(backend.as_ref(), &mut window_cache);
+                }
+                is_enabled = state;
+
+                if is_enabled {
+                    apply_rules(backend.as_ref(), &mut config, &mut window_cache);
+                }
+            }
+            event = next_window_event(&mut window_events) => {
+                match event {
+                    Some(event) if is_enabled => {
+                        apply_window_event(backend.as_ref(), &config, &mut window_cache, event);
+                    }
+                    Some(_) => {}
+                    // The backend stopped sending events, go back to polling
+                    None => {
+                        window_events = None;
+                        rescan = rescan_interval(false);
+                    }
+                }
+            }
+            _ = next_hover_check(&mut hover_check) => {
+                if is_enabled {
+                    apply_hover(backend.as_ref(), &config, &mut window_cache);
+                }
+            }
+            _ = expiry_check.tick() => {
+                // The new config comes back through the config updates, which restores the windows of the expired rules
+                if let Err(e) = app_state.remove_expired_rules().await {
+                    eprintln!("Failed to remove expired rules: {}", e);
+                }
+            }
+            _ = rescan.tick() => {
+                if is_enabled {
+                    apply_rules(backend.as_ref(), &mut config, &mut window_cache);
+                }
+            }
+            else => break
+        }
+    }
+}
+
+/*
+  Full rescans are the fallback when there are events, otherwise they are all we have.
+*/
+fn rescan_interval(has_events: bool) -> Interval {
+    let delay = if has_events {
+        FALLBACK_DELAY
+    } else {
+        MONITOR_DELAY
+    };
+
+    let mut rescan = interval(Duration::from_millis(delay));
+    rescan.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    rescan
+}
+
+/*
+  Cursor checks for hover, None when no enabled rule has it so nothing wakes up for them.
+*/
+fn hover_interval(config: &Config) -> Option<Interval> {
+    let has_hover = config
+        .get_active_rules()
+        .into_iter()
+        .any(|window_config| window_config.is_enabled() && window_config.get_hover().is_some());
+
+    has_hover.then(|| {
+        let mut hover_check = interval(Duration::from_millis(HOVER_DELAY));
+        hover_check.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        hover_check
+    })
+}
+
+/*
+  Waits for the next cursor check, forever when there are none.
+*/
+async fn next_hover_check(hover_check: &mut Option<Interval>) {
+    match hover_check {
+        Some(hover_check) => {
+            hover_check.tick().await;
+        }
+        None => pending().await,
+    }
+}
+
+/*
+  Waits until quitting is asked for. The borrow of the flag is dropped here, it must not be held across the other branches.
+*/
+async fn wait_for_shutdown(shutdown: &mut watch::Receiver<bool>) {
+    _ = shutdown.wait_for(|&quit| quit).await;
+}
+
+/*
+  Waits for the next window event, forever when the backend has none.
+*/
+async fn next_window_event(
+    window_events: &mut Option<UnboundedReceiver<WindowEvent>>,
+) -> Option<WindowEvent> {
+    match window_events {
+        Some(receiver) => receiver.recv().await,
+        None => pending().await,
+    }
+}
+
+#[inline(always)]
+fn apply_rules(backend: &dyn WindowBackend, config: &mut Config, window_cache: &mut WindowCache) {
+    backend.prune_processes();
+    // Focus events keep it current, this catches the ones that were missed
+    window_cache.foreground = backend.get_foreground_window();
+    refresh_window_cache(backend, config, window_cache);
+    update_windows(backend, config, window_cache);
+}
+
+/*
+  Applies the rules to the one window the event is about, without rescanning every rule.
+  Everything found under it is resolved again, windows that stopped matching (class change) are reset and dropped from the cache.
+*/
+fn apply_window_event(
+    backend: &dyn WindowBackend,
+    config: &Config,
+    window_cache: &mut WindowCache,
+    event: WindowEvent,
+) {
+    let root = match event {
+        WindowEvent::Created(handle) | WindowEvent::Changed(handle) => handle,
+        WindowEvent::Foreground(foreground) => {
+            apply_focus_change(backend, config, window_cache, foreground);
+            return;
+        }
+        WindowEvent::Destroyed(handle) => {
+            let WindowCache {
+                rules, originals, ..
+            } = window_cache;
+
+            for states in rules.values_mut() {
+                states.retain(|state| {
+                    let destroyed = state.get_handle() == handle || state.get_root() == handle;
+                    if destroyed {
+                        originals.forget(state.get_handle());
+                    }
+                    !destroyed
+                });
+            }
+            rules.retain(|_, states| !states.is_empty());
+            originals.forget(handle);
+            return;
+        }
+    };
+
+    // The same first match wins as in a full refresh, just for the windows under this one. None is an exclusion.
+    let mut winners: HashMap<WindowHandle, Option<(String, &WindowConfig)>> = HashMap::new();
+    for window_config in config.get_windows_in_order() {
+        if window_config.is_exclude() && !window_config.is_active_exclusion() {
+            continue;
+        }
+
+        for target in window_config.get_targets(backend, root) {
+            winners.entry(target).or_insert_with(|| {
+                (!window_config.is_exclude())
+                    .then(|| (window_config.get_cache_key(), window_config))
+            });
+        }
+    }
+
+    let WindowCache {
+        rules,
+        originals,
+        foreground,
+        hover,
+    } = window_cache;
+
+    for (key, states) in rules.iter_mut() {
+        states.retain_mut(|state| {
+            let keep = state.get_root() != root
+                || winners.get(&state.get_handle()).is_some_and(|winner| {
+                    winner
+                        .as_ref()
+                        .is_some_and(|(winner_key, _)| winner_key == key)
+                });
+            if !keep {
+                state.refresh_window(backend, originals);
+            }
+            keep
+        });
+    }
+
+    for (target, (key, window_config)) in winners
+        .into_iter()
+        .filter_map(|(target, winner)| Some((target, winner?)))
+    {
+        let states = rules.entry(key).or_default();
+        let index = match states.iter().position(|state| state.get_handle() == target) {
+            Some(index) => index,
+            None => {
+                states.push(WindowHandleState::new(target, root));
+                states.len() - 1
+            }
+        };
+
+        states[index].update_window(
+            backend,
+            originals,
+            get_rule_transparency(window_config, root, *foreground, hover),
+            window_config.is_enabled(),
+        );
+    }
+
+    rules.retain(|_, states| !states.is_empty());
+}
+
+/*
+  Moves the focus from one window to another. Only the windows of those two are updated, and only for rules with an inactive transparency.
+*/
+fn apply_focus_change(
+    backend: &dyn WindowBackend,
+    config: &Config,
+    window_cache: &mut WindowCache,
+    new_foreground: Option<WindowHandle>,
+) {
+    let previous = mem::replace(&mut window_cache.foreground, new_foreground);
+    if previous == new_foreground {
+        return;
+    }
+
+    let WindowCache {
+        rules,
+        originals,
+        foreground,
+        hover,
+    } = window_cache;
+
+    for window_config in config
+        .get_active_rules()
+        .into_iter()
+        .filter(|cfg| cfg.get_inactive_transparency().is_some())
+    {
+        let Some(states) = rules.get_mut(&window_config.get_cache_key()) else {
+            continue;
+        };
+
+        for state in states.iter_mut() {
+            let root = state.get_root();
+            if Some(root) != previous && Some(root) != new_foreground {
+                continue;
+            }
+
+            state.update_window(
+                backend,
+                originals,
+                get_rule_transparency(window_config, root, *foreground, hover),
+                window_config.is_enabled(),
+            );
+        }
+    }
+}
+
+/*
+  Checks the cursor against the windows of hover rules, only the windows it moved onto or off of are updated.
+*/
+fn apply_hover(backend: &dyn WindowBackend, config: &Config, window_cache: &mut WindowCache) {
+    let WindowCache {
+        rules,
+        originals,
+        foreground,
+        hover,
+    } = window_cache;
+    let active_rules = config.get_active_rules();
+
+    let mut windows: HashMap<WindowHandle, HoverSettings> = HashMap::new();
+    for window_config in active_rules.iter().filter(|cfg| cfg.is_enabled()) {
+        let Some(settings) = window_config.get_hover() else {
+            continue;
+        };
+
+        if let Some(states) = rules.get(&window_config.get_cache_key()) {
+            windows.extend(states.iter().map(|state| (state.get_root(), settings)));
+        }
+    }
+
+    let changed = hover.update(backend, &windows, Instant::now());
+    if changed.is_empty() {
+        return;
+    }
+
+    for window_config in active_rules {
+        let Some(states) = rules.get_mut(&window_config.get_cache_key()) else {
+            continue;
+        };
+
+        for state in states
+            .iter_mut()
+            .filter(|state| changed.contains(&state.get_root()))
+        {
+            state.update_window(
+                backend,
+                originals,
+                get_rule_transparency(window_config, state.get_root(), *foreground, hover),
+                window_config.is_enabled(),
+            );
+        }
+    }
+}
+
+/*
+  What the windows of a rule under a top level window are set to: by whether it has the focus, opaque while hovered and fading back after.
+*/
+fn get_rule_transparency(
+    window_config: &WindowConfig,
+    root: WindowHandle,
+    foreground: Option<WindowHandle>,
+    hover: &HoverTracker,
+) -> u8 {
+    let transparency = window_config.get_transparency_for(foreground == Some(root));
+
+    if window_config.get_hover().is_some() {
+        hover.get_alpha(root, transparency)
+    } else {
+        transparency
+    }
+}
+
+/*
+  Enumerates the desktop once, then resolves every rule against that index.
+  Windows of rules that are gone (removed, or renamed to another class) are restored, as are touched windows no rule holds anymore (hidden).
+*/
+#[inline(always)]
+fn refresh_window_cache(backend: &dyn WindowBackend, config: &mut Config, cache: &mut WindowCache) {
+    let index = WindowIndex::build(backend);
+    let WindowCache {
+        rules, originals, ..
+    } = cache;
+    let active_rules = config.get_active_rules();
+    let rule_keys: HashSet<String> = active_rules.iter().map(|cfg| cfg.get_cache_key()).collect();
+
+    // Not restored here, a window another rule now holds goes straight to its new alpha. The rest are restored below as untracked.
+    rules.retain(|key, _| rule_keys.contains(key));
+
+    let mut winners = resolve_winners(backend, config, &index);
+
+    for cfg in active_rules {
+        let key = cfg.get_cache_key();
+        let handles = winners.remove(&key).unwrap_or_default();
+
+        if handles.is_empty() {
+            if let Some(val) = rules.get_mut(&key) {
+                val.clear();
+            }
+            continue;
+        }
+
+        let states = rules.entry(key).or_default();
+
+        states.retain(|state| handles.iter().any(|&(_, target)| target == state.handle));
+
+        let existing_handles: HashSet<_> = states.iter().map(|state| state.handle).collect();
+        for (root, target) in handles {
+            if !existing_handles.contains(&target) {
+                states.push(WindowHandleState::new(target, root));
+            }
+        }
+    }
+
+    rules.retain(|_, states| !states.is_empty());
+
+    let tracked: HashSet<WindowHandle> = rules
+        .values()
+        .flat_map(|states| states.iter().map(|state| state.get_handle()))
+        .collect();
+    let untracked: Vec<WindowHandle> = originals
+        .get_windows()
+        .filter(|window| !tracked.contains(window))
+        .collect();
+
+    // Windows below a top level one are not in the index, they still exist while they have a parent
+    for window in untracked {
+        if index.contains(window) || backend.get_parent(window).is_some() {
+            originals.restore(backend, window);
+        } else {
+            originals.forget(window);
+        }
+    }
+}
+
+/*
+  Picks the one rule each visible window belongs to, once per refresh.
+  Rules are tried from the highest precedence down and the first to match a window keeps it, the others would only fight over its alpha.
+  Exclusions come first, the windows they claim are in no rule so a window that was transparent is restored.
+  Returns the (top level window, window to set the alpha of) pairs won by each rule, by cache key.
+*/
+fn resolve_winners(
+    backend: &dyn WindowBackend,
+    config: &Config,
+    index: &WindowIndex,
+) -> HashMap<String, Vec<(WindowHandle, WindowHandle)>> {
+    let mut claimed: HashSet<WindowHandle> = HashSet::new();
+    let mut winners: HashMap<String, Vec<(WindowHandle, WindowHandle)>> = HashMap::new();
+
+    for cfg in config.get_windows_in_order() {
+        // An exclusion that is switched off protects nothing
+        if cfg.is_exclude() && !cfg.is_active_exclusion() {
+            continue;
+        }
+
+        let handles: Vec<(WindowHandle, WindowHandle)> = cfg
+            .get_indexed_hwnds(backend, index)
+            .into_iter()
+            .filter(|&(_, target)| claimed.insert(target))
+            .collect();
+
+        // An exclusion keeps what it matches from every rule below it, and wins nothing itself
+        if !handles.is_empty() && !cfg.is_exclude() {
+            winners.insert(cfg.get_cache_key(), handles);
+        }
+    }
+
+    winners
+}
+
+#[inline(always)]
+fn update_windows(backend: &dyn WindowBackend, config: &Config, window_cache: &mut WindowCache) {
+    let WindowCache {
+        rules,
+        originals,
+        foreground,
+        hover,
+    } = window_cache;
+
+    for window_config in config.get_active_rules() {
+        if let Some(handle_states) = rules.get_mut(&window_config.get_cache_key()) {
+            for state in handle_states.iter_mut() {
+                state.update_window(
+                    backend,
+                    originals,
+                    get_rule_transparency(window_config, state.get_root(), *foreground, hover),
+                    window_config.is_enabled(),
+                );
+            }
+        }
+    }
+}
+
+/*
+  Puts every window WinAlpha touched back the way it was.
+*/
+#[inline(always)]
+fn reset_windows(backend: &dyn WindowBackend, window_cache: &mut WindowCache) {
+    let WindowCache {
+        rules, originals, ..
+    } = window_cache;
+
+    rules
+        .values_mut()
+        .flat_map(|handles| handles.iter_mut())
+        .for_each(|handle| handle.refresh_window(backend, originals));
+    originals.restore_all(backend);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backend::{
+            memory::{MemoryBackend, MemoryWindow},
+            MonitorInfo, WindowRole,
+        },
+        monitor_match::MonitorMatch,
+        pattern::MatchMode,
+        window_config::WindowInfo,
+    };
+    use std::{fs, path::PathBuf};
+    use tokio::time::timeout;
+
+    fn add_rule(config: &mut Config, process_name: &str, class_name: &str, alpha: u8) -> String {
+        let window_config = WindowConfig::new(
+            &WindowInfo {
+                class_name: class_name.to_owned(),
+                process_name: process_name.to_owned(),
+                ..Default::default()
+            },
+            alpha,
+        );
+        let key = window_config.get_key();
+        config.get_windows().insert(key.clone(), window_config);
+        key
+    }
+
+    fn add_window(backend: &MemoryBackend, process_id: u32, class_name: &str) -> WindowHandle {
+        backend.add_window(MemoryWindow {
+            class_name: class_name.to_owned(),
+            process_id,
+            ..Default::default()
+        })
+    }
+
+    fn new_cache() -> WindowCache {
+        WindowCache {
+            rules: HashMap::new(),
+            originals: OriginalStates::new(),
+            foreground: None,
+            hover: HoverTracker::default(),
+        }
+    }
+
+    fn alpha(backend: &MemoryBackend, window: WindowHandle) -> Option<u8> {
+        backend.get_window(window).and_then(|window| window.alpha)
+    }
+
+    // A config path of its own per test, the journal is written next to it
+    fn temp_config_path(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("win_alpha_test_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&dir).expect("Failed to create the test directory");
+        dir.join("config.json")
+    }
+
+    #[test]
+    fn rule_is_applied_to_matching_window() {
+        let backend = MemoryBackend::new();
+        backend.add_process(1, "app", false);
+        backend.add_process(2, "other", false);
+        let matching = add_window(&backend, 1, "Main");
+        let other_class = add_window(&backend, 1, "Other");
+        let other_process = add_window(&backend, 2, "Main");
+
+        let mut config = Config::new();
+        add_rule(&mut config, "app", "Main", 128);
+
+        apply_rules(&backend, &mut config, &mut new_cache());
+
+        assert_eq!(alpha(&backend, matching), Some(128));
+        assert_eq!(alpha(&backend, other_class), None);
+        assert_eq!(alpha(&backend, other_process), None);
+    }
+
+    #[test]
+    fn refresh_picks_up_new_and_closed_windows() {
+        let backend = MemoryBackend::new();
+        backend.add_process(1, "app", false);
+        let first = add_window(&backend, 1, "Main");
+
+        let mut config = Config::new();
+        add_rule(&mut config, "app", "Main", 128);
+        let mut cache = new_cache();
+        apply_rules(&backend, &mut config, &mut cache);
+
+        let second = add_window(&backend, 1, "Main");
+        apply_rules(&backend, &mut config, &mut cache);
+        assert_eq!(alpha(&backend, second), Some(128));
+
+        backend.remove_window(first);
+        apply_rules(&backend, &mut config, &mut cache);
+
+        let tracked: Vec<WindowHandle> = cache
+            .rules
+            .values()
+            .flatten()
+            .map(WindowHandleState::get_handle)
+            .collect();
+        assert_eq!(tracked, vec![second]);
+        assert!(!cache.originals.get_windows().any(|window| window == first));
+    }
+
+    #[test]
+    fn disabling_a_rule_restores_the_original_alpha() {
+        let backend = MemoryBackend::new();
+        backend.add_process(1, "app", false);
+        let window = backend.add_window(MemoryWindow {
+            class_name: "Main".to_owned(),
+            process_id: 1,
+            alpha: Some(200),
+            ..Default::default()
+        });
+
+        let mut config = Config::new();
+        let key = add_rule(&mut config, "app", "Main", 128);
+        let mut cache = new_cache();
+        apply_rules(&backend, &mut config, &mut cache);
+        assert_eq!(alpha(&backend, window), Some(128));
+
+        config
+            .get_windows()
+            .get_mut(&key)
+            .expect("The rule was just added")
+            .set_enabled(false);
+        apply_rules(&backend, &mut config, &mut cache);
+        assert_eq!(alpha(&backend, window), Some(200));
+    }
+
+    #[test]
+    fn removing_a_rule_restores_the_original_alpha() {
+        let backend = MemoryBackend::new();
+        backend.add_process(1, "app", false);
+        let window = add_window(&backend, 1, "Main");
+
+        let mut config = Config::new();
+        let key = add_rule(&mut config, "app", "Main", 128);
+        let mut cache = new_cache();
+        apply_rules(&backend, &mut config, &mut cache);
+
+        config.get_windows().remove(&key);
+        apply_rules(&backend, &mut config, &mut cache);
+        assert_eq!(alpha(&backend, window), None);
+    }
+
+    #[test]
+    fn rules_for_the_same_class_keep_their_own_windows() {
+        let backend = MemoryBackend::new();
+        backend.add_process(1, "editor", false);
+        backend.add_process(2, "browser", false);
+        let editor = backend.add_window(MemoryWindow {
+            class_name: "Chrome_WidgetWin_1".to_owned(),
+            process_id: 1,
+            alpha: Some(200),
+            ..Default::default()
+        });
+        let browser = backend.add_window(MemoryWindow {
+            class_name: "Chrome_WidgetWin_1".to_owned(),
+            process_id: 2,
+            alpha: Some(220),
+            ..Default::default()
+        });
+
+        let mut config = Config::new();
+        let editor_key = add_rule(&mut config, "editor", "Chrome_WidgetWin_1", 100);
+        let browser_key = add_rule(&mut config, "browser", "Chrome_WidgetWin_1", 150);
+        let mut cache = new_cache();
+        apply_rules(&backend, &mut config, &mut cache);
+        assert_eq!(alpha(&backend, editor), Some(100));
+        assert_eq!(alpha(&backend, browser), Some(150));
+
+        // Each goes back to what it had, not to what the other one had
+        config.get_windows().remove(&editor_key);
+        apply_rules(&backend, &mut config, &mut cache);
+        assert_eq!(alpha(&backend, editor), Some(200));
+        assert_eq!(alpha(&backend, browser), Some(150));
+
+        config.get_windows().remove(&browser_key);
+        apply_rules(&backend, &mut config, &mut cache);
+        assert_eq!(alpha(&backend, browser), Some(220));
+    }
+
+    // A window with the rule applied at 128, as a refresh leaves it
+    fn applied_state(backend: &MemoryBackend, originals: &mut OriginalStates) -> WindowHandleState {
+        backend.add_process(1, "app", false);
+        let window = add_window(backend, 1, "Main");
+
+        let mut state = WindowHandleState::new(window, window);
+        state.update_window(backend, originals, 128, true);
+        assert_eq!(alpha(backend, window), Some(128));
+        state
+    }
+
+    // What an app resetting its own alpha looks like
+    fn reset_alpha(backend: &MemoryBackend, window: WindowHandle) {
+        backend.update_window(window, |window| window.alpha = Some(255));
+    }
+
+    #[test]
+    fn drift_is_reapplied_with_doubling_backoff() {
+        let backend = MemoryBackend::new();
+        let mut originals = OriginalStates::new();
+        let mut state = applied_state(&backend, &mut originals);
+        let window = state.get_handle();
+        let start = Instant::now();
+
+        reset_alpha(&backend, window);
+        state.check_drift(&backend, &mut originals, start);
+        assert_eq!(alpha(&backend, window), Some(128));
+        assert_eq!(state.retry_at, Some(start + DRIFT_BACKOFF));
+
+        // Changed back again before the backoff is up, left alone until it is
+        reset_alpha(&backend, window);
+        state.check_drift(&backend, &mut originals, start + Duration::from_millis(500));
+        assert_eq!(alpha(&backend, window), Some(255));
+
+        let second = start + DRIFT_BACKOFF;
+        state.check_drift(&backend, &mut originals, second);
+        assert_eq!(alpha(&backend, window), Some(128));
+        assert_eq!(state.drift_count, 2);
+        assert_eq!(state.retry_at, Some(second + DRIFT_BACKOFF * 2));
+
+        reset_alpha(&backend, window);
+        state.check_drift(&backend, &mut originals, second + DRIFT_BACKOFF);
+        assert_eq!(alpha(&backend, window), Some(255));
+        state.check_drift(&backend, &mut originals, second + DRIFT_BACKOFF * 2);
+        assert_eq!(alpha(&backend, window), Some(128));
+        assert_eq!(state.drift_count, 3);
+    }
+
+    #[test]
+    fn drift_backoff_stops_at_the_max() {
+        let backend = MemoryBackend::new();
+        let mut originals = OriginalStates::new();
+        let mut state = applied_state(&backend, &mut originals);
+        let now = Instant::now();
+
+        state.drift_count = 40;
+        reset_alpha(&backend, state.get_handle());
+        state.check_drift(&backend, &mut originals, now);
+
+        assert_eq!(state.retry_at, Some(now + DRIFT_BACKOFF_MAX));
+    }
+
+    #[test]
+    fn drift_is_forgotten_after_a_quiet_while() {
+        let backend = MemoryBackend::new();
+        let mut originals = OriginalStates::new();
+        let mut state = applied_state(&backend, &mut originals);
+        let start = Instant::now();
+
+        reset_alpha(&backend, state.get_handle());
+        state.check_drift(&backend, &mut originals, start);
+        assert_eq!(state.drift_count, 1);
+
+        // Left alone but not for long enough
+        state.check_drift(&backend, &mut originals, start + DRIFT_FORGET / 2);
+        assert_eq!(state.drift_count, 1);
+
+        state.check_drift(&backend, &mut originals, start + DRIFT_FORGET);
+        assert_eq!(state.drift_count, 0);
+        assert_eq!(state.last_drift, None);
+
+        // The next drift starts over at the shortest backoff
+        let later = start + DRIFT_FORGET * 2;
+        reset_alpha(&backend, state.get_handle());
+        state.check_drift(&backend, &mut originals, later);
+        assert_eq!(state.retry_at, Some(later + DRIFT_BACKOFF));
+    }
+
+    #[test]
+    fn hidden_window_is_left_alone_by_events_and_refreshes() {
+        let backend = MemoryBackend::new();
+        backend.add_process(1, "app", false);
+        let window = backend.add_window(MemoryWindow {
+            class_name: "Main".to_owned(),
+            process_id: 1,
+            hidden: true,
+            ..Default::default()
+        });
+
+        let mut config = Config::new();
+        add_rule(&mut config, "app", "Main", 128);
+        let mut cache = new_cache();
+
+        // Events for a hidden window used to apply the rule and the next refresh took it off again
+        for _ in 0..3 {
+            apply_rules(&backend, &mut config, &mut cache);
+            apply_window_event(&backend, &config, &mut cache, WindowEvent::Changed(window));
+        }
+        assert!(backend.alpha_writes().is_empty());
+
+        // Shown it gets the rule, hidden again it goes back, whichever path notices first
+        backend.update_window(window, |window| window.hidden = false);
+        apply_window_event(&backend, &config, &mut cache, WindowEvent::Changed(window));
+        assert_eq!(alpha(&backend, window), Some(128));
+
+        backend.update_window(window, |window| window.hidden = true);
+        apply_window_event(&backend, &config, &mut cache, WindowEvent::Changed(window));
+        assert_eq!(alpha(&backend, window), None);
+
+        backend.clear_alpha_writes();
+        apply_rules(&backend, &mut config, &mut cache);
+        assert!(backend.alpha_writes().is_empty());
+    }
+
+    // A rule that is 200 with the focus and 100 without it
+    fn add_inactive_rule(config: &mut Config, process_name: &str) {
+        let key = add_rule(config, process_name, "Main", 200);
+        config
+            .get_windows()
+            .get_mut(&key)
+            .expect("The rule was just added")
+            .set_inactive_transparency(Some(100));
+    }
+
+    fn sorted_writes(backend: &MemoryBackend) -> Vec<(WindowHandle, u8)> {
+        let mut writes = backend.alpha_writes();
+        writes.sort();
+        writes
+    }
+
+    #[test]
+    fn inactive_alpha_follows_the_focus() {
+        let backend = MemoryBackend::new();
+        backend.add_process(1, "app", false);
+        let first = add_window(&backend, 1, "Main");
+        let second = add_window(&backend, 1, "Main");
+        backend.set_foreground(Some(first));
+
+        let mut config = Config::new();
+        add_inactive_rule(&mut config, "app");
+        let mut cache = new_cache();
+        apply_rules(&backend, &mut config, &mut cache);
+        assert_eq!(alpha(&backend, first), Some(200));
+        assert_eq!(alpha(&backend, second), Some(100));
+
+        backend.set_foreground(Some(second));
+        apply_window_event(
+            &backend,
+            &config,
+            &mut cache,
+            WindowEvent::Foreground(Some(second)),
+        );
+        assert_eq!(alpha(&backend, first), Some(100));
+        assert_eq!(alpha(&backend, second), Some(200));
+
+        // The desktop taking the focus leaves every window unfocused
+        backend.set_foreground(None);
+        apply_window_event(&backend, &config, &mut cache, WindowEvent::Foreground(None));
+        assert_eq!(alpha(&backend, first), Some(100));
+        assert_eq!(alpha(&backend, second), Some(100));
+
+        // A refresh agrees with what the focus events left
+        backend.clear_alpha_writes();
+        apply_rules(&backend, &mut config, &mut cache);
+        assert!(backend.alpha_writes().is_empty());
+    }
+
+    #[test]
+    fn focus_switch_only_updates_the_windows_involved() {
+        let backend = MemoryBackend::new();
+        backend.add_process(1, "app", false);
+        backend.add_process(2, "plain", false);
+        let first = add_window(&backend, 1, "Main");
+        let second = add_window(&backend, 1, "Main");
+        let bystander = add_window(&backend, 1, "Main");
+        let plain = add_window(&backend, 2, "Main");
+        backend.set_foreground(Some(first));
+
+        let mut config = Config::new();
+        add_inactive_rule(&mut config, "app");
+        add_rule(&mut config, "plain", "Main", 150);
+        let mut cache = new_cache();
+        apply_rules(&backend, &mut config, &mut cache);
+        backend.clear_alpha_writes();
+
+        apply_window_event(
+            &backend,
+            &config,
+            &mut cache,
+            WindowEvent::Foreground(Some(second)),
+        );
+        let mut expected = vec![(first, 100), (second, 200)];
+        expected.sort();
+        assert_eq!(sorted_writes(&backend), expected);
+
+        // A rule without an inactive alpha does not care where the focus is
+        backend.clear_alpha_writes();
+        apply_window_event(
+            &backend,
+            &config,
+            &mut cache,
+            WindowEvent::Foreground(Some(plain)),
+        );
+        assert_eq!(sorted_writes(&backend), vec![(second, 100)]);
+        assert_eq!(alpha(&backend, plain), Some(150));
+        assert_eq!(alpha(&backend, bystander), Some(100));
+
+        // The same window reported again changes nothing
+        backend.clear_alpha_writes();
+        apply_window_event(
+            &backend,
+            &config,
+            &mut cache,
+            WindowEvent::Foreground(Some(plain)),
+        );
+        assert!(backend.alpha_writes().is_empty());
+    }
+
+    #[test]
+    fn new_windows_start_with_the_alpha_for_their_focus() {
+        let backend = MemoryBackend::new();
+        backend.add_process(1, "app", false);
+        let focused = add_window(&backend, 1, "Main");
+        backend.set_foreground(Some(focused));
+
+        let mut config = Config::new();
+        add_inactive_rule(&mut config, "app");
+        let mut cache = new_cache();
+        apply_rules(&backend, &mut config, &mut cache);
+
+        // Opened behind the focused window, then focused
+        let opened = add_window(&backend, 1, "Main");
+        apply_window_event(&backend, &config, &mut cache, WindowEvent::Created(opened));
+        assert_eq!(alpha(&backend, opened), Some(100));
+
+        apply_window_event(
+            &backend,
+            &config,
+            &mut cache,
+            WindowEvent::Foreground(Some(opened)),
+        );
+        assert_eq!(alpha(&backend, opened), Some(200));
+        assert_eq!(alpha(&backend, focused), Some(100));
+    }
+
+    const LEFT_SCREEN: (i32, i32, i32, i32) = (0, 0, 1920, 1080);
+    const RIGHT_SCREEN: (i32, i32, i32, i32) = (1920, 0, 3840, 1080);
+
+    // A primary monitor on the left and a second one to its right
+    fn side_by_side(backend: &MemoryBackend, primary_on_left: bool) {
+        backend.set_monitors(vec![
+            MonitorInfo {
+                index: 0,
+                name: "DP-1".to_owned(),
+                primary: primary_on_left,
+                rect: LEFT_SCREEN,
+            },
+            MonitorInfo {
+                index: 0,
+                name: "HDMI-1".to_owned(),
+                primary: !primary_on_left,
+                rect: RIGHT_SCREEN,
+            },
+        ]);
+    }
+
+    fn add_window_at(
+        backend: &MemoryBackend,
+        process_id: u32,
+        rect: (i32, i32, i32, i32),
+    ) -> WindowHandle {
+        backend.add_window(MemoryWindow {
+            class_name: "Main".to_owned(),
+            process_id,
+            rect,
+            ..Default::default()
+        })
+    }
+
+    fn add_monitor_rule(config: &mut Config, monitor: MonitorMatch) {
+        let key = add_rule(config, "app", "Main", 128);
+        config
+            .get_windows()
+            .get_mut(&key)
+            .expect("The rule was just added")
+            .set_monitor(Some(monitor));
+    }
+
+    #[test]
+    fn simulated_monitors_are_indexed_in_order() {
+        let backend = MemoryBackend::new();
+        side_by_side(&backend, true);
+
+        let monitors = backend.get_monitors();
+        assert_eq!(
+            monitors
+                .iter()
+                .map(|monitor| (monitor.index, monitor.name.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(0, "DP-1"), (1, "HDMI-1")]
+        );
+
+        backend.add_process(1, "app", false);
+        let right = add_window_at(&backend, 1, (2000, 100, 2400, 500));
+        let offscreen = add_window_at(&backend, 1, (-500, -500, -100, -100));
+
+        assert_eq!(
+            backend
+                .get_window_monitor(right)
+                .map(|monitor| monitor.index),
+            Some(1)
+        );
+        assert_eq!(backend.get_window_monitor(offscreen), None);
+    }
+
+    #[test]
+    fn monitor_rule_only_applies_on_its_monitor() {
+        let backend = MemoryBackend::new();
+        side_by_side(&backend, true);
+        backend.add_process(1, "app", false);
+        let primary = add_window_at(&backend, 1, (100, 100, 500, 500));
+        let secondary = add_window_at(&backend, 1, (2000, 100, 2400, 500));
+
+        for monitor in [
+            MonitorMatch::Secondary,
+            MonitorMatch::Index(1),
+            MonitorMatch::Name("hdmi-1".to_owned()),
+        ] {
+            let mut config = Config::new();
+            add_monitor_rule(&mut config, monitor.clone());
+            let mut cache = new_cache();
+            apply_rules(&backend, &mut config, &mut cache);
+
+            assert_eq!(alpha(&backend, primary), None, "{}", monitor);
+            assert_eq!(alpha(&backend, secondary), Some(128), "{}", monitor);
+
+            config.get_windows().clear();
+            apply_rules(&backend, &mut config, &mut cache);
+        }
+    }
+
+    #[test]
+    fn window_moved_to_another_monitor_is_reevaluated() {
+        let backend = MemoryBackend::new();
+        side_by_side(&backend, true);
+        backend.add_process(1, "app", false);
+        let window = add_window_at(&backend, 1, (100, 100, 500, 500));
+
+        let mut config = Config::new();
+        add_monitor_rule(&mut config, MonitorMatch::Secondary);
+        let mut cache = new_cache();
+        apply_rules(&backend, &mut config, &mut cache);
+        assert_eq!(alpha(&backend, window), None);
+
+        backend.update_window(window, |window| window.rect = (2000, 100, 2400, 500));
+        apply_window_event(&backend, &config, &mut cache, WindowEvent::Changed(window));
+        assert_eq!(alpha(&backend, window), Some(128));
+
+        backend.update_window(window, |window| window.rect = (100, 100, 500, 500));
+        apply_window_event(&backend, &config, &mut cache, WindowEvent::Changed(window));
+        assert_eq!(alpha(&backend, window), None);
+    }
+
+    #[test]
+    fn new_monitor_layout_is_reevaluated() {
+        let backend = MemoryBackend::new();
+        side_by_side(&backend, true);
+        backend.add_process(1, "app", false);
+        let left = add_window_at(&backend, 1, (100, 100, 500, 500));
+        let right = add_window_at(&backend, 1, (2000, 100, 2400, 500));
+
+        let mut config = Config::new();
+        add_monitor_rule(&mut config, MonitorMatch::Primary);
+        let mut cache = new_cache();
+        apply_rules(&backend, &mut config, &mut cache);
+        assert_eq!(alpha(&backend, left), Some(128));
+        assert_eq!(alpha(&backend, right), None);
+
+        // The primary display moved to the right, neither window did
+        side_by_side(&backend, false);
+        for window in [left, right] {
+            apply_window_event(&backend, &config, &mut cache, WindowEvent::Changed(window));
+        }
+        assert_eq!(alpha(&backend, left), None);
+        assert_eq!(alpha(&backend, right), Some(128));
+
+        // Unplugged, the window on it is on no monitor and no longer matches
+        backend.set_monitors(vec![MonitorInfo {
+            index: 0,
+            name: "DP-1".to_owned(),
+            primary: true,
+            rect: LEFT_SCREEN,
+        }]);
+        apply_rules(&backend, &mut config, &mut cache);
+        assert_eq!(alpha(&backend, left), Some(128));
+        assert_eq!(alpha(&backend, right), None);
+    }
+
+    #[tokio::test]
+    async fn shutting_down_restores_the_original_alpha() {
+        let backend = Arc::new(MemoryBackend::new());
+        backend.add_process(1, "app", false);
+        let window = add_window(&backend, 1, "Main");
+
+        let mut config = Config::new();
+        add_rule(&mut config, "app", "Main", 128);
+        let config_path = temp_config_path("shutdown");
+        let app_state = Arc::new(AppState::new(config, config_path.clone(), backend.clone()));
+
+        let monitor = tokio::spawn(monitor_windows(Arc::clone(&app_state)));
+        timeout(Duration::from_secs(5), async {
+            while alpha(&backend, window) != Some(128) {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .expect("The rule was never applied");
+
+        app_state.quit().await;
+        monitor.await.expect("The monitor panicked");
+
+        assert_eq!(alpha(&backend, window), None);
+        assert!(!app_state.get_journal_path().exists());
+        _ = fs::remove_dir_all(config_path.parent().expect("Has a parent"));
+    }
+
+    #[test]
+    fn renamed_window_gets_and_loses_a_title_rule() {
+        let backend = MemoryBackend::new();
+        backend.add_process(1, "browser", false);
+        let window = backend.add_window(MemoryWindow {
+            class_name: "Main".to_owned(),
+            title: "Home".to_owned(),
+            process_id: 1,
+            ..Default::default()
+        });
+
+        let mut config = Config::new();
+        let mut private = WindowConfig::new(
+            &WindowInfo {
+                class_name: "Main".to_owned(),
+                process_name: "browser".to_owned(),
+                ..Default::default()
+            },
+            128,
+        );
+        private.set_window_title(Some("Private".to_owned()));
+        private.set_title_match(MatchMode::Substring);
+        config.get_windows().insert(private.get_key(), private);
+        let mut cache = new_cache();
+
+        apply_rules(&backend, &mut config, &mut cache);
+        assert_eq!(alpha(&backend, window), None);
+
+        backend.update_window(window, |window| {
+            window.title = "Private browsing".to_owned()
+        });
+        apply_window_event(&backend, &config, &mut cache, WindowEvent::Changed(window));
+        assert_eq!(alpha(&backend, window), Some(128));
+
+        backend.update_window(window, |window| window.title = "Home".to_owned());
+        apply_window_event(&backend, &config, &mut cache, WindowEvent::Changed(window));
+        assert_eq!(alpha(&backend, window), None);
+    }
+
+    fn exclusion_for(process_name: &str, class_name: &str) -> WindowConfig {
+        let mut exclusion = WindowConfig::new(
+            &WindowInfo {
+                class_name: class_name.to_owned(),
+                process_name: process_name.to_owned(),
+                ..Default::default()
+            },
+            255,
+        );
+        exclusion.set_exclude(true);
+        exclusion
+    }
+
+    #[test]
+    fn exclusion_beats_a_rule_with_a_higher_priority() {
+        let backend = MemoryBackend::new();
+        backend.add_process(1, "app", false);
+        let window = add_window(&backend, 1, "Main");
+
+        let mut config = Config::new();
+        let key = add_rule(&mut config, "app", "Main", 128);
+        config
+            .get_windows()
+            .get_mut(&key)
+            .expect("Rule was added")
+            .set_priority(10);
+        let mut exclusion = exclusion_for("app", "Main");
+        exclusion.set_priority(-5);
+        config.insert_rule(exclusion);
+        let mut cache = new_cache();
+
+        apply_rules(&backend, &mut config, &mut cache);
+        apply_window_event(&backend, &config, &mut cache, WindowEvent::Changed(window));
+
+        assert_eq!(config.get_windows().len(), 2);
+        assert!(backend.alpha_writes().is_empty());
+        assert_eq!(alpha(&backend, window), None);
+    }
+
+    #[test]
+    fn excluding_a_transparent_window_restores_it() {
+        let backend = MemoryBackend::new();
+        backend.add_process(1, "app", false);
+        let window = add_window(&backend, 1, "Main");
+
+        let mut config = Config::new();
+        let key = add_rule(&mut config, "app", "Main", 128);
+        let mut cache = new_cache();
+        apply_rules(&backend, &mut config, &mut cache);
+        assert_eq!(alpha(&backend, window), Some(128));
+
+        // The exclusion is saved next to the rule instead of over it
+        config.insert_rule(exclusion_for("app", "Main"));
+        apply_rules(&backend, &mut config, &mut cache);
+
+        assert!(config.get_windows().contains_key(&key));
+        assert_eq!(alpha(&backend, window), None);
+
+        // Taking the exclusion away again brings the rule back
+        config.get_windows().retain(|rule_key, _| *rule_key == key);
+        apply_rules(&backend, &mut config, &mut cache);
+        assert_eq!(alpha(&backend, window), Some(128));
+    }
+
+    #[test]
+    fn dialog_only_rule_skips_the_main_window() {
+        let backend = MemoryBackend::new();
+        backend.add_process(1, "app", false);
+        let main = add_window(&backend, 1, "AppWindow");
+        let dialog = backend.add_window(MemoryWindow {
+            class_name: "AppWindow".to_owned(),
+            process_id: 1,
+            role: WindowRole::Dialog,
+            ..Default::default()
+        });
+
+        let mut config = Config::new();
+        let key = add_rule(&mut config, "app", "AppWindow", 128);
+        let mut rule = config.get_windows().remove(&key).expect("Rule was added");
+        rule.set_roles(vec![WindowRole::Dialog]);
+        config.insert_rule(rule);
+        let mut cache = new_cache();
+
+        apply_rules(&backend, &mut config, &mut cache);
+        assert_eq!(alpha(&backend, dialog), Some(128));
+        assert_eq!(alpha(&backend, main), None);
+
+        // Events go through the same check
+        apply_window_event(&backend, &config, &mut cache, WindowEvent::Changed(main));
+        assert_eq!(alpha(&backend, main), None);
+    }
+}
@@ -0,0 +1,68 @@
+use crate::backend::MonitorInfo;
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/*
+  Which monitor a window has to be on for a rule to apply.
+  Indexes follow the order the backend lists monitors in and can shift when a display is plugged in, device names do not.
+*/
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum MonitorMatch {
+    Primary,
+    Secondary,
+    Index(usize),
+    Name(String),
+}
+
+impl MonitorMatch {
+    pub fn matches(&self, monitor: &MonitorInfo) -> bool {
+        match self {
+            MonitorMatch::Primary => monitor.primary,
+            MonitorMatch::Secondary => !monitor.primary,
+            MonitorMatch::Index(index) => monitor.index == *index,
+            MonitorMatch::Name(name) => monitor.name.eq_ignore_ascii_case(name),
+        }
+    }
+
+    /*
+      What the rules window shows for the monitor.
+    */
+    pub fn describe(&self) -> String {
+        match self {
+            MonitorMatch::Primary => "Primary monitor".to_owned(),
+            MonitorMatch::Secondary => "Not the primary monitor".to_owned(),
+            MonitorMatch::Index(index) => format!("Monitor {}", index + 1),
+            MonitorMatch::Name(name) => format!("Monitor {}", name),
+        }
+    }
+}
+
+/*
+  Written as `primary`, `secondary`, `index:<n>` or `name:<device>`, so the UI can carry a monitor around as text.
+*/
+impl fmt::Display for MonitorMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MonitorMatch::Primary => f.write_str("primary"),
+            MonitorMatch::Secondary => f.write_str("secondary"),
+            MonitorMatch::Index(index) => write!(f, "index:{}", index),
+            MonitorMatch::Name(name) => write!(f, "name:{}", name),
+        }
+    }
+}
+
+impl FromStr for MonitorMatch {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        // Device names have backslashes and colons of their own, only the first colon splits
+        match value.split_once(':') {
+            None if value == "primary" => Ok(MonitorMatch::Primary),
+            None if value == "secondary" => Ok(MonitorMatch::Secondary),
+            Some(("index", index)) => Ok(MonitorMatch::Index(index.parse()?)),
+            Some(("name", name)) if !name.is_empty() => Ok(MonitorMatch::Name(name.to_owned())),
+            _ => Err(anyhow::anyhow!("Unknown monitor: {}", value)),
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use crate::{
+    backend::{OriginalWindowState, WindowBackend, WindowHandle},
+    journal::{Journal, JournalEntry},
+};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    path::PathBuf,
+};
+
+/*
+  The state of every window WinAlpha has changed, as it was before the first change.
+  All alpha writes of the monitor go through here so nothing gets touched without being recorded first.
+  With a journal every record is also kept on disk, so a crash can be undone by the next run.
+*/
+#[derive(Default, Debug)]
+pub struct OriginalStates {
+    windows: HashMap<WindowHandle, OriginalWindowState>,
+    journal: Option<Journal>,
+}
+
+impl OriginalStates {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_journal(journal_path: PathBuf) -> Self {
+        Self {
+            windows: HashMap::new(),
+            journal: Some(Journal::new(journal_path)),
+        }
+    }
+
+    /*
+      Records the window the first time it is touched, then sets the alpha.
+    */
+    pub fn set_alpha(&mut self, backend: &dyn WindowBackend, window: WindowHandle, alpha: u8) {
+        if let Entry::Vacant(entry) = self.windows.entry(window) {
+            let Some(original) = backend.capture_window_state(window) else {
+                eprintln!(
+                    "Failed to read the state of window {:#x}, leaving it alone",
+                    window
+                );
+                return;
+            };
+            entry.insert(original);
+
+            if let Some(journal) = self.journal.as_mut() {
+                journal.record(JournalEntry::new(backend, window, original));
+            }
+        }
+
+        backend.set_window_alpha(window, alpha).ok();
+    }
+
+    /*
+      Puts the window back the way it was, does nothing for windows that were never touched.
+    */
+    pub fn restore(&mut self, backend: &dyn WindowBackend, window: WindowHandle) {
+        let result = self
+            .windows
+            .remove(&window)
+            .map(|original| backend.restore_window_state(window, &original));
+        if let Some(Err(e)) = result {
+            eprintln!("Failed to restore window {:#x}: {}", window, e);
+        }
+
+        if let Some(journal) = self.journal.as_mut() {
+            journal.remove(window);
+        }
+    }
+
+    pub fn restore_all(&mut self, backend: &dyn WindowBackend) {
+        let windows: Vec<WindowHandle> = self.windows.keys().copied().collect();
+
+        for window in windows {
+            self.restore(backend, window);
+        }
+    }
+
+    /*
+      Drops the record of a window that no longer exists.
+    */
+    pub fn forget(&mut self, window: WindowHandle) {
+        self.windows.remove(&window);
+
+        if let Some(journal) = self.journal.as_mut() {
+            journal.remove(window);
+        }
+    }
+
+    pub fn get_windows(&self) -> impl Iterator<Item = WindowHandle> + '_ {
+        self.windows.keys().copied()
+    }
+}
@@ -0,0 +1,318 @@
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt,
+    str::FromStr,
+    sync::{LazyLock, Mutex},
+};
+
+/*
+  How a rule compares its process name, window class or title against a window.
+  Substring only has to be found in the name. Glob has `*` and `?` and has to match the whole name, a regex only has to be found somewhere in it (anchor it with ^ and $).
+*/
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchMode {
+    #[default]
+    Exact,
+    Substring,
+    Glob,
+    Regex,
+}
+
+impl MatchMode {
+    pub fn is_exact(&self) -> bool {
+        *self == MatchMode::Exact
+    }
+
+    /*
+      How narrow a pattern in this mode is, higher is narrower. Used to pick between rules that both match.
+    */
+    pub fn specificity(&self) -> u8 {
+        match self {
+            MatchMode::Exact => 3,
+            MatchMode::Glob => 2,
+            MatchMode::Substring | MatchMode::Regex => 1,
+        }
+    }
+}
+
+impl fmt::Display for MatchMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            MatchMode::Exact => "exact",
+            MatchMode::Substring => "substring",
+            MatchMode::Glob => "glob",
+            MatchMode::Regex => "regex",
+        })
+    }
+}
+
+impl FromStr for MatchMode {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "exact" => Ok(MatchMode::Exact),
+            "substring" => Ok(MatchMode::Substring),
+            "glob" => Ok(MatchMode::Glob),
+            "regex" => Ok(MatchMode::Regex),
+            _ => Err(anyhow::anyhow!("Unknown match mode: {}", value)),
+        }
+    }
+}
+
+// Keyed by mode, case insensitivity and the pattern. Patterns that do not compile are kept as None so they are only reported once.
+type CompiledPatterns = HashMap<(MatchMode, bool, String), Option<Regex>>;
+
+// Every glob and regex a rule has used, compiled once.
+static COMPILED: LazyLock<Mutex<CompiledPatterns>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/*
+  Returns if the value matches the pattern in the mode. A pattern that does not compile matches nothing.
+*/
+pub fn matches_pattern(mode: MatchMode, pattern: &str, value: &str) -> bool {
+    match mode {
+        MatchMode::Exact => pattern == value,
+        MatchMode::Substring => value.contains(pattern),
+        MatchMode::Glob | MatchMode::Regex => {
+            get_compiled(mode, false, pattern).is_some_and(|regex| regex.is_match(value))
+        }
+    }
+}
+
+/*
+  Same as matches_pattern, ignoring case. Uses Unicode lowercasing, so `É` and `é` are the same.
+*/
+pub fn matches_pattern_ignore_case(mode: MatchMode, pattern: &str, value: &str) -> bool {
+    match mode {
+        MatchMode::Exact => pattern.to_lowercase() == value.to_lowercase(),
+        MatchMode::Substring => value.to_lowercase().contains(&pattern.to_lowercase()),
+        MatchMode::Glob | MatchMode::Regex => {
+            get_compiled(mode, true, pattern).is_some_and(|regex| regex.is_match(value))
+        }
+    }
+}
+
+fn get_compiled(mode: MatchMode, ignore_case: bool, pattern: &str) -> Option<Regex> {
+    let mut compiled = COMPILED
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    let key = (mode, ignore_case, pattern.to_owned());
+    if let Some(regex) = compiled.get(&key) {
+        return regex.clone();
+    }
+
+    let source = match mode {
+        MatchMode::Glob => glob_to_regex(pattern),
+        _ => pattern.to_owned(),
+    };
+    let regex = match RegexBuilder::new(&source)
+        .case_insensitive(ignore_case)
+        .build()
+    {
+        Ok(regex) => Some(regex),
+        Err(e) => {
+            eprintln!(
+                "Invalid {} pattern {:?}, it will match nothing: {}",
+                mode, pattern, e
+            );
+            None
+        }
+    };
+
+    compiled.insert(key, regex.clone());
+    regex
+}
+
+/*
+  Translates a glob into an anchored regex.
+  Only `*` and `?` are wildcards, brackets are literal since WPF class names are full of them (HwndWrapper[App;;<guid>]).
+*/
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+
+    for c in glob.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            _ => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+
+    regex.push('$');
+    regex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exact_needs_the_whole_name() {
+        assert!(matches_pattern(MatchMode::Exact, "Notepad", "Notepad"));
+        assert!(!matches_pattern(MatchMode::Exact, "Notepad", "Notepad++"));
+        assert!(!matches_pattern(MatchMode::Exact, "Notepad", "notepad"));
+        assert!(matches_pattern_ignore_case(
+            MatchMode::Exact,
+            "Notepad",
+            "NOTEPAD"
+        ));
+    }
+
+    #[test]
+    fn substring_is_found_anywhere() {
+        assert!(matches_pattern(
+            MatchMode::Substring,
+            "Chrome",
+            "Chrome_WidgetWin_1"
+        ));
+        assert!(matches_pattern(
+            MatchMode::Substring,
+            "Widget",
+            "Chrome_WidgetWin_1"
+        ));
+        assert!(!matches_pattern(
+            MatchMode::Substring,
+            "widget",
+            "Chrome_WidgetWin_1"
+        ));
+        assert!(matches_pattern_ignore_case(
+            MatchMode::Substring,
+            "widget",
+            "Chrome_WidgetWin_1"
+        ));
+        // Unicode lowercasing, not just ASCII
+        assert!(matches_pattern_ignore_case(
+            MatchMode::Substring,
+            "ÉTÉ",
+            "Photos d'été"
+        ));
+    }
+
+    #[test]
+    fn glob_matches_the_whole_name_with_wildcards() {
+        assert!(matches_pattern(
+            MatchMode::Glob,
+            "Chrome_*",
+            "Chrome_WidgetWin_1"
+        ));
+        assert!(matches_pattern(
+            MatchMode::Glob,
+            "Chrome_WidgetWin_?",
+            "Chrome_WidgetWin_1"
+        ));
+        assert!(!matches_pattern(
+            MatchMode::Glob,
+            "Chrome_WidgetWin_?",
+            "Chrome_WidgetWin_10"
+        ));
+        assert!(!matches_pattern(
+            MatchMode::Glob,
+            "Widget*",
+            "Chrome_WidgetWin_1"
+        ));
+        assert!(!matches_pattern(
+            MatchMode::Glob,
+            "chrome_*",
+            "Chrome_WidgetWin_1"
+        ));
+        assert!(matches_pattern_ignore_case(
+            MatchMode::Glob,
+            "chrome_*",
+            "Chrome_WidgetWin_1"
+        ));
+    }
+
+    #[test]
+    fn glob_brackets_and_dots_are_literal() {
+        let class_name = "HwndWrapper[App.exe;;1234-abcd]";
+
+        assert!(matches_pattern(
+            MatchMode::Glob,
+            "HwndWrapper[App.exe;;*]",
+            class_name
+        ));
+        assert!(matches_pattern(
+            MatchMode::Glob,
+            "HwndWrapper[App?exe;;*]",
+            class_name
+        ));
+        assert!(!matches_pattern(
+            MatchMode::Glob,
+            "HwndWrapper[A]*",
+            class_name
+        ));
+        assert!(!matches_pattern(MatchMode::Glob, "App.exe", "AppXexe"));
+    }
+
+    #[test]
+    fn regex_is_found_anywhere_unless_anchored() {
+        assert!(matches_pattern(
+            MatchMode::Regex,
+            r"Win_\d+",
+            "Chrome_WidgetWin_1"
+        ));
+        assert!(!matches_pattern(
+            MatchMode::Regex,
+            r"^Win_\d+$",
+            "Chrome_WidgetWin_1"
+        ));
+        assert!(matches_pattern(
+            MatchMode::Regex,
+            r"^Chrome_.*_\d$",
+            "Chrome_WidgetWin_1"
+        ));
+        assert!(matches_pattern_ignore_case(
+            MatchMode::Regex,
+            r"^chrome",
+            "Chrome_WidgetWin_1"
+        ));
+        assert!(!matches_pattern(
+            MatchMode::Regex,
+            r"^chrome",
+            "Chrome_WidgetWin_1"
+        ));
+    }
+
+    #[test]
+    fn invalid_regex_matches_nothing() {
+        assert!(!matches_pattern(MatchMode::Regex, "(unclosed", "(unclosed"));
+        assert!(!matches_pattern_ignore_case(
+            MatchMode::Regex,
+            "(unclosed",
+            ""
+        ));
+        // Asked for again it comes from the cache, still matching nothing
+        assert!(!matches_pattern(MatchMode::Regex, "(unclosed", "(unclosed"));
+    }
+
+    #[test]
+    fn mode_round_trips_as_text() {
+        for mode in [
+            MatchMode::Exact,
+            MatchMode::Substring,
+            MatchMode::Glob,
+            MatchMode::Regex,
+        ] {
+            assert_eq!(mode.to_string().parse::<MatchMode>().unwrap(), mode);
+        }
+        assert!("fuzzy".parse::<MatchMode>().is_err());
+        assert_eq!(
+            serde_json::from_str::<MatchMode>(r#""glob""#).unwrap(),
+            MatchMode::Glob
+        );
+    }
+
+    #[test]
+    fn narrower_modes_are_more_specific() {
+        assert!(MatchMode::Exact.specificity() > MatchMode::Glob.specificity());
+        assert!(MatchMode::Glob.specificity() > MatchMode::Substring.specificity());
+        assert_eq!(
+            MatchMode::Substring.specificity(),
+            MatchMode::Regex.specificity()
+        );
+    }
+}
@@ -0,0 +1,1028 @@
+use crate::{
+    backend::{MonitorInfo, ProcessIdentity, WindowBackend, WindowHandle, WindowRole},
+    hover::HoverSettings,
+    lifetime::RuleLifetime,
+    monitor_match::MonitorMatch,
+    pattern::{matches_pattern, matches_pattern_ignore_case, MatchMode},
+    window_index::WindowIndex,
+};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+const MINIMUM_TRANSPARENCY: i32 = 30;
+
+// Between the classes of a chain, spaces around it are ignored when reading one
+pub const CLASS_CHAIN_SEPARATOR: &str = " > ";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WindowInfo {
+    pub class_name: String,
+    pub process_name: String,
+    pub title: String,
+    pub process_id: u32,
+    // The monitor the top level window is on, None when the backend cannot tell
+    pub monitor: Option<MonitorInfo>,
+    // Classes of the windows above it up to the top level one, outermost first. Empty for top level windows.
+    pub parent_classes: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WindowConfig {
+    #[serde(default)]
+    process_name: String,
+    // A class, or a chain of classes from the top level window down (`ApplicationFrameWindow > Windows.UI.Core.CoreWindow`)
+    #[serde(default)]
+    window_class: String,
+    // How many windows up the chain the alpha goes, 0 is the matched window itself
+    #[serde(default)]
+    apply_to: usize,
+    #[serde(default)]
+    process_match: MatchMode,
+    #[serde(default)]
+    class_match: MatchMode,
+    // Only windows with a matching title, any title when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    window_title: Option<String>,
+    #[serde(default)]
+    title_match: MatchMode,
+    // Higher wins when more than one rule matches a window
+    #[serde(default)]
+    priority: i32,
+    #[serde(default)]
+    transparency: u8,
+    // Used while the window does not have the focus, None keeps the transparency above
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    inactive_transparency: Option<u8>,
+    // Opaque while the cursor is over the window, None leaves it alone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hover: Option<HoverSettings>,
+    #[serde(default)]
+    enabled: bool,
+    // The windows this matches are never touched, whatever other rules say
+    #[serde(default, skip_serializing_if = "core::ops::Not::not")]
+    exclude: bool,
+    #[serde(default, skip_serializing_if = "RuleLifetime::is_permanent")]
+    lifetime: RuleLifetime,
+    // The kinds of window the rule is for, empty is every kind
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    roles: Vec<WindowRole>,
+    // The monitor the window has to be on, None is any monitor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    monitor: Option<MonitorMatch>,
+    // Only read to migrate configs from before class chains, see migrate_forced
+    #[serde(default, skip_serializing)]
+    force: bool,
+    #[serde(default, skip_serializing)]
+    old_class: Option<String>,
+}
+
+impl WindowConfig {
+    /*
+      A window below a top level one gets a chain down to it, with the alpha on the top level window since that is the one that can be made transparent.
+    */
+    pub fn new(info: &WindowInfo, transparency: u8) -> Self {
+        let window_class = info
+            .parent_classes
+            .iter()
+            .chain(Some(&info.class_name))
+            .map(String::as_str)
+            .collect::<Vec<&str>>()
+            .join(CLASS_CHAIN_SEPARATOR);
+
+        Self {
+            process_name: info.process_name.to_owned(),
+            window_class,
+            apply_to: info.parent_classes.len(),
+            process_match: MatchMode::Exact,
+            class_match: MatchMode::Exact,
+            window_title: None,
+            title_match: MatchMode::Exact,
+            priority: 0,
+            transparency,
+            inactive_transparency: None,
+            hover: None,
+            enabled: true,
+            exclude: false,
+            lifetime: RuleLifetime::Permanent,
+            roles: Vec::new(),
+            monitor: None,
+            force: false,
+            old_class: None,
+        }
+    }
+
+    /*
+      Rules with a title get it in the key, so "only the private windows" can sit next to the rule for the whole app.
+      Patterns carry their mode (`glob:Chrome_*`), exact names are keyed as they are.
+      Exclusions start with `!`, excluding a window keeps the transparency rule it may already have.
+    */
+    pub fn get_key(&self) -> String {
+        let mut key = if self.exclude { "!" } else { "" }.to_owned()
+            + &with_mode(&self.process_name, self.process_match)
+            + "|"
+            + &with_mode(&self.window_class, self.class_match);
+
+        if let Some(title) = &self.window_title {
+            key = key + "|" + &with_mode(title, self.title_match);
+        }
+
+        if !self.roles.is_empty() {
+            key = key + "[" + &self.get_roles_string() + "]";
+        }
+
+        if let Some(monitor) = &self.monitor {
+            key = key + "@" + &monitor.to_string();
+        }
+
+        key
+    }
+
+    pub fn get_name(&self) -> String {
+        self.process_name.clone()
+    }
+
+    pub fn set_name(&mut self, new_process_name: String) {
+        self.process_name = new_process_name
+    }
+
+    pub fn get_transparency(&self) -> u8 {
+        self.transparency
+    }
+
+    pub fn set_transparency(&mut self, new_transparency: u8) {
+        self.transparency = new_transparency
+    }
+
+    pub fn get_inactive_transparency(&self) -> Option<u8> {
+        self.inactive_transparency
+    }
+
+    pub fn set_inactive_transparency(&mut self, new_transparency: Option<u8>) {
+        self.inactive_transparency = new_transparency
+    }
+
+    pub fn get_hover(&self) -> Option<HoverSettings> {
+        self.hover
+    }
+
+    pub fn set_hover(&mut self, new_hover: Option<HoverSettings>) {
+        self.hover = new_hover
+    }
+
+    /*
+      The transparency for a window with or without the focus.
+    */
+    pub fn get_transparency_for(&self, focused: bool) -> u8 {
+        match self.inactive_transparency {
+            Some(inactive) if !focused => inactive,
+            _ => self.transparency,
+        }
+    }
+
+    pub fn get_window_class(&self) -> &String {
+        &self.window_class
+    }
+
+    pub fn set_window_class(&mut self, new_class_name: &str) {
+        self.window_class = new_class_name.to_owned()
+    }
+
+    pub fn get_apply_to(&self) -> usize {
+        self.apply_to
+    }
+
+    pub fn set_apply_to(&mut self, new_apply_to: usize) {
+        self.apply_to = new_apply_to
+    }
+
+    /*
+      The classes from the top level window down to the matched window, a plain class is a chain of one.
+    */
+    pub fn get_class_chain(&self) -> Vec<&str> {
+        self.window_class.split('>').map(str::trim).collect()
+    }
+
+    pub fn is_class_chain(&self) -> bool {
+        self.window_class.contains('>')
+    }
+
+    /*
+      Turns a rule saved by the old force switch (parent class plus old_class) into a chain to the child with the alpha on the parent.
+      Returns if anything changed.
+    */
+    pub fn migrate_forced(&mut self) -> bool {
+        let Some(old_class) = self.old_class.take() else {
+            return false;
+        };
+
+        if self.force {
+            self.window_class =
+                [self.window_class.as_str(), &old_class].join(CLASS_CHAIN_SEPARATOR);
+            self.apply_to = 1;
+        }
+        self.force = false;
+
+        true
+    }
+
+    pub fn get_process_match(&self) -> MatchMode {
+        self.process_match
+    }
+
+    pub fn set_process_match(&mut self, new_mode: MatchMode) {
+        self.process_match = new_mode
+    }
+
+    pub fn get_class_match(&self) -> MatchMode {
+        self.class_match
+    }
+
+    pub fn set_class_match(&mut self, new_mode: MatchMode) {
+        self.class_match = new_mode
+    }
+
+    pub fn get_window_title(&self) -> &Option<String> {
+        &self.window_title
+    }
+
+    pub fn set_window_title(&mut self, new_title: Option<String>) {
+        self.window_title = new_title
+    }
+
+    pub fn get_title_match(&self) -> MatchMode {
+        self.title_match
+    }
+
+    pub fn set_title_match(&mut self, new_mode: MatchMode) {
+        self.title_match = new_mode
+    }
+
+    pub fn get_priority(&self) -> i32 {
+        self.priority
+    }
+
+    pub fn set_priority(&mut self, new_priority: i32) {
+        self.priority = new_priority
+    }
+
+    pub fn get_roles(&self) -> &Vec<WindowRole> {
+        &self.roles
+    }
+
+    /*
+      Sorted and without duplicates, so the same roles always make the same key.
+    */
+    pub fn set_roles(&mut self, mut new_roles: Vec<WindowRole>) {
+        new_roles.sort_by_key(|role| *role as u8);
+        new_roles.dedup();
+        self.roles = new_roles
+    }
+
+    /*
+      The roles as `main,dialog`, empty for every kind of window.
+    */
+    pub fn get_roles_string(&self) -> String {
+        self.roles
+            .iter()
+            .map(WindowRole::to_string)
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    pub fn matches_role(&self, backend: &dyn WindowBackend, handle: WindowHandle) -> bool {
+        self.roles.is_empty() || self.roles.contains(&backend.get_window_role(handle))
+    }
+
+    pub fn get_monitor(&self) -> Option<&MonitorMatch> {
+        self.monitor.as_ref()
+    }
+
+    pub fn set_monitor(&mut self, new_monitor: Option<MonitorMatch>) {
+        self.monitor = new_monitor
+    }
+
+    /*
+      A rule for a monitor matches nothing when the backend cannot tell which monitor the window is on.
+    */
+    pub fn matches_monitor(&self, backend: &dyn WindowBackend, handle: WindowHandle) -> bool {
+        self.monitor.as_ref().is_none_or(|monitor| {
+            backend
+                .get_window_monitor(handle)
+                .is_some_and(|window_monitor| monitor.matches(&window_monitor))
+        })
+    }
+
+    pub fn get_lifetime(&self) -> RuleLifetime {
+        self.lifetime
+    }
+
+    pub fn set_lifetime(&mut self, new_lifetime: RuleLifetime) {
+        self.lifetime = new_lifetime
+    }
+
+    /*
+      Title before roles before monitor before class before process, and within each an exact name before a pattern. No title is the least specific of all.
+    */
+    pub fn get_specificity(&self) -> (u8, u8, u8, u8, u8) {
+        (
+            self.window_title
+                .as_ref()
+                .map_or(0, |_| self.title_match.specificity()),
+            // Fewer roles is narrower, no roles at all is every window
+            match self.roles.len() {
+                0 => 0,
+                count => 5u8.saturating_sub(count as u8),
+            },
+            // Every monitor but the primary can be more than one
+            match self.monitor {
+                None => 0,
+                Some(MonitorMatch::Secondary) => 1,
+                Some(_) => 2,
+            },
+            // A longer chain is narrower than a shorter one in the same mode
+            self.class_match.specificity() * 16 + (self.get_class_chain().len() - 1).min(15) as u8,
+            // A path is narrower than a name in the same mode
+            self.process_match.specificity() * 2 + u8::from(self.is_process_path()),
+        )
+    }
+
+    /*
+      Orders rules so the one that should win comes first: exclusions, then priority, then specificity, then the key so ties are always broken the same way.
+    */
+    pub fn cmp_precedence(&self, other: &WindowConfig) -> Ordering {
+        self.cmp_precedence_with(self.priority, other, other.priority)
+    }
+
+    /*
+      Same as cmp_precedence with the priorities given, for a profile that moved the rules.
+    */
+    pub fn cmp_precedence_with(
+        &self,
+        priority: i32,
+        other: &WindowConfig,
+        other_priority: i32,
+    ) -> Ordering {
+        other
+            .is_active_exclusion()
+            .cmp(&self.is_active_exclusion())
+            .then_with(|| other_priority.cmp(&priority))
+            .then_with(|| other.get_specificity().cmp(&self.get_specificity()))
+            .then_with(|| self.get_key().cmp(&other.get_key()))
+    }
+
+    /*
+      Returns if the process name of the rule is a path, those are matched against the full executable path.
+    */
+    pub fn is_process_path(&self) -> bool {
+        self.process_name.contains(['\\', '/'])
+    }
+
+    /*
+      A path is compared to the full executable path, an exact path ending in `**` matches everything under that directory.
+      A name is compared to the file name, with or without the extension, so `slack` and `Slack.exe` both work.
+      Both ignore case.
+      Older versions saved names cut at the first dot (`my` for my.tool.exe), an exact name without a dot still matches that part.
+    */
+    pub fn matches_process(&self, process: &ProcessIdentity) -> bool {
+        let pattern = &self.process_name;
+
+        if self.is_process_path() {
+            let path = normalize_path(&process.path);
+
+            return match (self.process_match, pattern.strip_suffix("**")) {
+                (MatchMode::Exact, Some(prefix)) => path.starts_with(&normalize_path(prefix)),
+                (MatchMode::Exact, None) => path == normalize_path(pattern),
+                (mode, _) => matches_pattern_ignore_case(mode, pattern, &process.path),
+            };
+        }
+
+        let file_name = process
+            .path
+            .rsplit(['\\', '/'])
+            .next()
+            .filter(|file_name| !file_name.is_empty());
+
+        matches_pattern_ignore_case(self.process_match, pattern, &process.name)
+            || file_name.is_some_and(|file_name| {
+                matches_pattern_ignore_case(self.process_match, pattern, file_name)
+            })
+            || self.matches_cut_process_name(process)
+    }
+
+    fn matches_cut_process_name(&self, process: &ProcessIdentity) -> bool {
+        if !self.process_match.is_exact() || self.process_name.contains('.') {
+            return false;
+        }
+
+        process
+            .name
+            .split_once('.')
+            .is_some_and(|(cut_name, _)| cut_name.eq_ignore_ascii_case(&self.process_name))
+    }
+
+    /*
+      Returns if the class matches the top of the chain, the class of the top level window.
+    */
+    pub fn matches_class(&self, class_name: &str) -> bool {
+        self.get_class_chain()
+            .first()
+            .is_some_and(|pattern| matches_pattern(self.class_match, pattern, class_name))
+    }
+
+    /*
+      Titles change while the window is open, so they are read when matching instead of being indexed.
+    */
+    pub fn matches_title(&self, backend: &dyn WindowBackend, handle: WindowHandle) -> bool {
+        self.window_title.as_ref().is_none_or(|pattern| {
+            backend
+                .get_window_title(handle)
+                .is_some_and(|title| matches_pattern(self.title_match, pattern, &title))
+        })
+    }
+
+    pub fn set_enabled(&mut self, new_state: bool) {
+        self.enabled = new_state
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_exclude(&mut self, new_state: bool) {
+        self.exclude = new_state
+    }
+
+    pub fn is_exclude(&self) -> bool {
+        self.exclude
+    }
+
+    /*
+      An exclusion that is switched off does not protect anything.
+    */
+    pub fn is_active_exclusion(&self) -> bool {
+        self.exclude && self.enabled
+    }
+
+    /*
+      Returns all the current handles for the rule
+    */
+    pub fn get_window_hwnds(&self, backend: &dyn WindowBackend) -> Vec<WindowHandle> {
+        backend
+            .enumerate_windows()
+            .into_iter()
+            .flat_map(|root| self.get_targets(backend, root))
+            .collect()
+    }
+
+    /*
+      Returns (top level window, window to set the alpha of) for this rule out of an index built for the current refresh.
+    */
+    pub fn get_indexed_hwnds(
+        &self,
+        backend: &dyn WindowBackend,
+        index: &WindowIndex,
+    ) -> Vec<(WindowHandle, WindowHandle)> {
+        let mut targets = Vec::new();
+
+        for group in index
+            .iter()
+            .filter(|group| self.matches_class(&group.class_name))
+        {
+            for &root in &group.handles {
+                if self.is_class_chain() {
+                    targets.extend(
+                        self.get_chain_targets(backend, root)
+                            .into_iter()
+                            .map(|target| (root, target)),
+                    );
+                } else if self.matches_process(&group.process)
+                    && self.matches_title(backend, root)
+                    && self.matches_role(backend, root)
+                    && self.matches_monitor(backend, root)
+                {
+                    targets.push((root, root));
+                }
+            }
+        }
+
+        targets
+    }
+
+    /*
+      Returns the windows this rule sets the alpha of for one top level window, used when a single window changed.
+      Plain rules give the window itself, chains whatever is apply_to above the end of each chain.
+      Hidden windows give nothing, the same as in a full refresh which never sees them.
+    */
+    pub fn get_targets(
+        &self,
+        backend: &dyn WindowBackend,
+        root: WindowHandle,
+    ) -> Vec<WindowHandle> {
+        if !backend.is_top_level(root)
+            || !backend.is_window_visible(root)
+            || !backend
+                .get_window_class(root)
+                .is_some_and(|class_name| self.matches_class(&class_name))
+        {
+            return Vec::new();
+        }
+
+        if self.is_class_chain() {
+            return self.get_chain_targets(backend, root);
+        }
+
+        let matches = backend
+            .get_window_process_identity(root)
+            .is_some_and(|process| self.matches_process(&process))
+            && self.matches_title(backend, root)
+            && self.matches_role(backend, root)
+            && self.matches_monitor(backend, root);
+
+        if matches {
+            vec![root]
+        } else {
+            Vec::new()
+        }
+    }
+
+    /*
+      Walks down from a top level window that matches the top of the chain, one parent at a time.
+      The process and title are those of the window at the end of the chain, the one the rule was picked from.
+    */
+    fn get_chain_targets(
+        &self,
+        backend: &dyn WindowBackend,
+        root: WindowHandle,
+    ) -> Vec<WindowHandle> {
+        let chain = self.get_class_chain();
+        let descendants: Vec<(WindowHandle, Option<WindowHandle>, String)> = backend
+            .enumerate_child_windows(root)
+            .into_iter()
+            .filter_map(|child| {
+                let class_name = backend.get_window_class(child)?;
+                Some((child, backend.get_parent(child), class_name))
+            })
+            .collect();
+
+        let mut paths: Vec<Vec<WindowHandle>> = vec![vec![root]];
+
+        for pattern in &chain[1..] {
+            let mut next_paths = Vec::new();
+
+            for path in &paths {
+                let parent = path[path.len() - 1];
+
+                for (child, child_parent, class_name) in &descendants {
+                    if *child_parent == Some(parent)
+                        && matches_pattern(self.class_match, pattern, class_name)
+                    {
+                        let mut next_path = path.clone();
+                        next_path.push(*child);
+                        next_paths.push(next_path);
+                    }
+                }
+            }
+
+            paths = next_paths;
+        }
+
+        let mut targets = Vec::new();
+
+        for path in paths {
+            let matched = path[path.len() - 1];
+            let target = path[path.len() - 1 - self.apply_to.min(path.len() - 1)];
+
+            if !targets.contains(&target)
+                && backend
+                    .get_window_process_identity(matched)
+                    .is_some_and(|process| self.matches_process(&process))
+                && self.matches_title(backend, matched)
+                // Only top level windows have a role and a monitor
+                && self.matches_role(backend, root)
+                && self.matches_monitor(backend, root)
+            {
+                targets.push(target);
+            }
+        }
+
+        targets
+    }
+
+    /*
+      The monitor tracks windows per rule, so this is the full identity of the rule and not just the class.
+      Apps that share a class (every Electron app is Chrome_WidgetWin_1) each keep their own windows.
+    */
+    pub fn get_cache_key(&self) -> String {
+        format!(
+            "{}:{}|{}:{}@{}|{}:{}|{}|{}",
+            self.process_match,
+            self.process_name,
+            self.class_match,
+            self.window_class,
+            self.apply_to,
+            self.title_match,
+            self.window_title.as_deref().unwrap_or_default(),
+            self.get_roles_string(),
+            self.monitor
+                .as_ref()
+                .map(MonitorMatch::to_string)
+                .unwrap_or_default()
+        )
+    }
+}
+
+fn with_mode(pattern: &str, mode: MatchMode) -> String {
+    if mode.is_exact() {
+        pattern.to_owned()
+    } else {
+        format!("{}:{}", mode, pattern)
+    }
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            process_name: String::new(),
+            window_class: String::new(),
+            apply_to: 0,
+            process_match: MatchMode::Exact,
+            class_match: MatchMode::Exact,
+            window_title: None,
+            title_match: MatchMode::Exact,
+            priority: 0,
+            transparency: 255,
+            inactive_transparency: None,
+            hover: None,
+            enabled: false,
+            exclude: false,
+            lifetime: RuleLifetime::Permanent,
+            roles: Vec::new(),
+            monitor: None,
+            force: false,
+            old_class: None,
+        }
+    }
+}
+
+/*
+  Lowercases a path and makes every separator a backslash, so `C:/Tools` and `c:\tools` compare equal.
+*/
+fn normalize_path(path: &str) -> String {
+    path.to_lowercase().replace('/', "\\")
+}
+
+/*
+  Convert a value from 1 - 100 to its u8 (255) equivalent.
+*/
+pub fn convert_to_full(mut value: i32) -> u8 {
+    if value < MINIMUM_TRANSPARENCY {
+        value = MINIMUM_TRANSPARENCY;
+    }
+    if value > 100 {
+        return 255;
+    }
+    ((value as f32 / 100.0) * 255.0).round() as u8
+}
+
+/*
+  Takes a u8 (255) value and converts it to a measurable format (a percentage of 100)
+*/
+pub fn convert_to_human(value: u8) -> u8 {
+    ((value as f32 / 255.0) * 100.0).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::memory::{MemoryBackend, MemoryWindow};
+
+    fn rule(process_name: &str, class_name: &str) -> WindowConfig {
+        WindowConfig::new(
+            &WindowInfo {
+                process_name: process_name.to_owned(),
+                class_name: class_name.to_owned(),
+                ..Default::default()
+            },
+            128,
+        )
+    }
+
+    // Sorted the way Config::get_windows_in_order does
+    fn in_order(mut rules: Vec<WindowConfig>) -> Vec<String> {
+        rules.sort_by(|a, b| a.cmp_precedence(b));
+        rules.iter().map(WindowConfig::get_key).collect()
+    }
+
+    fn process(path: &str, name: &str) -> ProcessIdentity {
+        ProcessIdentity {
+            path: path.to_owned(),
+            name: name.to_owned(),
+            elevated: false,
+        }
+    }
+
+    #[test]
+    fn match_modes_are_part_of_the_key() {
+        let exact = rule("chrome", "Chrome_WidgetWin_1");
+        assert_eq!(exact.get_key(), "chrome|Chrome_WidgetWin_1");
+
+        let mut glob = rule("chrome", "Chrome_*");
+        glob.set_class_match(MatchMode::Glob);
+        let mut substring = rule("chrome", "Chrome_*");
+        substring.set_class_match(MatchMode::Substring);
+        substring.set_process_match(MatchMode::Substring);
+        substring.set_window_title(Some("Private".to_owned()));
+        substring.set_title_match(MatchMode::Regex);
+
+        assert_eq!(glob.get_key(), "chrome|glob:Chrome_*");
+        assert_eq!(
+            substring.get_key(),
+            "substring:chrome|substring:Chrome_*|regex:Private"
+        );
+        assert_ne!(glob.get_key(), rule("chrome", "Chrome_*").get_key());
+    }
+
+    #[test]
+    fn exclusions_do_not_share_a_key_with_rules() {
+        let transparent = rule("chrome", "Chrome_WidgetWin_1");
+        let mut exclusion = transparent.clone();
+        exclusion.set_exclude(true);
+
+        assert_eq!(exclusion.get_key(), "!chrome|Chrome_WidgetWin_1");
+        assert_ne!(exclusion.get_key(), transparent.get_key());
+    }
+
+    #[test]
+    fn inactive_transparency_only_applies_without_the_focus() {
+        let mut window_config = rule("app", "Main");
+        assert_eq!(window_config.get_transparency_for(true), 128);
+        assert_eq!(window_config.get_transparency_for(false), 128);
+
+        window_config.set_inactive_transparency(Some(60));
+        assert_eq!(window_config.get_transparency_for(true), 128);
+        assert_eq!(window_config.get_transparency_for(false), 60);
+
+        // Rules saved before there was an inactive alpha have the one for both
+        let saved: WindowConfig = serde_json::from_str(
+            r#"{"process_name": "app", "window_class": "Main", "transparency": 200, "enabled": true}"#,
+        )
+        .expect("Rules without an inactive alpha still parse");
+        assert_eq!(saved.get_inactive_transparency(), None);
+        assert_eq!(saved.get_transparency_for(false), 200);
+    }
+
+    #[test]
+    fn process_names_match_with_or_without_extension() {
+        let tool = process("C:\\Tools\\My.Tool.exe", "My.Tool");
+
+        assert!(rule("my.tool", "").matches_process(&tool));
+        assert!(rule("MY.TOOL.EXE", "").matches_process(&tool));
+        assert!(!rule("tool", "").matches_process(&tool));
+        assert!(rule("C:/tools/**", "").matches_process(&tool));
+        assert!(rule("c:\\tools\\my.tool.exe", "").matches_process(&tool));
+        assert!(!rule("C:\\Other\\**", "").matches_process(&tool));
+    }
+
+    #[test]
+    fn names_saved_cut_at_the_first_dot_still_match() {
+        let tool = process("C:\\Tools\\my.tool.exe", "my.tool");
+
+        // What versions before full paths saved for my.tool.exe
+        assert!(rule("my", "").matches_process(&tool));
+        assert!(rule("MY", "").matches_process(&tool));
+
+        // Only the whole part before the dot, and only for exact names
+        assert!(!rule("m", "").matches_process(&tool));
+        let mut glob = rule("my", "");
+        glob.set_process_match(MatchMode::Glob);
+        assert!(!glob.matches_process(&tool));
+        assert!(!rule("my.t", "").matches_process(&tool));
+        assert!(!rule("my", "").matches_process(&process("C:\\Tools\\mytool.exe", "mytool")));
+    }
+
+    // How versions before class chains saved a rule, the class of the parent with the child in old_class
+    fn saved_before_chains(force: bool) -> WindowConfig {
+        serde_json::from_str(&format!(
+            r#"{{"process_name":"ApplicationFrameHost","window_class":"ApplicationFrameWindow",
+                "transparency":128,"enabled":true,"force":{},"old_class":"Windows.UI.Core.CoreWindow"}}"#,
+            force
+        ))
+        .expect("Old rules still parse")
+    }
+
+    #[test]
+    fn forced_rule_becomes_a_chain_to_the_child() {
+        let mut window_config = saved_before_chains(true);
+
+        assert!(window_config.migrate_forced());
+        assert_eq!(
+            window_config.get_window_class(),
+            "ApplicationFrameWindow > Windows.UI.Core.CoreWindow"
+        );
+        assert_eq!(window_config.get_apply_to(), 1);
+        assert_eq!(window_config.get_transparency(), 128);
+
+        // Saved again it has neither of the old fields and needs no migration
+        let saved = serde_json::to_string(&window_config).unwrap();
+        assert!(!saved.contains("force") && !saved.contains("old_class"));
+        let mut reloaded: WindowConfig = serde_json::from_str(&saved).unwrap();
+        assert!(!reloaded.migrate_forced());
+        assert_eq!(
+            reloaded.get_window_class(),
+            window_config.get_window_class()
+        );
+    }
+
+    #[test]
+    fn unforced_rule_only_drops_the_old_class() {
+        let mut window_config = saved_before_chains(false);
+
+        assert!(window_config.migrate_forced());
+        assert_eq!(window_config.get_window_class(), "ApplicationFrameWindow");
+        assert_eq!(window_config.get_apply_to(), 0);
+        assert!(!window_config.migrate_forced());
+    }
+
+    #[test]
+    fn migrated_rule_sets_the_alpha_of_the_parent() {
+        let backend = MemoryBackend::new();
+        backend.add_process(1, "ApplicationFrameHost", false);
+        let frame = backend.add_window(MemoryWindow {
+            class_name: "ApplicationFrameWindow".to_owned(),
+            process_id: 1,
+            ..Default::default()
+        });
+        backend.add_window(MemoryWindow {
+            class_name: "Windows.UI.Core.CoreWindow".to_owned(),
+            process_id: 1,
+            parent: Some(frame),
+            ..Default::default()
+        });
+        // A frame without the child is not what the rule was picked from
+        backend.add_window(MemoryWindow {
+            class_name: "ApplicationFrameWindow".to_owned(),
+            process_id: 1,
+            ..Default::default()
+        });
+
+        let mut window_config = saved_before_chains(true);
+        window_config.migrate_forced();
+
+        assert_eq!(window_config.get_window_hwnds(&backend), vec![frame]);
+    }
+
+    #[test]
+    fn title_is_more_specific_than_everything_else() {
+        let mut titled = rule("app.exe", "App*");
+        titled.set_class_match(MatchMode::Glob);
+        titled.set_window_title(Some("Private".to_owned()));
+        titled.set_title_match(MatchMode::Substring);
+
+        let mut narrow = rule("C:\\Apps\\app.exe", "App > Main > Content");
+        narrow.set_roles(vec![WindowRole::Main]);
+        narrow.set_monitor(Some(MonitorMatch::Index(1)));
+
+        assert!(titled.get_specificity() > narrow.get_specificity());
+    }
+
+    #[test]
+    fn exact_is_more_specific_than_a_pattern() {
+        let exact = rule("app.exe", "AppWindow");
+        let mut glob = rule("app.exe", "App*");
+        glob.set_class_match(MatchMode::Glob);
+        let mut regex = rule("app.exe", "^App");
+        regex.set_class_match(MatchMode::Regex);
+
+        assert!(exact.get_specificity() > glob.get_specificity());
+        assert!(glob.get_specificity() > regex.get_specificity());
+
+        let mut exact_title = rule("app.exe", "AppWindow");
+        exact_title.set_window_title(Some("Inbox".to_owned()));
+        let mut pattern_title = exact_title.clone();
+        pattern_title.set_title_match(MatchMode::Regex);
+        assert!(exact_title.get_specificity() > pattern_title.get_specificity());
+    }
+
+    #[test]
+    fn narrower_roles_monitors_chains_and_paths_are_more_specific() {
+        let base = rule("app.exe", "AppWindow");
+
+        let mut one_role = base.clone();
+        one_role.set_roles(vec![WindowRole::Dialog]);
+        let mut two_roles = base.clone();
+        two_roles.set_roles(vec![WindowRole::Dialog, WindowRole::Popup]);
+        assert!(one_role.get_specificity() > two_roles.get_specificity());
+        assert!(two_roles.get_specificity() > base.get_specificity());
+
+        let mut named = base.clone();
+        named.set_monitor(Some(MonitorMatch::Name("DP-1".to_owned())));
+        let mut secondary = base.clone();
+        secondary.set_monitor(Some(MonitorMatch::Secondary));
+        assert!(named.get_specificity() > secondary.get_specificity());
+        assert!(secondary.get_specificity() > base.get_specificity());
+
+        let chain = rule("app.exe", "Frame > AppWindow");
+        assert!(chain.get_specificity() > base.get_specificity());
+
+        let path = rule("C:\\Apps\\app.exe", "AppWindow");
+        assert!(path.get_specificity() > base.get_specificity());
+    }
+
+    #[test]
+    fn precedence_is_exclusions_then_priority_then_specificity() {
+        let broad = rule("app.exe", "App*");
+        let mut narrow = rule("app.exe", "AppWindow");
+        narrow.set_window_title(Some("Inbox".to_owned()));
+
+        let mut preferred = rule("app.exe", "Other");
+        preferred.set_class_match(MatchMode::Substring);
+        preferred.set_priority(1);
+
+        let mut exclusion = rule("app.exe", "Excluded");
+        exclusion.set_exclude(true);
+        exclusion.set_priority(-5);
+
+        let mut disabled_exclusion = rule("app.exe", "Off");
+        disabled_exclusion.set_exclude(true);
+        disabled_exclusion.set_enabled(false);
+        disabled_exclusion.set_priority(-5);
+
+        assert_eq!(
+            in_order(vec![
+                disabled_exclusion.clone(),
+                broad.clone(),
+                narrow.clone(),
+                preferred.clone(),
+                exclusion.clone(),
+            ]),
+            vec![
+                exclusion.get_key(),
+                preferred.get_key(),
+                narrow.get_key(),
+                broad.get_key(),
+                disabled_exclusion.get_key(),
+            ]
+        );
+    }
+
+    #[test]
+    fn ties_are_broken_by_key_whatever_the_order() {
+        let first = rule("a.exe", "AppWindow");
+        let second = rule("b.exe", "AppWindow");
+
+        assert_eq!(first.cmp_precedence(&second), Ordering::Less);
+        assert_eq!(second.cmp_precedence(&first), Ordering::Greater);
+        assert_eq!(first.cmp_precedence(&first.clone()), Ordering::Equal);
+        assert_eq!(
+            in_order(vec![second.clone(), first.clone()]),
+            in_order(vec![first, second])
+        );
+    }
+
+    #[test]
+    fn titles_are_matched_by_their_mode() {
+        let backend = MemoryBackend::new();
+        let inbox = backend.add_window(MemoryWindow {
+            title: "Inbox - Mail".to_owned(),
+            ..Default::default()
+        });
+        let compose = backend.add_window(MemoryWindow {
+            title: "Compose".to_owned(),
+            ..Default::default()
+        });
+
+        let mut titled = rule("mail.exe", "MailWindow");
+        assert!(titled.matches_title(&backend, inbox));
+        assert!(titled.matches_title(&backend, compose));
+
+        titled.set_window_title(Some("Inbox".to_owned()));
+        assert!(!titled.matches_title(&backend, inbox));
+
+        titled.set_title_match(MatchMode::Substring);
+        assert!(titled.matches_title(&backend, inbox));
+        assert!(!titled.matches_title(&backend, compose));
+
+        titled.set_window_title(Some("* - Mail".to_owned()));
+        titled.set_title_match(MatchMode::Glob);
+        assert!(titled.matches_title(&backend, inbox));
+        assert!(!titled.matches_title(&backend, compose));
+
+        titled.set_window_title(Some("^Comp".to_owned()));
+        titled.set_title_match(MatchMode::Regex);
+        assert!(titled.matches_title(&backend, compose));
+        assert!(!titled.matches_title(&backend, inbox));
+    }
+
+    #[test]
+    fn a_closed_window_has_no_title_to_match() {
+        let backend = MemoryBackend::new();
+        let window = backend.add_window(MemoryWindow::default());
+        backend.remove_window(window);
+
+        let mut titled = rule("mail.exe", "MailWindow");
+        assert!(titled.matches_title(&backend, window));
+        titled.set_window_title(Some(String::new()));
+        titled.set_title_match(MatchMode::Substring);
+        assert!(!titled.matches_title(&backend, window));
+    }
+}
@@ -0,0 +1,202 @@
+use crate::backend::{ProcessIdentity, WindowBackend, WindowHandle};
+use std::collections::{HashMap, HashSet};
+
+/*
+  Windows of one executable that share a class.
+*/
+#[derive(Debug, Clone)]
+pub struct IndexedWindows {
+    pub process: ProcessIdentity,
+    pub class_name: String,
+    pub handles: Vec<WindowHandle>,
+}
+
+/*
+  Every visible top level window on the desktop, grouped by (executable path, window class).
+  Built with one enumeration pass per refresh so resolving a rule is a pass over the groups instead of another walk over the desktop.
+*/
+#[derive(Default, Debug, Clone)]
+pub struct WindowIndex {
+    windows: Vec<IndexedWindows>,
+    // Every top level window, hidden ones included
+    existing: HashSet<WindowHandle>,
+}
+
+impl WindowIndex {
+    pub fn build(backend: &dyn WindowBackend) -> Self {
+        let mut groups: HashMap<(String, String), IndexedWindows> = HashMap::new();
+        let mut existing = HashSet::new();
+
+        for handle in backend.enumerate_windows() {
+            existing.insert(handle);
+
+            if !backend.is_window_visible(handle) {
+                continue;
+            }
+
+            let Some(class_name) = backend.get_window_class(handle) else {
+                continue;
+            };
+            let Some(process) = backend.get_window_process_identity(handle) else {
+                continue;
+            };
+
+            // Without a path the name is all that tells processes apart
+            let process_key = if process.path.is_empty() {
+                process.name.clone()
+            } else {
+                process.path.clone()
+            };
+
+            groups
+                .entry((process_key, class_name.clone()))
+                .or_insert_with(|| IndexedWindows {
+                    process,
+                    class_name,
+                    handles: Vec::new(),
+                })
+                .handles
+                .push(handle);
+        }
+
+        Self {
+            windows: groups.into_values().collect(),
+            existing,
+        }
+    }
+
+    pub fn contains(&self, window: WindowHandle) -> bool {
+        self.existing.contains(&window)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &IndexedWindows> {
+        self.windows.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::memory::{MemoryBackend, MemoryWindow};
+
+    fn add_window(backend: &MemoryBackend, process_id: u32, class_name: &str) -> WindowHandle {
+        backend.add_window(MemoryWindow {
+            class_name: class_name.to_owned(),
+            process_id,
+            ..Default::default()
+        })
+    }
+
+    // Every group as (path, class, handles), sorted so the order of the HashMap does not matter
+    fn groups(index: &WindowIndex) -> Vec<(String, String, Vec<WindowHandle>)> {
+        let mut groups: Vec<_> = index
+            .iter()
+            .map(|group| {
+                let mut handles = group.handles.clone();
+                handles.sort();
+                (
+                    group.process.path.clone(),
+                    group.class_name.clone(),
+                    handles,
+                )
+            })
+            .collect();
+        groups.sort();
+        groups
+    }
+
+    #[test]
+    fn windows_are_grouped_by_executable_and_class() {
+        let backend = MemoryBackend::new();
+        backend.add_process_with_path(1, "app.exe", r"C:\One\app.exe", false);
+        backend.add_process_with_path(2, "app.exe", r"C:\Two\app.exe", false);
+        backend.add_process_with_path(3, "app.exe", r"C:\One\app.exe", false);
+        let first = add_window(&backend, 1, "Main");
+        let dialog = add_window(&backend, 1, "Dialog");
+        let other_path = add_window(&backend, 2, "Main");
+        let same_path = add_window(&backend, 3, "Main");
+
+        let index = WindowIndex::build(&backend);
+
+        let mut first_group = vec![first, same_path];
+        first_group.sort();
+        assert_eq!(
+            groups(&index),
+            vec![
+                (
+                    r"C:\One\app.exe".to_owned(),
+                    "Dialog".to_owned(),
+                    vec![dialog]
+                ),
+                (r"C:\One\app.exe".to_owned(), "Main".to_owned(), first_group),
+                (
+                    r"C:\Two\app.exe".to_owned(),
+                    "Main".to_owned(),
+                    vec![other_path]
+                ),
+            ]
+        );
+        assert!([first, dialog, other_path, same_path]
+            .iter()
+            .all(|&window| index.contains(window)));
+    }
+
+    #[test]
+    fn hidden_and_child_windows_are_not_grouped() {
+        let backend = MemoryBackend::new();
+        backend.add_process(1, "app", false);
+        let shown = add_window(&backend, 1, "Main");
+        let hidden = backend.add_window(MemoryWindow {
+            class_name: "Main".to_owned(),
+            process_id: 1,
+            hidden: true,
+            ..Default::default()
+        });
+        let child = backend.add_window(MemoryWindow {
+            class_name: "Main".to_owned(),
+            process_id: 1,
+            parent: Some(shown),
+            ..Default::default()
+        });
+
+        let index = WindowIndex::build(&backend);
+
+        assert_eq!(
+            groups(&index),
+            vec![("app".to_owned(), "Main".to_owned(), vec![shown])]
+        );
+        // Hidden windows still exist, their rules are kept
+        assert!(index.contains(hidden));
+        assert!(!index.contains(child));
+    }
+
+    #[test]
+    fn rebuilding_follows_destroyed_windows_and_class_changes() {
+        let backend = MemoryBackend::new();
+        backend.add_process(1, "app", false);
+        let kept = add_window(&backend, 1, "Main");
+        let destroyed = add_window(&backend, 1, "Main");
+        let renamed = add_window(&backend, 1, "Main");
+        WindowIndex::build(&backend);
+
+        backend.remove_window(destroyed);
+        backend.update_window(renamed, |window| window.class_name = "Other".to_owned());
+        let index = WindowIndex::build(&backend);
+
+        assert_eq!(
+            groups(&index),
+            vec![
+                ("app".to_owned(), "Main".to_owned(), vec![kept]),
+                ("app".to_owned(), "Other".to_owned(), vec![renamed]),
+            ]
+        );
+        assert!(!index.contains(destroyed));
+        assert!(index.contains(renamed));
+
+        // A window whose process is gone is dropped with it
+        backend.remove_process(1);
+        let index = WindowIndex::build(&backend);
+        assert!(groups(&index).is_empty());
+        assert!(!index.contains(kept));
+    }
+}